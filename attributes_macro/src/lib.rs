@@ -1,12 +1,34 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
+use proc_macro_crate::{crate_name, FoundCrate};
 use quote::{format_ident, quote};
-use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, Meta, Variant};
-
-
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, Variant};
+
+// The categories recognised on variants and the `ModOp` each one maps to.
+// Using the `ModOp` channels keeps plain `ModOp` modifiers working on the same attributes.
+const CATEGORIES: [(&str, &str); 4] = [
+    ("set", "Set"),
+    ("additive", "Add"),
+    ("increased", "Increase"),
+    ("multiplicative", "Mul"),
+];
+
+/// Declares a modifier enum whose variants are sorted into categories.
+///
+/// Each variant holds a single value convertible to `f64` and is tagged with one of
+/// `#[set]`, `#[additive]`, `#[increased]` or `#[multiplicative]`.
+/// Implements `vitality::modifier::ModifierCategories` so the enum can drive the aggregation
+/// of attributes.
 #[proc_macro_attribute]
-pub fn attribute_calculator(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn attribute_calculator(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        let attr = proc_macro2::TokenStream::from(attr);
+        return Error::new_spanned(attr, "attribute_calculator does not take arguments")
+            .to_compile_error()
+            .into();
+    }
+
     // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(item as DeriveInput);
 
@@ -17,19 +39,30 @@ pub fn attribute_calculator(_attr: TokenStream, item: TokenStream) -> TokenStrea
     }
 }
 
-fn generate_calculator_code(input: DeriveInput) -> Result<TokenStream, syn::Error> {
+fn generate_calculator_code(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
     // We expect the macro to be on an enum.
-    let enum_data = if let Data::Enum(ref data) = input.data {
-        data
-    } else {
+    let Data::Enum(ref enum_data) = input.data else {
         return Err(Error::new_spanned(
-            input,
-            "AttributeCalculator can only be used on enums",
+            &input.ident,
+            "attribute_calculator can only be used on enums",
         ));
     };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "attribute_calculator does not support generic enums",
+        ));
+    }
+    if enum_data.variants.is_empty() {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "attribute_calculator requires at least one variant",
+        ));
+    }
 
     let enum_name = &input.ident;
-    //let generics = &input.generics;
+    let enum_vis = &input.vis;
+    let enum_attrs = &input.attrs;
 
     // A helper struct to hold the variants sorted by their category.
     struct CategorizedVariants<'a> {
@@ -46,176 +79,163 @@ fn generate_calculator_code(input: DeriveInput) -> Result<TokenStream, syn::Erro
         multiplicatives: Vec::new(),
     };
 
-    println!("PROC_MACRO");
-
     // Iterate over each variant of the enum (e.g., `BaseSet(f64)`).
     for variant in &enum_data.variants {
         // Each variant must hold a single unnamed field (e.g., `(f64)`).
-        if let Fields::Unnamed(fields) = &variant.fields {
-            if fields.unnamed.len() != 1 {
+        match &variant.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {}
+            Fields::Unnamed(fields) => {
                 return Err(Error::new_spanned(
-                    variant,
+                    fields,
                     "Variant must have exactly one unnamed field",
                 ));
             }
-        } else {
-            return Err(Error::new_spanned(
-                variant,
-                "Variant must be a tuple-style variant with one field",
-            ));
-        }
-
-        // Find the `#[category(...)]` attribute and classify the variant.
-        let category = get_category_from_attributes(&variant.attrs)?;
-        match category.as_str() {
-            "set" => categorized.overrides.push(variant),
-            "additive" => categorized.additives.push(variant),
-            "increased" => categorized.increased.push(variant),
-            "multiplicative" => categorized.multiplicatives.push(variant),
             _ => {
                 return Err(Error::new_spanned(
                     variant,
-                    format!("Unknown category: '{}'", category),
+                    "Variant must be a tuple-style variant with one field, e.g. `Flat(f64)`",
                 ));
             }
         }
+
+        // Find the category attribute and classify the variant.
+        match get_category_from_attributes(variant)? {
+            "set" => categorized.overrides.push(variant),
+            "additive" => categorized.additives.push(variant),
+            "increased" => categorized.increased.push(variant),
+            _ => categorized.multiplicatives.push(variant),
+        }
     }
 
     // Generate the enum without the custom attributes
-    let variants_without_attrs: Vec<_> = enum_data.variants.iter().map(|variant| {
+    let variants_without_attrs = enum_data.variants.iter().map(|variant| {
         let variant_name = &variant.ident;
         let fields = &variant.fields;
 
         // Remove our custom attributes
-        let filtered_attrs: Vec<_> = variant.attrs.iter()
-            .filter(|attr| !is_custom_attribute(attr))
-            .collect();
+        let filtered_attrs = variant
+            .attrs
+            .iter()
+            .filter(|attr| category_of(attr).is_none());
 
         quote! {
             #(#filtered_attrs)*
             #variant_name #fields
         }
-    }).collect();
-
-    // --- 2. CODE GENERATION ---
-
-    // Generate the names for the new structs based on the input enum's name.
-    let calculator_name = format_ident!("{}Calculator", enum_name);
-    let aggregator_name = format_ident!("{}Aggregator", enum_name);
-
-    // Helper to handle generics correctly.
-    //let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-
-    // Generate the match arms for the `from_iter` method.
-    let override_arms = categorized.overrides.iter().map(|v| {
-        let variant_name = &v.ident;
-        quote! { #enum_name::#variant_name(value) => { final_set = Some(*value); } }
     });
-    let additive_arms = categorized.additives.iter().map(|v| {
-        let variant_name = &v.ident;
-        quote! { #enum_name::#variant_name(value) => { total_additive += value; } }
-    });
-    let increased_arms = categorized.increased.iter().map(|v| {
-        let variant_name = &v.ident;
-        quote! { #enum_name::#variant_name(value) => { total_increased += value; } }
-    });
-    let multiplicative_arms = categorized.multiplicatives.iter().map(|v| {
+
+    let vitality = vitality_path();
+
+    // Generate the match arms for `ModifierCategories`.
+    let operation_of = |variants: &[&Variant], category: &str| {
+        let (_, operation) = CATEGORIES
+            .iter()
+            .find(|(name, _)| *name == category)
+            .expect("Known category");
+        let operation = format_ident!("{}", operation);
+        variants
+            .iter()
+            .map(|v| {
+                let variant_name = &v.ident;
+                quote! { #enum_name::#variant_name(_) => #vitality::modifier::ModOp::#operation, }
+            })
+            .collect::<Vec<_>>()
+    };
+    let operation_arms = [
+        operation_of(&categorized.overrides, "set"),
+        operation_of(&categorized.additives, "additive"),
+        operation_of(&categorized.increased, "increased"),
+        operation_of(&categorized.multiplicatives, "multiplicative"),
+    ]
+    .concat();
+    let magnitude_arms = enum_data.variants.iter().map(|v| {
         let variant_name = &v.ident;
-        quote! { #enum_name::#variant_name(value) => { total_multiplicative *= (1.0 + value); } }
+        if categorized.multiplicatives.iter().any(|m| m.ident == v.ident) {
+            // Multiplicative values are percentages and the channel multiplies the factors
+            quote! { #enum_name::#variant_name(value) => 1.0 + f64::from(*value), }
+        } else {
+            quote! { #enum_name::#variant_name(value) => f64::from(*value), }
+        }
     });
 
     // Use the `quote!` macro to build the final TokenStream.
     let generated_code = quote! {
-        // Pass through the original enum definition untouched.
-        #[derive(Debug, Clone)]
-        pub enum #enum_name {
+        #(#enum_attrs)*
+        #enum_vis enum #enum_name {
             #(#variants_without_attrs),*
         }
 
-
-        // --- GENERATED CALCULATOR STRUCT ---
-        #[derive(Debug, Clone)]
-        pub struct #calculator_name {
-            pub set: Option<f64>,
-            pub additive: f64,
-            pub increased: f64,
-            pub multiplicative: f64,
-        }
-
-        impl #calculator_name {
-            pub fn calculate(&self, base_value: f64) -> f64 {
-                if let Some(set_value) = self.set {
-                    return set_value;
-                }
-
-                let after_additive = base_value + self.additive;
-                let after_increased = after_additive * (1.0 + self.increased);
-                let after_multiplicative = after_increased * self.multiplicative;
-
-                after_multiplicative
+        impl #vitality::modifier::ModifierCategories for #enum_name {
+            fn pipeline() -> #vitality::modifier::AggregationPipeline {
+                use #vitality::modifier::{AggregationPipeline, ModOp, StageKind};
+                AggregationPipeline::empty()
+                    .stage(ModOp::Set.channel(), StageKind::Override)
+                    .stage(ModOp::Add.channel(), StageKind::Add)
+                    .stage(ModOp::Increase.channel(), StageKind::Increase)
+                    .stage(ModOp::Mul.channel(), StageKind::Multiply)
             }
-        }
 
-        // --- GENERATED AGGREGATOR STRUCT ---
-        pub struct #aggregator_name;
-
-        impl #aggregator_name {
-            pub fn from_iter<'a>(modifiers: impl IntoIterator<Item = &'a #enum_name>) -> #calculator_name {
-                let mut final_set: Option<f64> = None;
-                let mut total_additive: f64 = 0.0;
-                let mut total_increased: f64 = 0.0;
-                let mut total_multiplicative: f64 = 1.0;
-
-                for modifier in modifiers {
-                    match modifier {
-                        #( #override_arms )*
-                        #( #additive_arms )*
-                        #( #increased_arms )*
-                        #( #multiplicative_arms )*
-                    }
+            fn operation(&self) -> #vitality::modifier::ModOp {
+                match self {
+                    #( #operation_arms )*
                 }
+            }
 
-                #calculator_name {
-                    set: final_set,
-                    additive: total_additive,
-                    increased: total_increased,
-                    multiplicative: total_multiplicative,
+            fn magnitude(&self) -> f64 {
+                match self {
+                    #( #magnitude_arms )*
                 }
             }
         }
     };
 
-    Ok(TokenStream::from(generated_code))
+    Ok(generated_code)
 }
 
-fn is_custom_attribute(attr: &Attribute) -> bool {
-    if let Meta::Path(path) = &attr.meta {
-        path.is_ident("set") ||
-            path.is_ident("additive") ||
-            path.is_ident("multiplicative")
-    } else {
-        false
+// The path of the vitality crate, following renames in the manifest of the user.
+// Inside vitality, its tests and its examples, `::vitality` resolves to the crate itself.
+fn vitality_path() -> proc_macro2::TokenStream {
+    match crate_name("vitality") {
+        Ok(FoundCrate::Itself) | Err(_) => quote!(::vitality),
+        Ok(FoundCrate::Name(name)) => {
+            let name = format_ident!("{}", name);
+            quote!(::#name)
+        }
     }
 }
 
+// Returns the category of a `#[set]`, `#[additive]`, `#[increased]` or `#[multiplicative]` attribute.
+fn category_of(attr: &Attribute) -> Option<&'static str> {
+    CATEGORIES
+        .iter()
+        .map(|(category, _)| *category)
+        .find(|category| attr.path().is_ident(category))
+}
 
-/// Helper function to parse `#[category(...)]` attributes.
-fn get_category_from_attributes(attrs: &[Attribute]) -> Result<String, Error> {
-    for attr in attrs {
-        println!("attr: {:?}", attr.path().get_ident());
-        if attr.path().is_ident("set")
-            || attr.path().is_ident("additive")
-            || attr.path().is_ident("multiplicative")
-        {
-            return Ok(attr
-                .path()
-                .get_ident()
-                .expect("Expected `category`")
-                .to_string());
-        }
+/// Finds the single category attribute of a variant.
+fn get_category_from_attributes(variant: &Variant) -> Result<&'static str, Error> {
+    let mut categories = variant
+        .attrs
+        .iter()
+        .filter_map(|attr| category_of(attr).map(|category| (attr, category)));
+
+    let Some((attr, category)) = categories.next() else {
+        return Err(Error::new_spanned(
+            &variant.ident,
+            "Missing category, expected one of #[set], #[additive], #[increased] or #[multiplicative]",
+        ));
+    };
+    if let Some((duplicate, _)) = categories.next() {
+        return Err(Error::new_spanned(
+            duplicate,
+            "A variant can only have one category",
+        ));
+    }
+    if !matches!(attr.meta, syn::Meta::Path(_)) {
+        return Err(Error::new_spanned(
+            attr,
+            format!("#[{}] does not take arguments", category),
+        ));
     }
-    Err(Error::new_spanned(
-        attrs.first().expect("Expected at least one attribute"),
-        "Missing required `#[category(...)]` attribute",
-    ))
+    Ok(category)
 }
@@ -0,0 +1,297 @@
+use crate::ability::systems::{commit_ability, execute_ability, AbilityInstance};
+use crate::ability::{
+    Ability, AbilityCancel, AbilityDuration, AbilityOf, CancelReason, CommitPolicy, EndAbility,
+    ExecuteAbility, GrantedAbilities,
+};
+use crate::assets::AbilityDef;
+use crate::attributes::Attribute;
+use crate::context::AbilityExprContext;
+use crate::inspector::pretty_type_name;
+use crate::tags::GameplayTagAdded;
+use crate::{AppAttributeBindings, AttributesMut, BaseValueChanged};
+use bevy::ecs::entity::EntityHashSet;
+use bevy::ecs::resource::IsResource;
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// An ability that began and has not ended yet.
+#[derive(Component, Debug)]
+pub struct ActiveAbility {
+    target: Entity,
+    elapsed: Duration,
+    /// Executes channeled abilities.
+    ticker: Option<Timer>,
+}
+
+impl ActiveAbility {
+    /// Fails if a channeled ability does not take some time between executions.
+    pub(crate) fn new(target: Entity, duration: &AbilityDuration) -> Result<Self, BevyError> {
+        let ticker = match duration {
+            AbilityDuration::Channeled { interval, .. } => {
+                match Duration::try_from_secs_f32(*interval) {
+                    Ok(interval) if !interval.is_zero() => {
+                        Some(Timer::new(interval, TimerMode::Repeating))
+                    }
+                    _ => return Err(format!("Invalid channel interval {}", interval).into()),
+                }
+            }
+            _ => None,
+        };
+        Ok(Self {
+            target,
+            elapsed: Duration::ZERO,
+            ticker,
+        })
+    }
+
+    pub fn target(&self) -> Entity {
+        self.target
+    }
+
+    /// The time since the ability began.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Abilities accepted for activation this frame, whose [`ActiveAbility`] may not be inserted yet.
+/// Keeps a second activation in the same frame from passing the already active check.
+#[derive(Resource, Default)]
+pub(crate) struct PendingActivations(pub EntityHashSet);
+
+pub(crate) fn clear_pending_activations(mut pending: ResMut<PendingActivations>) {
+    pending.0.clear();
+}
+
+type InterruptionFn = dyn Fn(&mut Commands, Entity, Entity) + Send + Sync;
+
+/// Watches the caster of an ability to cancel the ability while it is active.
+pub struct AbilityInterruption(Box<InterruptionFn>);
+
+impl AbilityInterruption {
+    /// Cancels the ability when the base value of the attribute of the caster decreases,
+    /// such as when it takes damage. Changes made by the ability itself, such as paying
+    /// its costs, do not interrupt it.
+    pub fn on_decrease<T: Attribute>() -> Self {
+        Self(Box::new(|commands, caster, ability| {
+            let mut observer = Observer::new(
+                move |trigger: On<BaseValueChanged<T>>,
+                      active: Query<(), With<ActiveAbility>>,
+                      mut commands: Commands| {
+                    let own_change = trigger.effect == Some(ability);
+                    if trigger.new < trigger.old && !own_change && active.contains(ability) {
+                        commands.trigger(AbilityCancel {
+                            ability,
+                            source: caster,
+                            reason: CancelReason::Interrupted,
+                        });
+                    }
+                },
+            );
+            observer.watch_entity(caster);
+
+            commands.spawn((
+                observer,
+                ChildOf(ability),
+                Name::new(format!("Interrupt<{}>", pretty_type_name::<T>())),
+            ));
+        }))
+    }
+
+    /// Starts watching the caster. The watch is removed with the ability.
+    pub(crate) fn apply(&self, commands: &mut Commands, caster: Entity, ability: Entity) {
+        (self.0)(commands, caster, ability);
+    }
+}
+
+/// Ends an active ability, or cancels it if there is a reason.
+#[derive(EntityEvent)]
+pub(crate) struct FinishAbility {
+    #[event_target]
+    pub ability: Entity,
+    pub cancel: Option<CancelReason>,
+}
+
+/// Executes channeled abilities and ends abilities whose duration ran out or whose
+/// conditions no longer hold.
+pub(crate) fn tick_active_abilities(
+    mut abilities: Query<(Entity, &AbilityOf, &Ability, &mut ActiveAbility)>,
+    mut actors: Query<AttributesMut<'static, 'static>, Without<IsResource>>,
+    ability_assets: Res<Assets<AbilityDef>>,
+    mut commands: Commands,
+    type_registry: Res<AppTypeRegistry>,
+    type_bindings: Res<AppAttributeBindings>,
+    time: Res<Time>,
+) {
+    for (ability_entity, ability_of, ability, mut active) in abilities.iter_mut() {
+        let Some(ability_spec) = ability_assets.get(&ability.0) else {
+            continue;
+        };
+        let instance = AbilityInstance {
+            source: ability_of.0,
+            target: active.target,
+            ability: ability_entity,
+            ability_def: ability.0.id(),
+        };
+        active.elapsed += time.delta();
+
+        let ticks = active.ticker.as_mut().map_or(0, |ticker| {
+            ticker.tick(time.delta());
+            ticker.times_finished_this_tick()
+        });
+        for _ in 0..ticks {
+            if let Err(err) = execute_ability(
+                &mut actors,
+                ability_spec,
+                instance,
+                &type_registry.0,
+                &type_bindings,
+                &mut commands,
+            ) {
+                error!("{}: Failed to execute channeled ability: {}", ability_entity, err);
+            }
+            commands.trigger(ExecuteAbility {
+                source: instance.source,
+                target: instance.target,
+                ability: ability_entity,
+            });
+        }
+
+        let expired = ability_spec
+            .duration
+            .max_duration()
+            .is_some_and(|duration| active.elapsed >= duration);
+
+        let holds = || -> Result<bool, BevyError> {
+            let [caster_ref, target_ref, ability_ref] =
+                actors.get_many([instance.source, instance.target, ability_entity])?;
+            let context = AbilityExprContext {
+                caster_ref: &caster_ref,
+                target_ref: &target_ref,
+                ability_ref: &ability_ref,
+                ability_def: instance.ability_def,
+                type_registry: type_registry.0.clone(),
+            };
+            for condition in &ability_spec.active_conditions {
+                if !condition.eval(&context)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        };
+
+        if expired || !holds().unwrap_or(false) {
+            commands.trigger(FinishAbility {
+                ability: ability_entity,
+                cancel: None,
+            });
+        }
+    }
+}
+
+/// Cancels an active ability.
+pub(crate) fn cancel_ability(trigger: On<AbilityCancel>, mut commands: Commands) {
+    commands.trigger(FinishAbility {
+        ability: trigger.ability,
+        cancel: Some(trigger.reason.clone()),
+    });
+}
+
+/// Ends an active ability. Held abilities execute as they end, unless they are cancelled.
+pub(crate) fn finish_ability(
+    trigger: On<FinishAbility>,
+    abilities: Query<(&Ability, &AbilityOf, &ActiveAbility)>,
+    mut actors: Query<AttributesMut<'static, 'static>, Without<IsResource>>,
+    ability_assets: Res<Assets<AbilityDef>>,
+    mut commands: Commands,
+    type_registry: Res<AppTypeRegistry>,
+    type_bindings: Res<AppAttributeBindings>,
+) -> Result<(), BevyError> {
+    let Ok((ability, ability_of, active)) = abilities.get(trigger.ability) else {
+        // The ability is not active
+        return Ok(());
+    };
+    let ability_spec = ability_assets
+        .get(&ability.0)
+        .ok_or("No ability asset")?;
+    let instance = AbilityInstance {
+        source: ability_of.0,
+        target: active.target,
+        ability: trigger.ability,
+        ability_def: ability.0.id(),
+    };
+
+    match &trigger.cancel {
+        Some(reason) => debug!("{}: Cancel ability: {:?}", trigger.ability, reason),
+        None => {
+            debug!("{}: End ability", trigger.ability);
+            if matches!(ability_spec.duration, AbilityDuration::Held { .. }) {
+                execute_ability(
+                    &mut actors,
+                    ability_spec,
+                    instance,
+                    &type_registry.0,
+                    &type_bindings,
+                    &mut commands,
+                )?;
+                commands.trigger(ExecuteAbility {
+                    source: instance.source,
+                    target: instance.target,
+                    ability: trigger.ability,
+                });
+            }
+            if ability_spec.commit == CommitPolicy::OnEnd {
+                commit_ability(
+                    &mut actors,
+                    ability_spec,
+                    instance,
+                    &type_registry.0,
+                    &type_bindings,
+                    &mut commands,
+                )?;
+            }
+        }
+    }
+
+    // Held abilities keep their charging time while they execute
+    commands.entity(trigger.ability).remove::<ActiveAbility>();
+    commands.trigger(EndAbility {
+        source: instance.source,
+        ability: trigger.ability,
+        cancelled: trigger.cancel.is_some(),
+    });
+    Ok(())
+}
+
+/// Cancels the active abilities of an actor when it gains a tag cancelling them.
+pub(crate) fn cancel_abilities_on_tag(
+    trigger: On<GameplayTagAdded>,
+    actors: Query<&GrantedAbilities>,
+    abilities: Query<&Ability, With<ActiveAbility>>,
+    ability_assets: Res<Assets<AbilityDef>>,
+    mut commands: Commands,
+) {
+    let Ok(granted) = actors.get(trigger.entity) else {
+        return;
+    };
+    for &ability_entity in granted.0.iter() {
+        let Some(ability_spec) = abilities
+            .get(ability_entity)
+            .ok()
+            .and_then(|ability| ability_assets.get(&ability.0))
+        else {
+            continue;
+        };
+        if let Some(tag) = ability_spec
+            .cancelled_by_tags
+            .iter()
+            .find(|tag| trigger.tag.matches(tag))
+        {
+            commands.trigger(AbilityCancel {
+                ability: ability_entity,
+                source: trigger.entity,
+                reason: CancelReason::Tag(tag.clone()),
+            });
+        }
+    }
+}
@@ -1,24 +1,36 @@
-use crate::ability::AbilityCooldown;
+use crate::ability::{
+    AbilityCharges, AbilityCooldown, AbilityCost, AbilityDuration, AbilityInterruption, Charges,
+    CommitPolicy, CooldownGroup,
+};
 use crate::assets::AbilityDef;
 use crate::attributes::Attribute;
 use crate::context::{AbilityExprSchema, EffectExprSchema};
 use crate::inspector::pretty_type_name;
 use crate::modifier::{AttributeCalculatorCached, EffectSubject};
 use crate::mutator::EntityActions;
+use crate::tags::GameplayTag;
 use bevy::ecs::system::IntoObserverSystem;
 use bevy::prelude::*;
 use express_it::expr::Expr;
 use express_it::frame::LazyPlan;
-use express_it::logic::{BoolExpr, CompareExpr};
+use express_it::logic::BoolExpr;
 use num_traits::{AsPrimitive, Num};
 
 pub struct AbilityBuilder {
     name: String,
     mutators: Vec<EntityActions>,
     triggers: Vec<EntityActions>,
-    cost_condition: Vec<BoolExpr<AbilityExprSchema>>,
+    execution_conditions: Vec<BoolExpr<AbilityExprSchema>>,
+    blocked_tags: Vec<GameplayTag>,
+    costs: Vec<AbilityCost>,
     cost_modifiers: LazyPlan,
+    cooldown_groups: Vec<(CooldownGroup, Expr<f64, EffectExprSchema>)>,
     on_execute: Vec<LazyPlan>,
+    duration: AbilityDuration,
+    commit: CommitPolicy,
+    active_conditions: Vec<BoolExpr<AbilityExprSchema>>,
+    cancelled_by_tags: Vec<GameplayTag>,
+    interruptions: Vec<AbilityInterruption>,
 }
 
 impl AbilityBuilder {
@@ -27,9 +39,17 @@ impl AbilityBuilder {
             name: "Ability".to_string(),
             mutators: Default::default(),
             triggers: vec![],
-            cost_condition: vec![],
+            execution_conditions: vec![],
+            blocked_tags: vec![],
+            costs: vec![],
             cost_modifiers: LazyPlan::new(),
+            cooldown_groups: vec![],
             on_execute: vec![],
+            duration: AbilityDuration::Instant,
+            commit: CommitPolicy::OnStart,
+            active_conditions: vec![],
+            cancelled_by_tags: vec![],
+            interruptions: vec![],
         }
     }
 
@@ -45,16 +65,24 @@ impl AbilityBuilder {
         self
     }
 
-    pub fn with_cost<T: Attribute>(mut self, cost: impl Into<Expr<T::Property, AbilityExprSchema>>) -> Self
-    where
-        Expr<T::Property, AbilityExprSchema>: CompareExpr<AbilityExprSchema>,
-    {
+    pub fn with_cost<T: Attribute>(mut self, cost: impl Into<Expr<T::Property, AbilityExprSchema>>) -> Self {
         let cost_expr = cost.into();
         let cost_assignment = T::sub(EffectSubject::Source, cost_expr.clone());
         self.cost_modifiers = self.cost_modifiers.step(cost_assignment);
 
-        let cost_expr = cost_expr.le(T::src());
-        self.cost_condition.push(cost_expr);
+        self.costs.push(AbilityCost::new::<T>(cost_expr));
+        self
+    }
+
+    /// The ability can only be activated while the condition holds.
+    pub fn with_condition(mut self, condition: BoolExpr<AbilityExprSchema>) -> Self {
+        self.execution_conditions.push(condition);
+        self
+    }
+
+    /// The ability cannot be activated while the caster has the tag or one of its descendants.
+    pub fn blocked_by_tag(mut self, tag: impl Into<GameplayTag>) -> Self {
+        self.blocked_tags.push(tag.into());
         self
     }
 
@@ -72,6 +100,43 @@ impl AbilityBuilder {
         self
     }
 
+    /// Starts a cooldown on the group when the ability is committed.
+    /// The ability cannot be activated while the group is on cooldown.
+    pub fn in_cooldown_group(
+        mut self,
+        group: impl Into<CooldownGroup>,
+        expr: impl Into<Expr<f64, EffectExprSchema>>,
+    ) -> Self {
+        self.cooldown_groups.push((group.into(), expr.into()));
+        self
+    }
+
+    /// Starts the global cooldown when the ability is committed.
+    pub fn with_global_cooldown(self, expr: impl Into<Expr<f64, EffectExprSchema>>) -> Self {
+        self.in_cooldown_group(CooldownGroup::GLOBAL, expr)
+    }
+
+    /// Gives the ability up to `max` charges, each restored after `recharge` seconds.
+    /// Activating the ability consumes a charge, and it cannot be activated without one.
+    pub fn with_charges(
+        mut self,
+        max: impl Into<Expr<f64, EffectExprSchema>>,
+        recharge: impl Into<Expr<f64, EffectExprSchema>>,
+    ) -> Self {
+        let max = max.into();
+        let recharge = recharge.into();
+
+        self.mutators.push(EntityActions::new(
+            move |entity_commands: &mut EntityCommands| {
+                entity_commands.try_insert((
+                    Charges::new(0),
+                    AbilityCharges::new(max.clone(), recharge.clone()),
+                ));
+            },
+        ));
+        self
+    }
+
     pub fn add_execution<E: EntityEvent, B: Bundle, M>(
         mut self,
         observer: impl IntoObserverSystem<E, B, M> + Clone + Send + Sync + 'static,
@@ -89,6 +154,53 @@ impl AbilityBuilder {
         self
     }
 
+    /// Keeps the ability active instead of ending as it executes.
+    pub fn with_duration(mut self, duration: AbilityDuration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Executes the ability every `interval` seconds while it is channeled.
+    /// Abilities whose interval is not positive fail to begin and pay nothing.
+    pub fn channeled(self, interval: f32) -> Self {
+        self.with_duration(AbilityDuration::Channeled {
+            interval,
+            duration: None,
+        })
+    }
+
+    /// Executes the ability once it is released, after charging for up to `max_duration` seconds.
+    pub fn held(self, max_duration: f32) -> Self {
+        self.with_duration(AbilityDuration::Held {
+            max_duration: Some(max_duration),
+        })
+    }
+
+    /// When the cost and cooldown of an active ability are committed.
+    pub fn commit_on(mut self, commit: CommitPolicy) -> Self {
+        self.commit = commit;
+        self
+    }
+
+    /// The active ability ends as soon as the condition no longer holds.
+    pub fn active_while(mut self, condition: BoolExpr<AbilityExprSchema>) -> Self {
+        self.active_conditions.push(condition);
+        self
+    }
+
+    /// The active ability is cancelled when the caster gains the tag or one of its descendants.
+    pub fn cancelled_by_tag(mut self, tag: impl Into<GameplayTag>) -> Self {
+        self.cancelled_by_tags.push(tag.into());
+        self
+    }
+
+    /// The active ability is cancelled when the base value of the attribute of the caster
+    /// decreases, unless the ability decreased it itself.
+    pub fn interrupted_by<T: Attribute>(mut self) -> Self {
+        self.interruptions.push(AbilityInterruption::on_decrease::<T>());
+        self
+    }
+
     pub fn add_trigger<E: EntityEvent, B: Bundle, M>(
         mut self,
         observer: impl IntoObserverSystem<E, B, M> + Clone + Send + Sync + 'static,
@@ -127,11 +239,18 @@ impl AbilityBuilder {
             description: "".to_string(),
             mutators: self.mutators,
             observers: self.triggers,
-            cost_condition: self.cost_condition,
-            execution_conditions: vec![],
+            execution_conditions: self.execution_conditions,
+            blocked_tags: self.blocked_tags,
+            costs: self.costs,
             cost_modifiers: self.cost_modifiers,
+            cooldown_groups: self.cooldown_groups,
 
             on_execute: self.on_execute,
+            duration: self.duration,
+            commit: self.commit,
+            active_conditions: self.active_conditions,
+            cancelled_by_tags: self.cancelled_by_tags,
+            interruptions: self.interruptions,
         }
     }
 }
@@ -0,0 +1,138 @@
+use crate::ability::AbilityOf;
+use crate::attribute;
+use crate::context::{EffectExprContext, EffectExprSchema};
+use crate::prelude::Attribute;
+use crate::snapshot::TimerSnapshot;
+use crate::{attribute_impl, ReflectAccessAttribute, AttributesRef};
+use bevy::ecs::resource::IsResource;
+use bevy::prelude::*;
+use express_it::expr::Expr;
+use std::time::Duration;
+
+attribute!(Charges, u32);
+
+/// Lets an ability be activated while it has [`Charges`] left.
+///
+/// Each activation consumes a charge. Missing charges are restored one at a time,
+/// each after the recharge time. Both expressions are evaluated with the caster as
+/// source and target, and the ability as the effect holder.
+#[derive(Component)]
+pub struct AbilityCharges {
+    timer: Timer,
+    max: Expr<f64, EffectExprSchema>,
+    recharge: Expr<f64, EffectExprSchema>,
+    /// The ability starts with its maximum charges once they are first evaluated.
+    filled: bool,
+}
+
+impl AbilityCharges {
+    pub(crate) fn new(
+        max: Expr<f64, EffectExprSchema>,
+        recharge: Expr<f64, EffectExprSchema>,
+    ) -> Self {
+        Self {
+            timer: Timer::from_seconds(0.0, TimerMode::Repeating),
+            max,
+            recharge,
+            filled: false,
+        }
+    }
+
+    /// The timer restoring the next charge.
+    pub fn timer(&self) -> &Timer {
+        &self.timer
+    }
+
+    /// The time until the next charge is restored.
+    pub fn recharge_remaining(&self) -> Duration {
+        self.timer.remaining()
+    }
+
+    /// Restores saved charges, which must not be refilled.
+    pub(crate) fn restore(&mut self, recharge: &TimerSnapshot) -> Result<(), BevyError> {
+        recharge.restore(&mut self.timer)?;
+        self.filled = true;
+        Ok(())
+    }
+}
+
+/// Restores the charges of abilities over time.
+/// The recharge time is evaluated again before each charge.
+pub(crate) fn recharge_ability_charges(
+    mut abilities: Query<(Entity, &AbilityOf, &mut AbilityCharges)>,
+    mut params: ParamSet<(
+        Query<AttributesRef, Without<IsResource>>,
+        Query<&mut Charges>,
+    )>,
+    type_registry: Res<AppTypeRegistry>,
+    time: Res<Time>,
+) {
+    for (ability_entity, ability_of, mut ability_charges) in abilities.iter_mut() {
+        let (max, recharge, current) = {
+            let refs = params.p0();
+            let Ok([caster, ability]) = refs.get_many([ability_of.0, ability_entity]) else {
+                continue;
+            };
+            let Some(charges) = ability.get::<Charges>() else {
+                continue;
+            };
+            let context = EffectExprContext {
+                source_actor: &caster,
+                target_actor: &caster,
+                effect_holder: &ability,
+                params: None,
+                type_registry: type_registry.0.clone(),
+            };
+            let (max, recharge) = match (
+                ability_charges.max.eval(&context),
+                ability_charges.recharge.eval(&context),
+            ) {
+                (Ok(max), Ok(recharge)) => (max.max(0.0) as u32, recharge),
+                (Err(err), _) | (_, Err(err)) => {
+                    error!("{}: Failed to evaluate ability charges: {}", ability_entity, err);
+                    continue;
+                }
+            };
+            (max, recharge, charges.base_value())
+        };
+
+        // A charge must take some time to restore
+        let recharge = match Duration::try_from_secs_f64(recharge) {
+            Ok(duration) if !duration.is_zero() => duration,
+            _ => {
+                error!("{}: Invalid ability recharge time {}.", ability_entity, recharge);
+                continue;
+            }
+        };
+        let restored = if !ability_charges.filled {
+            ability_charges.filled = true;
+            ability_charges.timer.set_duration(recharge);
+            ability_charges.timer.reset();
+            max
+        } else if current >= max {
+            // Full abilities wait for a charge to be consumed before recharging
+            ability_charges.timer.set_duration(recharge);
+            ability_charges.timer.reset();
+            continue;
+        } else {
+            ability_charges.timer.tick(time.delta());
+            let restored = ability_charges.timer.times_finished_this_tick();
+            if restored > 0 {
+                ability_charges.timer.set_duration(recharge);
+            }
+            restored
+        };
+
+        if restored == 0 {
+            continue;
+        }
+        let mut charges = params.p1();
+        let Ok(mut charges) = charges.get_mut(ability_entity) else {
+            continue;
+        };
+        let new = current.saturating_add(restored).min(max);
+        charges.set_base_value(new);
+        charges.set_current_value(new);
+        debug!("{}: Ability recharged to {} charges.", ability_entity, new);
+    }
+}
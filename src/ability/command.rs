@@ -1,4 +1,4 @@
-use crate::ability::Ability;
+use crate::ability::{Ability, CooldownGroups};
 use crate::assets::AbilityDef;
 use bevy::asset::{Assets, Handle};
 use bevy::ecs::world::CommandQueue;
@@ -35,6 +35,17 @@ impl EntityCommand for GrantAbilityCommand {
                 observer.apply(&mut entity_commands);
             }
 
+            // The actor holds the timers of the cooldown groups
+            if !ability_def.cooldown_groups.is_empty() {
+                commands
+                    .entity(self.parent)
+                    .insert_if_new(CooldownGroups::default());
+            }
+
+            for interruption in &ability_def.interruptions {
+                interruption.apply(&mut commands, self.parent, actor.id());
+            }
+
             queue
         };
 
@@ -0,0 +1,114 @@
+use crate::attribute;
+use crate::prelude::Attribute;
+use crate::{attribute_impl, ReflectAccessAttribute, AttributesRef};
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::time::Duration;
+
+attribute!(CooldownReduction);
+
+/// The fraction of a cooldown remaining after the [`CooldownReduction`] of the caster.
+/// A reduction of 0.25 shortens cooldowns by a quarter.
+pub(crate) fn cooldown_scale(caster: &AttributesRef) -> f64 {
+    caster
+        .get::<CooldownReduction>()
+        .map_or(1.0, |reduction| {
+            1.0 - (reduction.current_value() as f64).clamp(0.0, 1.0)
+        })
+}
+
+/// A cooldown shared by the abilities of an actor, such as potions or the global cooldown.
+#[derive(Clone, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub struct CooldownGroup(SmolStr);
+
+impl CooldownGroup {
+    /// The group of the global cooldown.
+    pub const GLOBAL: CooldownGroup = CooldownGroup::new_static("Global");
+
+    /// Construct a new [`CooldownGroup`] from a [`SmolStr`].
+    pub const fn new(text: SmolStr) -> Self {
+        Self(text)
+    }
+
+    /// Construct a new [`CooldownGroup`] from a static string.
+    pub const fn new_static(text: &'static str) -> Self {
+        Self(SmolStr::new_static(text))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for CooldownGroup {
+    fn from(value: &str) -> Self {
+        Self(SmolStr::new(value))
+    }
+}
+
+impl core::fmt::Display for CooldownGroup {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl core::fmt::Debug for CooldownGroup {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "CooldownGroup({:?})", self.0)
+    }
+}
+
+/// The cooldown groups of an actor and their timers.
+#[derive(Component, Default, Debug)]
+pub struct CooldownGroups {
+    timers: HashMap<CooldownGroup, Timer>,
+}
+
+impl CooldownGroups {
+    /// The time until the abilities of the group can be activated again.
+    pub fn remaining(&self, group: &CooldownGroup) -> Duration {
+        self.timers
+            .get(group)
+            .map_or(Duration::ZERO, Timer::remaining)
+    }
+
+    pub fn is_ready(&self, group: &CooldownGroup) -> bool {
+        self.remaining(group).is_zero()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&CooldownGroup, &Timer)> {
+        self.timers.iter()
+    }
+
+    /// Starts the cooldown of the group.
+    pub fn start(&mut self, group: CooldownGroup, duration: Duration) {
+        self.timers
+            .insert(group, Timer::new(duration, TimerMode::Once));
+    }
+
+    /// Shortens the remaining time of the group.
+    pub fn reduce(&mut self, group: &CooldownGroup, amount: Duration) {
+        if let Some(timer) = self.timers.get_mut(group) {
+            timer.tick(amount);
+        }
+    }
+
+    /// Ends the cooldown of the group.
+    pub fn reset(&mut self, group: &CooldownGroup) {
+        self.timers.remove(group);
+    }
+
+    pub(crate) fn timer_mut(&mut self, group: CooldownGroup) -> &mut Timer {
+        self.timers
+            .entry(group)
+            .or_insert_with(|| Timer::new(Duration::ZERO, TimerMode::Once))
+    }
+}
+
+pub(crate) fn tick_cooldown_groups(mut query: Query<&mut CooldownGroups>, time: Res<Time>) {
+    query.par_iter_mut().for_each(|mut groups| {
+        groups.timers.retain(|_, timer| !timer.tick(time.delta()).is_finished());
+    });
+}
@@ -0,0 +1,37 @@
+use crate::attributes::Attribute;
+use crate::context::{AbilityExprContext, AbilityExprSchema};
+use crate::inspector::pretty_type_name;
+use bevy::prelude::*;
+use express_it::expr::Expr;
+use num_traits::AsPrimitive;
+
+type MissingFn = dyn Fn(&AbilityExprContext) -> Result<f64, BevyError> + Send + Sync;
+
+/// An amount of an attribute the caster pays to activate an ability.
+pub struct AbilityCost {
+    /// The name of the attribute paying the cost.
+    pub attribute: String,
+    missing: Box<MissingFn>,
+}
+
+impl AbilityCost {
+    pub fn new<T: Attribute>(cost: Expr<T::Property, AbilityExprSchema>) -> Self {
+        Self {
+            attribute: pretty_type_name::<T>(),
+            missing: Box::new(move |ctx| {
+                let cost: f64 = cost.eval(ctx)?.as_();
+                let available: f64 = ctx
+                    .caster_ref
+                    .get::<T>()
+                    .map(|attribute| attribute.current_value().as_())
+                    .unwrap_or(0.0);
+                Ok((cost - available).max(0.0))
+            }),
+        }
+    }
+
+    /// How much of the attribute the caster is missing to pay the cost.
+    pub fn missing(&self, ctx: &AbilityExprContext) -> Result<f64, BevyError> {
+        (self.missing)(ctx)
+    }
+}
@@ -1,34 +1,65 @@
+mod active;
 mod builder;
+mod charges;
 mod command;
+mod cooldown;
+mod cost;
 mod system_param;
 mod systems;
 
+use crate::ability::active::{
+    cancel_abilities_on_tag, cancel_ability, clear_pending_activations, finish_ability,
+    tick_active_abilities, PendingActivations,
+};
+use crate::ability::charges::recharge_ability_charges;
+use crate::ability::cooldown::tick_cooldown_groups;
 use crate::ability::systems::{
     activate_ability, reset_ability_cooldown, tick_ability_cooldown, try_activate_ability_observer,
 };
 use crate::assets::AbilityDef;
-use crate::condition::HasComponent;
+use crate::condition::{AbilityCondition, HasComponent};
 use crate::schedule::EffectsSet;
 use bevy::prelude::*;
+pub use active::{AbilityInterruption, ActiveAbility};
 pub use builder::AbilityBuilder;
+pub use charges::{AbilityCharges, Charges};
 pub use command::GrantAbilityCommand;
+pub use cooldown::{CooldownGroup, CooldownGroups, CooldownReduction};
+pub use cost::AbilityCost;
 use express_it::expr::Expr;
 use express_it::logic::{BoolExpr, BoolExprNode};
 use std::error::Error;
 use std::fmt::Formatter;
 use std::sync::Arc;
+use std::time::Duration;
 pub use system_param::AbilityContext;
 use crate::context::AbilityExprSchema;
 use crate::prelude::EffectExprSchema;
+use crate::tags::GameplayTag;
+use crate::registry::ability_registry::AbilityToken;
 
 pub struct AbilityPlugin;
 
 impl Plugin for AbilityPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, tick_ability_cooldown.in_set(EffectsSet::Prepare))
+        app.add_systems(
+            Update,
+            (
+                tick_ability_cooldown,
+                tick_cooldown_groups,
+                recharge_ability_charges,
+                tick_active_abilities,
+            )
+                .in_set(EffectsSet::Prepare),
+        )
+            .add_systems(Update, clear_pending_activations.in_set(EffectsSet::Last))
+            .init_resource::<PendingActivations>()
             .add_observer(try_activate_ability_observer)
             .add_observer(reset_ability_cooldown)
             .add_observer(activate_ability)
+            .add_observer(finish_ability)
+            .add_observer(cancel_ability)
+            .add_observer(cancel_abilities_on_tag)
             .register_type::<AbilityOf>()
             .register_type::<GrantedAbilities>();
     }
@@ -66,13 +97,20 @@ impl TryActivateAbility {
             target_data,
         }
     }
-    pub fn by_def(_target: Entity, _handle: AssetId<AbilityDef>, _target_data: TargetData) -> Self {
-        unimplemented!()
-        /*Self {
+    /// Tries to activate the abilities of the actor granted from this definition.
+    pub fn by_def(
+        target: Entity,
+        handle: impl Into<AssetId<AbilityDef>>,
+        target_data: TargetData,
+    ) -> Self {
+        let node = BoolExprNode::Boxed(Box::new(AbilityCondition::new(handle.into())));
+        let expr = Expr::new(Arc::new(node));
+
+        Self {
             ability: target,
-            condition: BoxCondition::new(AbilityCondition::new(handle)),
+            condition: expr,
             target_data,
-        }*/
+        }
     }
 }
 
@@ -82,6 +120,62 @@ pub struct AbilityCooldown {
     value: Expr<f64, EffectExprSchema>,
 }
 
+impl AbilityCooldown {
+    pub fn timer(&self) -> &Timer {
+        &self.timer
+    }
+
+    pub fn timer_mut(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+}
+
+/// How long an ability stays active once activated.
+#[derive(Debug, Clone, Default)]
+pub enum AbilityDuration {
+    /// The ability begins, executes and ends at once.
+    #[default]
+    Instant,
+    /// The ability executes once, then stays active until it ends after `duration` seconds, if any.
+    Lasting { duration: Option<f32> },
+    /// The ability executes every `interval` seconds until it ends after `duration` seconds, if any.
+    Channeled { interval: f32, duration: Option<f32> },
+    /// The ability charges until it ends, then executes once.
+    /// It ends on its own after `max_duration` seconds, if any.
+    /// The charging time is [`ActiveAbility::elapsed`].
+    Held { max_duration: Option<f32> },
+}
+
+impl AbilityDuration {
+    pub fn is_instant(&self) -> bool {
+        matches!(self, AbilityDuration::Instant)
+    }
+
+    /// The time after which the ability ends on its own.
+    /// Negative durations end the ability at once and infinite ones never end it.
+    pub fn max_duration(&self) -> Option<Duration> {
+        match self {
+            AbilityDuration::Instant => Some(Duration::ZERO),
+            AbilityDuration::Lasting { duration }
+            | AbilityDuration::Channeled { duration, .. }
+            | AbilityDuration::Held {
+                max_duration: duration,
+            } => duration.and_then(|seconds| Duration::try_from_secs_f32(seconds.max(0.0)).ok()),
+        }
+    }
+}
+
+/// When the cost, charge and cooldown of an ability that stays active are committed.
+/// Instant abilities commit as they execute.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommitPolicy {
+    /// Committed when the ability begins, even if it is cancelled later.
+    #[default]
+    OnStart,
+    /// Committed when the ability ends. Cancelled abilities commit nothing.
+    OnEnd,
+}
+
 #[derive(Debug)]
 pub enum TargetData {
     SelfCast,
@@ -108,19 +202,79 @@ pub struct EndAbility {
     #[event_target]
     pub ability: Entity,
     pub source: Entity,
+    /// The ability was cancelled instead of ending.
+    pub cancelled: bool,
 }
 
+/// Cancels an [`ActiveAbility`]. Cancelled abilities do not execute when they end.
 #[derive(EntityEvent)]
 pub struct AbilityCancel {
     #[event_target]
     pub ability: Entity,
     pub source: Entity,
+    pub reason: CancelReason,
+}
+
+/// Why an active ability was cancelled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CancelReason {
+    /// The cancellation was requested, usually by input.
+    Requested,
+    /// An [`AbilityInterruption`] of the ability was triggered.
+    Interrupted,
+    /// The caster gained a tag cancelling the ability.
+    Tag(GameplayTag),
+}
+
+/// Why an ability could not be activated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActivationFailure {
+    /// The ability is already active.
+    AlreadyActive,
+    /// The ability is on cooldown for the remaining time.
+    OnCooldown { remaining: Duration },
+    /// A cooldown group of the ability is on cooldown for the remaining time.
+    OnGroupCooldown {
+        group: CooldownGroup,
+        remaining: Duration,
+    },
+    /// The ability has no charges left until the next one is restored.
+    NoCharges { recharge_remaining: Duration },
+    /// The caster is missing some of the attribute to pay the cost.
+    InsufficientCost { attribute: String, missing: f64 },
+    /// The execution condition at this index of the ability definition does not hold.
+    ConditionFailed { index: usize },
+    /// The caster has a tag blocking the ability.
+    BlockedByTag(GameplayTag),
+    /// The target is not an actor.
+    InvalidTarget(Entity),
+}
+
+/// Triggered on the caster, then on the ability, when an ability selected by
+/// [`TryActivateAbility`] cannot be activated.
+#[derive(EntityEvent, Debug, Clone)]
+pub struct AbilityActivationFailed {
+    /// The caster or the ability.
+    pub entity: Entity,
+    pub caster: Entity,
+    pub ability: Entity,
+    pub reason: ActivationFailure,
+}
+
+impl AbilityActivationFailed {
+    pub(crate) fn on(&self, entity: Entity) -> Self {
+        Self {
+            entity,
+            ..self.clone()
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum AbilityError {
     GrantingAbilityToNonActor(Entity),
     AbilityDoesNotExist(Entity),
+    AbilityNotRegistered(AbilityToken),
 }
 
 impl std::fmt::Display for AbilityError {
@@ -140,6 +294,9 @@ impl std::fmt::Display for AbilityError {
                     entity
                 )
             }
+            AbilityError::AbilityNotRegistered(token) => {
+                write!(f, "{:?} is not registered.", token)
+            }
         }
     }
 }
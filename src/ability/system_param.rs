@@ -1,16 +1,23 @@
+use crate::ability::active::FinishAbility;
 use crate::ability::{
-    Ability, AbilityError, GrantAbilityCommand, GrantedAbilities, TargetData, TryActivateAbility,
+    Ability, AbilityCancel, AbilityError, AbilityOf, CancelReason, CooldownGroup, CooldownGroups,
+    GrantAbilityCommand, GrantedAbilities, TargetData, TryActivateAbility,
 };
 use crate::actors::Actor;
 use crate::assets::AbilityDef;
+use crate::registry::ability_registry::{AbilityRegistry, AbilityToken};
 use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use std::time::Duration;
 
 #[derive(SystemParam)]
 pub struct AbilityContext<'w, 's> {
     abilities: Query<'w, 's, &'static Ability>,
+    owners: Query<'w, 's, &'static AbilityOf>,
+    cooldown_groups: Query<'w, 's, &'static mut CooldownGroups>,
     actors: Query<'w, 's, (&'static Actor, &'static GrantedAbilities)>,
     ability_definitions: Res<'w, Assets<AbilityDef>>,
+    ability_registry: Res<'w, AbilityRegistry>,
     commands: Commands<'w, 's>,
 }
 
@@ -45,10 +52,11 @@ impl<'w, 's> AbilityContext<'w, 's> {
         ));
     }
 
-    pub fn try_activate_by_def<T: Component>(
+    /// Tries to activate the abilities of the actor granted from the definition.
+    pub fn try_activate_by_def(
         &mut self,
         entity: Entity,
-        definition: AssetId<AbilityDef>,
+        definition: impl Into<AssetId<AbilityDef>>,
     ) {
         self.commands.trigger(TryActivateAbility::by_def(
             entity,
@@ -57,6 +65,64 @@ impl<'w, 's> AbilityContext<'w, 's> {
         ));
     }
 
+    /// Tries to activate the abilities of the actor granted from the registered definition.
+    pub fn try_activate_by_token(
+        &mut self,
+        entity: Entity,
+        token: &AbilityToken,
+    ) -> Result<(), AbilityError> {
+        let definition = self
+            .ability_registry
+            .get(token)
+            .ok_or_else(|| AbilityError::AbilityNotRegistered(token.clone()))?
+            .id();
+        self.try_activate_by_def(entity, definition);
+        Ok(())
+    }
+
+    /// Ends an active ability as if its duration ran out.
+    pub fn end_ability(&mut self, ability: Entity) {
+        self.commands.trigger(FinishAbility {
+            ability,
+            cancel: None,
+        });
+    }
+
+    /// Cancels an active ability, usually on input.
+    pub fn cancel_ability(&mut self, ability: Entity) -> Result<(), AbilityError> {
+        let owner = self
+            .owners
+            .get(ability)
+            .or(Err(AbilityError::AbilityDoesNotExist(ability)))?;
+        self.commands.trigger(AbilityCancel {
+            ability,
+            source: owner.0,
+            reason: CancelReason::Requested,
+        });
+        Ok(())
+    }
+
+    /// The time until the abilities of the group can be activated again by the actor.
+    pub fn cooldown_group_remaining(&self, actor: Entity, group: &CooldownGroup) -> Duration {
+        self.cooldown_groups
+            .get(actor)
+            .map_or(Duration::ZERO, |groups| groups.remaining(group))
+    }
+
+    /// Ends the cooldown of the group for the actor.
+    pub fn reset_cooldown_group(&mut self, actor: Entity, group: &CooldownGroup) {
+        if let Ok(mut groups) = self.cooldown_groups.get_mut(actor) {
+            groups.reset(group);
+        }
+    }
+
+    /// Shortens the remaining cooldown of the group for the actor.
+    pub fn reduce_cooldown_group(&mut self, actor: Entity, group: &CooldownGroup, amount: Duration) {
+        if let Ok(mut groups) = self.cooldown_groups.get_mut(actor) {
+            groups.reduce(group, amount);
+        }
+    }
+
     pub fn ability_def(&self, entity: Entity) -> Result<&AbilityDef, AbilityError> {
         let ability = self
             .abilities
@@ -1,10 +1,17 @@
-use crate::ability::{Ability, BeginAbility, AbilityCooldown, ExecuteAbility, AbilityOf, GrantedAbilities, TryActivateAbility, EndAbility};
+use crate::ability::active::PendingActivations;
+use crate::ability::cooldown::cooldown_scale;
+use crate::ability::{
+    Ability, AbilityActivationFailed, AbilityCharges, AbilityCooldown, AbilityDuration, AbilityOf,
+    ActivationFailure, ActiveAbility, BeginAbility, Charges, CommitPolicy, CooldownGroups,
+    EndAbility, ExecuteAbility, GrantedAbilities, TryActivateAbility,
+};
 use crate::assets::AbilityDef;
-use crate::context::{EffectExprContext, EffectExprContextMut, AbilityExprSchema, AbilityExprContext};
+use crate::context::{EffectExprContext, EffectExprContextMut, AbilityExprContext};
+use crate::prelude::Attribute;
+use crate::tags::GameplayTags;
 use crate::{AppAttributeBindings, AttributesMut, AttributesRef};
 use bevy::asset::Assets;
 use bevy::prelude::*;
-use express_it::logic::BoolExpr;
 use std::time::Duration;
 use bevy::ecs::resource::IsResource;
 use bevy::reflect::TypeRegistryArc;
@@ -15,115 +22,182 @@ pub fn tick_ability_cooldown(mut query: Query<&mut AbilityCooldown>, time: Res<T
     });
 }
 
-/// Tries to activate an ability.
+/// Tries to activate the abilities of an actor selected by the trigger's condition.
 ///
 /// Base conditions are:
+/// - Target
+/// - Already active
+/// - Blocking tags
 /// - Cooldown
+/// - Cooldown groups
+/// - Charges
 /// - Conditions
 /// - Cost
+///
+/// Selected abilities that cannot be activated trigger [`AbilityActivationFailed`].
 pub fn try_activate_ability_observer(
     trigger: On<TryActivateAbility>,
-    actors: Query<(AttributesRef, &GrantedAbilities), (Without<AbilityCooldown>, Without<IsResource>)>,
-    abilities: Query<(AttributesRef, &Ability, Option<&AbilityCooldown>)>,
+    actors: Query<
+        (AttributesRef, &GrantedAbilities, Option<&CooldownGroups>),
+        (Without<AbilityCooldown>, Without<IsResource>),
+    >,
+    abilities: Query<(
+        AttributesRef,
+        &Ability,
+        Option<&AbilityCooldown>,
+        Option<&AbilityCharges>,
+        Has<ActiveAbility>,
+    )>,
     ability_assets: Res<Assets<AbilityDef>>,
+    mut pending: ResMut<PendingActivations>,
     mut commands: Commands,
     type_registry: Res<AppTypeRegistry>,
 ) -> Result<(), BevyError> {
-    let Ok((source_entity_ref, actor_abilities)) = actors.get(trigger.ability) else {
+    let Ok((source_entity_ref, actor_abilities, cooldown_groups)) = actors.get(trigger.ability) else {
         warn!("The Actor({}) has no GrantedAbilities", trigger.ability);
         return Ok(());
     };
 
-    let target_entity_ref = match trigger.target_data {
-        crate::ability::TargetData::SelfCast => source_entity_ref,
-        crate::ability::TargetData::Target(target) => {
-            let Ok((entity, _)) = actors.get(target) else {
-                return Ok(());
-            };
-            entity
-        }
+    // An invalid target still lets the trigger select abilities, so the failure can be reported
+    let (target_entity_ref, invalid_target) = match trigger.target_data {
+        crate::ability::TargetData::SelfCast => (source_entity_ref, None),
+        crate::ability::TargetData::Target(target) => match actors.get(target) {
+            Ok((entity, ..)) => (entity, None),
+            Err(_) => (source_entity_ref, Some(target)),
+        },
     };
 
     for &ability_entity in actor_abilities.0.iter() {
-        let (ability_ref, ability, opt_cooldown) = abilities
+        let (ability_ref, ability, opt_cooldown, opt_charges, is_active) = abilities
             .get(ability_entity)
             .expect("Ability not found in: try_activate_ability_observer.");
 
-        // Handle cooldowns
-        let is_finished = match opt_cooldown {
-            None => true,
-            Some(cd) => cd.timer.is_finished(),
+        let ability_spec = ability_assets
+            .get(&ability.0.clone())
+            .ok_or("No ability asset.")?;
+
+        let context = AbilityExprContext {
+            target_ref: &target_entity_ref,
+            caster_ref: &source_entity_ref,
+            ability_ref: &ability_ref,
+            ability_def: ability.0.id(),
+            type_registry: type_registry.0.clone(),
         };
-        if !is_finished {
+
+        if !trigger.condition.eval(&context).unwrap_or(false) {
             continue;
         }
 
-        let ability_spec = ability_assets
-            .get(&ability.0.clone())
-            .ok_or("No ability asset.")?;
+        let failure = if let Some(target) = invalid_target {
+            Some(ActivationFailure::InvalidTarget(target))
+        } else if is_active || pending.0.contains(&ability_entity) {
+            Some(ActivationFailure::AlreadyActive)
+        } else if let Some(failure) = blocking_tag(&context, ability_spec) {
+            Some(failure)
+        } else if let Some(cd) = opt_cooldown.filter(|cd| !cd.timer.is_finished()) {
+            Some(ActivationFailure::OnCooldown {
+                remaining: cd.timer.remaining(),
+            })
+        } else if let Some(failure) = group_cooldown(cooldown_groups, ability_spec) {
+            Some(failure)
+        } else if let Some(charges) = opt_charges.filter(|_| {
+            ability_ref
+                .get::<Charges>()
+                .is_none_or(|charges| charges.current_value() == 0)
+        }) {
+            Some(ActivationFailure::NoCharges {
+                recharge_remaining: charges.recharge_remaining(),
+            })
+        } else {
+            match can_activate_ability(&context, ability_spec) {
+                Ok(failure) => failure,
+                Err(err) => {
+                    error!(
+                        "Ability({}) failed to evaluate its activation: {}",
+                        ability_entity, err
+                    );
+                    continue;
+                }
+            }
+        };
 
-        let can_activate = can_activate_ability(
-            &ability_ref,
-            &source_entity_ref,
-            &target_entity_ref,
-            &ability_spec,
-            &trigger.condition,
-            &type_registry.0.clone(),
-        )
-        .ok()
-        .unwrap_or(false);
-
-        if can_activate {
-            commands.trigger(AbilityCooldownReset {
-                target: target_entity_ref.id(),
-                source: source_entity_ref.id(),
-                ability: ability_entity,
-            });
-            commands.trigger(ActivateAbility {
-                target: target_entity_ref.id(),
-                source: source_entity_ref.id(),
+        if let Some(reason) = failure {
+            debug!(
+                "Ability({}) {} cannot be activated: {:?}",
+                ability_entity, ability_spec.name, reason
+            );
+            let event = AbilityActivationFailed {
+                entity: source_entity_ref.id(),
+                caster: source_entity_ref.id(),
                 ability: ability_entity,
-            });
+                reason,
+            };
+            commands.trigger(event.on(source_entity_ref.id()));
+            commands.trigger(event.on(ability_entity));
+            continue;
+        }
+
+        // The ability becomes active once the commands are applied
+        if !ability_spec.duration.is_instant() {
+            pending.0.insert(ability_entity);
         }
+        commands.trigger(ActivateAbility {
+            target: target_entity_ref.id(),
+            source: source_entity_ref.id(),
+            ability: ability_entity,
+        });
     }
     Ok(())
 }
 
-fn can_activate_ability(
-    ability_ref: &AttributesRef,
-    caster_ref: &AttributesRef,
-    target_ref: &AttributesRef,
-    ability_def: &AbilityDef,
-    conditions: &BoolExpr<AbilityExprSchema>,
-    type_registry: &TypeRegistryArc,
-) -> Result<bool, BevyError> {
-    let context = AbilityExprContext {
-        target_ref,
-        caster_ref,
-        ability_ref,
-        type_registry: type_registry.clone(),
-    };
-
-    let meet_conditions = conditions.eval(&context).unwrap_or(false);
-    if !meet_conditions {
-        debug!(
-            "Ability({}) conditions not met for: {}.",
-            ability_ref.id(),
-            ability_def.name
-        );
-        return Ok(false);
-    }
+/// The first tag of the caster blocking the ability.
+fn blocking_tag(context: &AbilityExprContext, ability_spec: &AbilityDef) -> Option<ActivationFailure> {
+    let tags = context.caster_ref.get::<GameplayTags>()?;
+    ability_spec
+        .blocked_tags
+        .iter()
+        .find(|tag| tags.has_tag(tag))
+        .map(|tag| ActivationFailure::BlockedByTag(tag.clone()))
+}
 
-    let can_activate = ability_def
-        .cost_condition
+/// The first cooldown group of the ability still on cooldown.
+fn group_cooldown(
+    cooldown_groups: Option<&CooldownGroups>,
+    ability_spec: &AbilityDef,
+) -> Option<ActivationFailure> {
+    let groups = cooldown_groups?;
+    ability_spec
+        .cooldown_groups
         .iter()
-        .all(|condition| condition.eval(&context).unwrap_or(false));
+        .find(|(group, _)| !groups.is_ready(group))
+        .map(|(group, _)| ActivationFailure::OnGroupCooldown {
+            group: group.clone(),
+            remaining: groups.remaining(group),
+        })
+}
+
+/// Checks the execution conditions and costs of an ability.
+/// Returns why the ability cannot be activated, if it cannot.
+fn can_activate_ability(
+    context: &AbilityExprContext,
+    ability_spec: &AbilityDef,
+) -> Result<Option<ActivationFailure>, BevyError> {
+    for (index, condition) in ability_spec.execution_conditions.iter().enumerate() {
+        if !condition.eval(context)? {
+            return Ok(Some(ActivationFailure::ConditionFailed { index }));
+        }
+    }
 
-    if !can_activate {
-        debug!("Insufficient resources to activate ability!");
-        return Ok(false);
+    for cost in &ability_spec.costs {
+        let missing = cost.missing(context)?;
+        if missing > 0.0 {
+            return Ok(Some(ActivationFailure::InsufficientCost {
+                attribute: cost.attribute.clone(),
+                missing,
+            }));
+        }
     }
-    Ok(true)
+    Ok(None)
 }
 
 #[derive(EntityEvent)]
@@ -134,16 +208,26 @@ pub(crate) struct AbilityCooldownReset {
     pub ability: Entity,
 }
 
+/// Starts the cooldown of the ability and of its cooldown groups,
+/// scaled by the [`CooldownReduction`](crate::ability::CooldownReduction) of the caster.
 pub(crate) fn reset_ability_cooldown(
     trigger: On<AbilityCooldownReset>,
     mut cooldowns: Query<(&AbilityOf, &mut AbilityCooldown)>,
+    mut cooldown_groups: Query<&mut CooldownGroups>,
+    abilities: Query<&Ability>,
+    ability_assets: Res<Assets<AbilityDef>>,
     query: Query<AttributesRef>,
     type_registry: Res<AppTypeRegistry>,
 ) -> Result<(), BevyError> {
-    let Ok((_parent, mut cooldown)) = cooldowns.get_mut(trigger.ability) else {
+    let ability = abilities.get(trigger.ability)?;
+    let ability_spec = ability_assets
+        .get(&ability.0)
+        .ok_or("No ability asset")?;
+    let opt_cooldown = cooldowns.get_mut(trigger.ability).ok();
+    if opt_cooldown.is_none() && ability_spec.cooldown_groups.is_empty() {
         // This event does not affect an ability without a cooldown.
         return Ok(());
-    };
+    }
 
     let [source, target, owner] =
         query.get_many([trigger.source, trigger.target, trigger.ability])?;
@@ -151,18 +235,37 @@ pub(crate) fn reset_ability_cooldown(
         target_actor: &source,
         source_actor: &target,
         effect_holder: &owner,
+        params: None,
         type_registry: type_registry.0.clone(),
     };
+    let scale = cooldown_scale(&source);
 
-    let cd_value = cooldown.value.eval(&context)?;
+    if let Some((_parent, mut cooldown)) = opt_cooldown {
+        let duration = cooldown_duration(cooldown.value.eval(&context)? * scale)?;
+        cooldown.timer.set_duration(duration);
+        cooldown.timer.reset();
+    }
 
-    cooldown
-        .timer
-        .set_duration(Duration::from_secs_f64(cd_value));
-    cooldown.timer.reset();
+    if ability_spec.cooldown_groups.is_empty() {
+        return Ok(());
+    }
+    let mut groups = cooldown_groups.get_mut(trigger.source)?;
+    for (group, expr) in &ability_spec.cooldown_groups {
+        let duration = cooldown_duration(expr.eval(&context)? * scale)?;
+        // A shorter cooldown does not cut the remaining time of the group
+        if duration > groups.remaining(group) {
+            groups.start(group.clone(), duration);
+        }
+    }
     Ok(())
 }
 
+/// Converts a cooldown in seconds. Negative cooldowns are over at once.
+fn cooldown_duration(seconds: f64) -> Result<Duration, BevyError> {
+    Duration::try_from_secs_f64(seconds.max(0.0))
+        .map_err(|err| format!("Invalid cooldown {}: {}", seconds, err).into())
+}
+
 #[derive(EntityEvent)]
 pub struct ActivateAbility {
     #[event_target]
@@ -171,7 +274,19 @@ pub struct ActivateAbility {
     pub ability: Entity,
 }
 
+/// An activation of an ability, from its caster to its target.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct AbilityInstance {
+    pub source: Entity,
+    pub target: Entity,
+    pub ability: Entity,
+    pub ability_def: AssetId<AbilityDef>,
+}
+
 /// Bypass [TryActivateAbility]'s checks. Usually triggered after a successful [TryActivateAbility].
+///
+/// Instant abilities begin, execute and end at once. Other abilities stay [`ActiveAbility`]
+/// until they end or are cancelled.
 pub(crate) fn activate_ability(
     trigger: On<ActivateAbility>,
     mut actors: Query<AttributesMut<'static, 'static>, Without<IsResource>>,
@@ -181,117 +296,178 @@ pub(crate) fn activate_ability(
     type_registry: Res<AppTypeRegistry>,
     type_bindings: Res<AppAttributeBindings>,
 ) -> Result<(), BevyError> {
-    debug!("{}: Commit ability cost.", trigger.ability);
     let ability = abilities.get(trigger.ability)?;
     let ability_spec = ability_assets
         .get(&ability.0.clone())
         .ok_or("No ability asset")?;
+    let instance = AbilityInstance {
+        source: trigger.source,
+        target: trigger.target,
+        ability: trigger.ability,
+        ability_def: ability.0.id(),
+    };
+
+    if ability_spec.duration.is_instant() {
+        execute_ability(&mut actors, ability_spec, instance, &type_registry.0, &type_bindings, &mut commands)?;
+        commit_ability(&mut actors, ability_spec, instance, &type_registry.0, &type_bindings, &mut commands)?;
+
+        // Activate the ability
+        debug!("{}: Execute ability", trigger.ability);
+        commands.trigger(BeginAbility {
+            source: trigger.source,
+            ability: trigger.ability,
+        });
+        commands.trigger(ExecuteAbility {
+            source: trigger.source,
+            target: trigger.target,
+            ability: trigger.ability,
+        });
+        commands.trigger(EndAbility {
+            source: trigger.source,
+            ability: trigger.ability,
+            cancelled: false,
+        });
+        return Ok(());
+    }
+
+    let active = match ActiveAbility::new(trigger.target, &ability_spec.duration) {
+        Ok(active) => active,
+        Err(err) => {
+            error!("{}: Failed to begin ability: {}", trigger.ability, err);
+            return Ok(());
+        }
+    };
+    if ability_spec.commit == CommitPolicy::OnStart {
+        commit_ability(&mut actors, ability_spec, instance, &type_registry.0, &type_bindings, &mut commands)?;
+    }
+
+    debug!("{}: Begin ability", trigger.ability);
+    commands.entity(trigger.ability).insert(active);
+    commands.trigger(BeginAbility {
+        source: trigger.source,
+        ability: trigger.ability,
+    });
 
-    if trigger.source == trigger.target {
-        for plan in &ability_spec.on_execute {
-            let [source, ability] = actors.get_many([trigger.source, trigger.ability])?;
+    if matches!(ability_spec.duration, AbilityDuration::Lasting { .. }) {
+        execute_ability(&mut actors, ability_spec, instance, &type_registry.0, &type_bindings, &mut commands)?;
+        commands.trigger(ExecuteAbility {
+            source: trigger.source,
+            target: trigger.target,
+            ability: trigger.ability,
+        });
+    }
+    Ok(())
+}
+
+/// Runs the execution plans of an ability.
+pub(crate) fn execute_ability(
+    actors: &mut Query<AttributesMut<'static, 'static>, Without<IsResource>>,
+    ability_spec: &AbilityDef,
+    instance: AbilityInstance,
+    type_registry: &TypeRegistryArc,
+    type_bindings: &AppAttributeBindings,
+    commands: &mut Commands,
+) -> Result<(), BevyError> {
+    let AbilityInstance {
+        source,
+        target,
+        ability,
+        ability_def,
+    } = instance;
+
+    for plan in &ability_spec.on_execute {
+        let output = {
+            let [source, target, ability] = actors.get_many([source, target, ability])?;
             let immutable_context = AbilityExprContext {
                 caster_ref: &source,
-                target_ref: &source,
+                target_ref: &target,
                 ability_ref: &ability,
-                type_registry: type_registry.0.clone(),
+                ability_def,
+                type_registry: type_registry.clone(),
             };
-            let output = plan.eval(&immutable_context)?;
+            plan.eval(&immutable_context)?
+        };
 
-            let [mut source, mut owner] = actors.get_many_mut([trigger.source, trigger.ability])?;
+        if source == target {
+            let [mut source, mut owner] = actors.get_many_mut([source, ability])?;
             let mut context = EffectExprContextMut {
                 source_actor: &mut source,
                 target_actor: None,
                 owner: &mut owner,
-                type_registry: type_registry.0.clone(),
+                type_registry: type_registry.clone(),
                 type_bindings: type_bindings.clone(),
+                base_value_changes: vec![],
             };
 
             output.flush_into(&mut context);
-        }
-
-        // Calculates the costs of the ability and applies them
-        let [source, ability] = actors.get_many([trigger.source, trigger.ability])?;
-        let immutable_context = AbilityExprContext {
-            caster_ref: &source,
-            target_ref: &source,
-            ability_ref: &ability,
-            type_registry: type_registry.0.clone(),
-        };
-
-        let plan_results = ability_spec.cost_modifiers.eval(&immutable_context)?;
-
-        let [mut source, mut owner] = actors.get_many_mut([trigger.source, trigger.ability])?;
-        let mut context = EffectExprContextMut {
-            source_actor: &mut source,
-            target_actor: None,
-            owner: &mut owner,
-            type_registry: type_registry.0.clone(),
-            type_bindings: type_bindings.clone(),
-        };
-
-        plan_results.flush_into(&mut context);
-    } else {
-        for plan in &ability_spec.on_execute {
-            let [source, target, ability] =
-                actors.get_many([trigger.source, trigger.target, trigger.ability])?;
-            let immutable_context = AbilityExprContext {
-                caster_ref: &source,
-                target_ref: &target,
-                ability_ref: &ability,
-                type_registry: type_registry.0.clone(),
-            };
-            let output = plan.eval(&immutable_context)?;
-
+            context.notify_base_value_changes(commands);
+        } else {
             let [mut source, mut target, mut owner] =
-                actors.get_many_mut([trigger.source, trigger.target, trigger.ability])?;
+                actors.get_many_mut([source, target, ability])?;
             let mut context = EffectExprContextMut {
                 source_actor: &mut source,
                 target_actor: Some(&mut target),
                 owner: &mut owner,
-                type_registry: type_registry.0.clone(),
+                type_registry: type_registry.clone(),
                 type_bindings: type_bindings.clone(),
+                base_value_changes: vec![],
             };
 
             output.flush_into(&mut context);
+            context.notify_base_value_changes(commands);
         }
+    }
+    Ok(())
+}
 
-        // Calculates the costs of the ability and applies them
-        let [source, ability] = actors.get_many([trigger.source, trigger.ability])?;
+/// Pays the costs of an ability, consumes a charge and starts its cooldown.
+pub(crate) fn commit_ability(
+    actors: &mut Query<AttributesMut<'static, 'static>, Without<IsResource>>,
+    ability_spec: &AbilityDef,
+    instance: AbilityInstance,
+    type_registry: &TypeRegistryArc,
+    type_bindings: &AppAttributeBindings,
+    commands: &mut Commands,
+) -> Result<(), BevyError> {
+    debug!("{}: Commit ability cost.", instance.ability);
+
+    // Calculates the costs of the ability and applies them
+    let plan_results = {
+        let [source, ability] = actors.get_many([instance.source, instance.ability])?;
         let immutable_context = AbilityExprContext {
             caster_ref: &source,
             target_ref: &source,
             ability_ref: &ability,
-            type_registry: type_registry.0.clone(),
-        };
-        let plan_results = ability_spec.cost_modifiers.eval(&immutable_context)?;
-
-        let [mut source, mut owner] = actors.get_many_mut([trigger.source, trigger.ability])?;
-        let mut context = EffectExprContextMut {
-            source_actor: &mut source,
-            target_actor: None,
-            owner: &mut owner,
-            type_registry: type_registry.0.clone(),
-            type_bindings: type_bindings.clone(),
+            ability_def: instance.ability_def,
+            type_registry: type_registry.clone(),
         };
+        ability_spec.cost_modifiers.eval(&immutable_context)?
+    };
 
-        plan_results.flush_into(&mut context);
+    let [mut source, mut owner] = actors.get_many_mut([instance.source, instance.ability])?;
+    let mut context = EffectExprContextMut {
+        source_actor: &mut source,
+        target_actor: None,
+        owner: &mut owner,
+        type_registry: type_registry.clone(),
+        type_bindings: type_bindings.clone(),
+        base_value_changes: vec![],
     };
 
-    // Activate the ability
-    debug!("{}: Execute ability", trigger.ability);
-    commands.trigger(BeginAbility {
-        source: trigger.source,
-        ability: trigger.ability,
-    });
-    commands.trigger(ExecuteAbility {
-        source: trigger.source,
-        target: trigger.target,
-        ability: trigger.ability,
-    });
-    commands.trigger(EndAbility {
-        source: trigger.source,
-        ability: trigger.ability,
+    plan_results.flush_into(&mut context);
+    context.notify_base_value_changes(commands);
+
+    // Consumes a charge of the ability
+    if let Some(mut charges) = owner.get_mut::<Charges>() {
+        let remaining = charges.base_value().saturating_sub(1);
+        charges.set_base_value(remaining);
+        charges.set_current_value(remaining);
+    }
+
+    commands.trigger(AbilityCooldownReset {
+        target: instance.target,
+        source: instance.source,
+        ability: instance.ability,
     });
     Ok(())
 }
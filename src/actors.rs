@@ -1,7 +1,9 @@
 use std::any::type_name_of_val;
 use crate::ability::{AbilityOf, GrantAbilityCommand};
 use crate::assets::{AbilityDef, ActorDef, EffectDef};
-use crate::effect::{ApplyEffectEvent, EffectTargeting};
+use crate::effect::{
+    ApplyEffectEvent, EffectImmunities, EffectParams, EffectTargeting, ImmunityRule,
+};
 use crate::graph::NodeType;
 use crate::modifier::AttributeCalculatorCached;
 use crate::mutator::EntityActions;
@@ -10,14 +12,20 @@ use crate::GrantedAbilities;
 use bevy::ecs::world::CommandQueue;
 use bevy::prelude::*;
 use express_it::expr::{Expr, ExprNode};
-use num_traits::{AsPrimitive, Num};
+use num_traits::{AsPrimitive, Num, Zero};
 use std::collections::HashSet;
 use smol_str::SmolStr;
 use crate::attribute::clamps::Clamp;
+use crate::attribute::derived::{
+    find_cyclic_derived_attributes, DerivedAttribute, DerivedAttributeOf,
+};
+use crate::assets::DerivedAttributeDef;
+use crate::context::split_path;
 use crate::inspector::pretty_type_name;
+use crate::tags::GameplayTags;
 
 #[derive(Component, Clone, Debug, Deref)]
-#[require(GrantedAbilities)]
+#[require(GrantedAbilities, GameplayTags)]
 pub struct Actor(pub Handle<ActorDef>);
 
 pub struct SpawnActorCommand {
@@ -40,17 +48,7 @@ impl EntityCommand for SpawnActorCommand {
                     let mut queue = CommandQueue::default();
                     let mut commands = Commands::new(&mut queue, world);
 
-                    commands.entity(actor_entity).insert((
-                        NodeType::Actor,
-                        Actor(self.handle.clone()),
-                        Name::new(actor_def.name.clone()),
-                    ));
-
-                    // Apply mutators
-                    for actions in &actor_def.builder_actions {
-                        let mut entity_commands = commands.entity(actor_entity);
-                        (actions.func)(&mut entity_commands);
-                    }
+                    insert_actor_components(&mut commands, actor_entity, &self.handle, actor_def);
 
                     // Spawn the granted ability entities
                     for ability in actor_def.abilities.iter() {
@@ -71,6 +69,7 @@ impl EntityCommand for SpawnActorCommand {
                         entity: actor_entity,
                         targeting: EffectTargeting::SelfCast(actor_entity),
                         handle: effect.clone(),
+                        params: EffectParams::default(),
                     });
                 }
 
@@ -81,6 +80,41 @@ impl EntityCommand for SpawnActorCommand {
     }
 }
 
+/// Inserts the actor's components, attributes and derived attributes.
+/// Granted abilities and effects of the definition are left to the caller.
+pub(crate) fn insert_actor_components(
+    commands: &mut Commands,
+    actor_entity: Entity,
+    handle: &Handle<ActorDef>,
+    actor_def: &ActorDef,
+) {
+    commands.entity(actor_entity).insert((
+        NodeType::Actor,
+        Actor(handle.clone()),
+        Name::new(actor_def.name.clone()),
+    ));
+
+    // Apply mutators
+    for actions in &actor_def.builder_actions {
+        let mut entity_commands = commands.entity(actor_entity);
+        (actions.func)(&mut entity_commands);
+    }
+
+    // Spawn derived attributes, except those depending on themselves
+    let cyclic = find_cyclic_derived_attributes(&actor_def.derived_attributes);
+    for derived in actor_def.derived_attributes.iter() {
+        if cyclic.contains(&derived.name) {
+            error!(
+                "Derived attribute {} of actor {} has cyclic dependencies.",
+                derived.name, actor_def.name
+            );
+            continue;
+        }
+        let mut entity_commands = commands.entity(actor_entity);
+        derived.action.apply(&mut entity_commands);
+    }
+}
+
 pub struct ActorBuilder {
     actor: ActorDef,
 }
@@ -96,6 +130,7 @@ impl ActorBuilder {
                 effects: vec![],
                 clamp_exprs: Default::default(),
                 clamp_reverse_lookup: Default::default(),
+                derived_attributes: vec![],
             },
         }
     }
@@ -122,6 +157,25 @@ impl ActorBuilder {
         self
     }
 
+    /// Blocks the application of matching effects to the actor.
+    pub fn immune_to(mut self, rule: impl Into<ImmunityRule>) -> ActorBuilder {
+        let rule = rule.into();
+        self.actor.builder_actions.push_back(EntityActions::new(
+            move |entity_commands: &mut EntityCommands| {
+                let rule = rule.clone();
+                entity_commands.queue(move |mut entity: EntityWorldMut| {
+                    match entity.get_mut::<EffectImmunities>() {
+                        Some(mut immunities) => immunities.0.push(rule),
+                        None => {
+                            entity.insert(EffectImmunities(vec![rule]));
+                        }
+                    }
+                });
+            },
+        ));
+        self
+    }
+
     pub fn clamp<T>(
         mut self,
         min_expr: impl Into<Expr<T::Property, ActorExprSchema>> + Send + Sync + 'static,
@@ -158,6 +212,67 @@ impl ActorBuilder {
         self
     }
 
+    /// Defines the base value of attribute `T` as an expression over the actor's attributes.
+    ///
+    /// The base value is recomputed whenever the current value of one of its inputs changes.
+    /// Derived attributes depending on themselves are rejected when the actor spawns.
+    ///
+    /// # Example
+    /// ```
+    /// # use vitality::prelude::*;
+    /// # use vitality::actors::ActorBuilder;
+    /// attribute!(Strength, f32);
+    /// attribute!(MaxHealth, f32);
+    ///
+    /// let actor = ActorBuilder::new()
+    ///     .with::<Strength>(12.0)
+    ///     .derive::<MaxHealth>(Strength::src() * Strength::lit(10.0) + Strength::lit(50.0))
+    ///     .build();
+    /// ```
+    pub fn derive<T>(
+        mut self,
+        expr: impl Into<Expr<T::Property, ActorExprSchema>>,
+    ) -> ActorBuilder
+    where
+        T: Attribute,
+    {
+        let expr = expr.into();
+
+        let mut paths = HashSet::default();
+        expr.inner.get_dependencies(&mut paths);
+        let dependencies: Vec<SmolStr> = paths
+            .iter()
+            .map(|path| match split_path(&*path.0) {
+                Ok((_, component, _)) => SmolStr::new(component),
+                Err(_) => SmolStr::new(&*path.0),
+            })
+            .collect();
+        debug!("Derived<{}> dependencies: {:?}", pretty_type_name::<T>(), dependencies);
+
+        let derived = DerivedAttribute::<T> {
+            expr,
+            dependencies: dependencies.clone(),
+        };
+
+        self.actor.derived_attributes.push(DerivedAttributeDef {
+            name: SmolStr::new(pretty_type_name::<T>()),
+            dependencies,
+            action: EntityActions::new(move |entity_commands: &mut EntityCommands| {
+                let actor_entity = entity_commands.id();
+                entity_commands.try_insert_if_new((
+                    T::new(T::Property::zero()),
+                    AttributeCalculatorCached::<T>::default(),
+                ));
+                entity_commands.commands().spawn((
+                    DerivedAttributeOf(actor_entity),
+                    derived.clone(),
+                    Name::new(format!("Derived<{}>", pretty_type_name::<T>())),
+                ));
+            }),
+        });
+        self
+    }
+
     pub fn insert<T: Bundle + Clone + 'static>(mut self, bundle: T) -> ActorBuilder {
         self.actor.builder_actions.push_front(EntityActions::new(
             move |entity_commands: &mut EntityCommands| {
@@ -1,15 +1,24 @@
 
-use crate::effect::{EffectApplicationPolicy, EffectStackingPolicy};
+use crate::ability::{
+    AbilityCost, AbilityDuration, AbilityInterruption, CommitPolicy, CooldownGroup,
+};
+use crate::effect::{
+    ChainTrigger, EffectApplicationPolicy, EffectExecution, EffectStackingPolicy, ImmunityRule,
+    StackExpiration, StackingScope,
+};
 use crate::modifier::ModifierFn;
 use crate::modifier::modifier::Modifier;
 use crate::mutator::EntityActions;
 use bevy::prelude::*;
+use express_it::expr::Expr;
 use express_it::frame::LazyPlan;
 use express_it::logic::BoolExpr;
 use std::any::Any;
 use std::collections::{HashMap, VecDeque};
 use smol_str::SmolStr;
-use crate::context::{AbilityExprSchema, EffectExprSchema};
+use crate::context::{AbilityExprSchema, EffectExprContext, EffectExprSchema};
+use crate::registry::effect_registry::EffectToken;
+use crate::tags::GameplayTag;
 
 #[derive(Asset, TypePath)]
 pub struct ActorDef {
@@ -23,22 +32,66 @@ pub struct ActorDef {
     // Box<(Expr<T::Property>, Expr<T::Property>)>
     pub clamp_exprs: HashMap<SmolStr, Box<dyn Any + Send + Sync>>,
     pub clamp_reverse_lookup: HashMap<SmolStr, Vec<SmolStr>>,
+
+    pub derived_attributes: Vec<DerivedAttributeDef>,
+}
+
+/// An attribute whose base value is computed from the actor's other attributes.
+pub struct DerivedAttributeDef {
+    pub name: SmolStr,
+    pub dependencies: Vec<SmolStr>,
+    pub action: EntityActions,
 }
 
 #[derive(Asset, TypePath)]
 pub struct EffectDef {
+    /// Registers the effect under this token once it is loaded.
+    pub token: Option<EffectToken>,
     pub application_policy: EffectApplicationPolicy,
+    /// The duration in seconds, evaluated when the effect is applied.
+    /// Overrides the duration of the application policy.
+    pub duration: Option<Expr<f64, EffectExprSchema>>,
+    /// The duration in seconds set when a new application refreshes the effect.
+    /// Defaults to [`duration`](Self::duration).
+    pub refresh_duration: Option<Expr<f64, EffectExprSchema>>,
+    /// Whether a periodic effect ticks as soon as it is applied.
+    pub tick_on_apply: bool,
+    /// The number of ticks after which a periodic effect expires.
+    pub max_ticks: Option<u32>,
+    /// The interval of a periodic effect in seconds, re-evaluated after each tick.
+    pub tick_interval: Option<Expr<f32, EffectExprSchema>>,
     pub stacking_policy: EffectStackingPolicy,
+    pub stacking_scope: StackingScope,
+    pub stack_expiration: StackExpiration,
+    /// Effects applied from the same source to the same target on the lifecycle of the effect.
+    pub chained_effects: Vec<(ChainTrigger, Handle<EffectDef>)>,
     pub effect_fn: Vec<Box<ModifierFn>>,
     pub modifiers: Vec<Box<dyn Modifier>>,
+    /// Calculations run when the effect is applied, or when it ticks if periodic.
+    pub executions: Vec<Box<dyn EffectExecution>>,
 
     pub attach_conditions: Vec<BoolExpr<EffectExprSchema>>,
     pub activate_conditions: Vec<BoolExpr<EffectExprSchema>>,
 
+    /// Tags granted to the target while the effect is active.
+    pub granted_tags: Vec<GameplayTag>,
+    /// Immunities granted to the target while the effect is active.
+    pub immunities: Vec<ImmunityRule>,
+
     pub on_actor_triggers: Vec<EntityActions>,
     pub on_effect_triggers: Vec<EntityActions>,
 }
 
+impl EffectDef {
+    /// Whether the activation conditions hold for an application or a tick of the effect.
+    /// Conditions failing to evaluate do not hold.
+    pub(crate) fn should_activate(&self, context: &EffectExprContext) -> bool {
+        self.activate_conditions
+            .iter()
+            .all(|condition| condition.eval(context).unwrap_or(false))
+    }
+}
+
 #[derive(Asset, TypePath)]
 pub struct AbilityDef {
     pub name: String,
@@ -48,9 +101,23 @@ pub struct AbilityDef {
     pub observers: Vec<EntityActions>,
 
     pub execution_conditions: Vec<BoolExpr<AbilityExprSchema>>,
+    /// The ability cannot be activated while the caster has one of these tags.
+    pub blocked_tags: Vec<GameplayTag>,
 
-    pub cost_condition: Vec<BoolExpr<AbilityExprSchema>>,
+    pub costs: Vec<AbilityCost>,
     pub cost_modifiers: LazyPlan,
+    /// The cooldowns the ability starts on the groups of its caster.
+    pub cooldown_groups: Vec<(CooldownGroup, Expr<f64, EffectExprSchema>)>,
 
     pub on_execute: Vec<LazyPlan>,
+
+    /// How long the ability stays active.
+    pub duration: AbilityDuration,
+    /// When the cost and cooldown of an ability that stays active are committed.
+    pub commit: CommitPolicy,
+    /// The active ability ends when one of the conditions no longer holds.
+    pub active_conditions: Vec<BoolExpr<AbilityExprSchema>>,
+    /// The active ability is cancelled when the caster gains one of these tags.
+    pub cancelled_by_tags: Vec<GameplayTag>,
+    pub interruptions: Vec<AbilityInterruption>,
 }
@@ -4,7 +4,7 @@ use crate::assets::ActorDef;
 use crate::attributes::AttributeQueryData;
 use crate::context::{ActorExprContext};
 use crate::prelude::*;
-use crate::{ AttributesRef, CurrentValueChanged};
+use crate::{AppAttributeBindings, AttributesRef, BaseValueChanged, CurrentValueChanged};
 use bevy::prelude::*;
 use express_it::expr::Expr;
 use crate::inspector::pretty_type_name;
@@ -71,15 +71,20 @@ pub fn update_clamps<T: Attribute>(
     Ok(())
 }
 
+/// Keeps a value within the limits, for properties that are only partially ordered.
+pub(crate) fn clamp_partial<V: Copy + PartialOrd>(value: V, min: V, max: V) -> V {
+    let value = if value < min { min } else { value };
+    if value > max { max } else { value }
+}
+
 pub fn apply_clamps<T>(
     mut query: Query<(AttributeQueryData<T>, &Clamp<T>), (Changed<T>, Changed<Clamp<T>>)>,
+    mut commands: Commands,
+    type_bindings: Res<AppAttributeBindings>,
 ) where
     T: Attribute,
 {
-    fn clamp_partial<V: Copy + PartialOrd>(value: V, min: V, max: V) -> V {
-        let value = if value < min { min } else { value };
-        if value > max { max } else { value }
-    }
+    let pipeline = type_bindings.internal.read().unwrap().pipeline::<T>();
 
     for (mut attribute_data, clamp) in query.iter_mut() {
         let base = attribute_data.attribute.base_value();
@@ -88,7 +93,16 @@ pub fn apply_clamps<T>(
         if clamped != base {
             attribute_data.attribute.set_base_value(clamped);
             // Base changed => recompute current from cached calculator.
-            attribute_data.update_attribute_from_cache();
+            attribute_data.update_attribute_from_cache(&pipeline);
+
+            commands.trigger(BaseValueChanged::<T> {
+                phantom_data: Default::default(),
+                old: base,
+                new: clamped,
+                entity: attribute_data.entity,
+                source: attribute_data.entity,
+                effect: None,
+            });
         }
     }
 }
@@ -0,0 +1,126 @@
+use crate::assets::DerivedAttributeDef;
+use crate::context::ActorExprContext;
+use crate::inspector::pretty_type_name;
+use crate::math::AbsDiff;
+use crate::modifier::modifier::RecalculateExpression;
+use crate::prelude::*;
+use crate::systems::MarkNodeDirty;
+use crate::{AppAttributeBindings, AttributesRef, BaseValueChanged};
+use bevy::ecs::resource::IsResource;
+use bevy::prelude::*;
+use express_it::expr::Expr;
+use petgraph::algo::tarjan_scc;
+use petgraph::graphmap::DiGraphMap;
+use smol_str::SmolStr;
+use std::collections::HashSet;
+
+/// Defines the base value of attribute `T` on the [`DerivedAttributeOf`] actor.
+#[derive(Component, Clone)]
+pub struct DerivedAttribute<T: Attribute> {
+    pub expr: Expr<T::Property, ActorExprSchema>,
+    pub dependencies: Vec<SmolStr>,
+}
+
+/// The actor whose attribute is derived by this entity.
+#[derive(Component, Reflect, Debug)]
+#[relationship(relationship_target = DerivedAttributes)]
+pub struct DerivedAttributeOf(pub Entity);
+
+/// All derived attributes of this actor.
+#[derive(Component, Reflect, Debug)]
+#[relationship_target(relationship = DerivedAttributeOf, linked_spawn)]
+pub struct DerivedAttributes(Vec<Entity>);
+
+/// Returns the derived attributes whose expressions depend on themselves.
+pub(crate) fn find_cyclic_derived_attributes(derived: &[DerivedAttributeDef]) -> HashSet<SmolStr> {
+    let mut graph = DiGraphMap::<&str, ()>::new();
+    for def in derived {
+        graph.add_node(def.name.as_str());
+        for dependency in &def.dependencies {
+            graph.add_edge(def.name.as_str(), dependency.as_str(), ());
+        }
+    }
+
+    tarjan_scc(&graph)
+        .into_iter()
+        .filter(|scc| scc.len() > 1 || graph.contains_edge(scc[0], scc[0]))
+        .flatten()
+        .map(SmolStr::new)
+        .collect()
+}
+
+/// Watches the inputs of the derived attribute and computes its initial value.
+pub fn on_add_derived_attribute<T: Attribute>(
+    trigger: On<Add, DerivedAttribute<T>>,
+    derived: Query<(&DerivedAttribute<T>, &DerivedAttributeOf)>,
+    type_bindings: Res<AppAttributeBindings>,
+    mut commands: Commands,
+) {
+    let Ok((derived, derived_of)) = derived.get(trigger.entity) else {
+        return;
+    };
+
+    let bindings = type_bindings.internal.read().unwrap();
+    let mut entity_commands = commands.entity(trigger.entity);
+    for dependency in &derived.dependencies {
+        let Some(attr_dep) = bindings.how_to_insert_dependency.get(dependency) else {
+            error!(
+                "Derived<{}> depends on unregistered attribute {}.",
+                pretty_type_name::<T>(),
+                dependency
+            );
+            continue;
+        };
+        attr_dep(derived_of.0, &mut entity_commands);
+    }
+
+    commands.trigger(RecalculateExpression {
+        modifier_entity: trigger.entity,
+    });
+}
+
+/// When an input's current value changes, recompute the base value of the derived attribute.
+pub fn recalculate_derived_attribute<T: Attribute>(
+    trigger: On<RecalculateExpression>,
+    derived: Query<(&DerivedAttribute<T>, &DerivedAttributeOf)>,
+    mut set: ParamSet<(Query<AttributesRef, Without<IsResource>>, Query<&mut T>)>,
+    type_registry: Res<AppTypeRegistry>,
+    mut commands: Commands,
+) -> Result<(), BevyError> {
+    let Ok((derived, derived_of)) = derived.get(trigger.modifier_entity) else {
+        return Ok(());
+    };
+    let actor = derived_of.0;
+
+    let new_value = {
+        let p0 = set.p0();
+        let actor_ref = p0.get(actor)?;
+        let actor_context = ActorExprContext {
+            actor_context: &actor_ref,
+            type_registry: type_registry.0.clone(),
+        };
+        derived.expr.eval(&actor_context)?
+    };
+
+    let mut attributes = set.p1();
+    let mut attribute = attributes.get_mut(actor)?;
+    let old_value = attribute.base_value();
+    if !old_value.are_different(new_value) {
+        return Ok(());
+    }
+    attribute.set_base_value(new_value);
+
+    commands.trigger(MarkNodeDirty::<T> {
+        entity: actor,
+        phantom_data: Default::default(),
+    });
+    commands.trigger(BaseValueChanged::<T> {
+        phantom_data: Default::default(),
+        old: old_value,
+        new: new_value,
+        entity: actor,
+        source: actor,
+        effect: None,
+    });
+    Ok(())
+}
@@ -1 +1,2 @@
 pub mod clamps;
+pub mod derived;
@@ -2,7 +2,7 @@ use crate::context::{AbilityExprSchema, ActorExprSchema, EffectExprSchema};
 use crate::effect::AttributeDependents;
 use crate::inspector::pretty_type_name;
 use crate::math::{AbsDiff, SaturatingAttributes};
-use crate::modifier::{AttributeCalculator, AttributeCalculatorCached};
+use crate::modifier::{AggregationPipeline, AttributeCalculator, AttributeCalculatorCached};
 use crate::systems::MarkNodeDirty;
 use bevy::ecs::component::Mutable;
 use bevy::ecs::query::QueryData;
@@ -256,7 +256,7 @@ macro_rules! tag {
             //serde::Serialize,
             //serde::Deserialize,
         )]
-        #[reflect(Component)]
+        #[reflect(Component, Default)]
         pub struct $StructName;
     };
 }
@@ -287,9 +287,13 @@ pub struct AttributeQueryData<T: Attribute + 'static> {
 }
 
 impl<T: Attribute> AttributeQueryDataItem<'_, '_, T> {
-    pub fn update_attribute(&mut self, calculator: &AttributeCalculator<T>) -> bool {
+    pub fn update_attribute(
+        &mut self,
+        calculator: &AttributeCalculator<T>,
+        pipeline: &AggregationPipeline,
+    ) -> bool {
         let old_val = self.attribute.current_value();
-        let new_val = calculator.eval(self.attribute.base_value());
+        let new_val = calculator.eval(self.attribute.base_value(), pipeline);
 
         let has_changed = old_val.are_different(new_val);
         if has_changed {
@@ -298,12 +302,12 @@ impl<T: Attribute> AttributeQueryDataItem<'_, '_, T> {
         has_changed
     }
 
-    pub fn update_attribute_from_cache(&mut self) -> bool {
+    pub fn update_attribute_from_cache(&mut self, pipeline: &AggregationPipeline) -> bool {
         let old_val = self.attribute.current_value();
         let new_val = self
             .calculator_cache
             .calculator
-            .eval(self.attribute.base_value());
+            .eval(self.attribute.base_value(), pipeline);
 
         let has_changed = old_val.are_different(new_val);
         if has_changed {
@@ -2,7 +2,8 @@ use crate::assets::AbilityDef;
 use crate::attributes::Attribute;
 use crate::context::{AbilityExprContext, AbilityExprSchema, EffectExprContext, EffectExprSchema};
 use crate::inspector::pretty_type_name;
-use crate::modifier::EffectSubject;
+use crate::modifier::{AbilitySubject, EffectSubject};
+use crate::tags::{GameplayTag, GameplayTags};
 use bevy::asset::AssetId;
 use bevy::prelude::{Component, TypePath};
 use bevy::reflect::Reflect;
@@ -198,6 +199,7 @@ impl<C: Component> std::fmt::Debug for HasComponent<C> {
     }
 }
 
+/// The ability was granted from this definition.
 pub struct AbilityCondition {
     asset: AssetId<AbilityDef>,
 }
@@ -209,17 +211,15 @@ impl AbilityCondition {
 }
 
 impl ExprNode<bool, AbilityExprSchema> for AbilityCondition {
-    fn eval(&self, _ctx: &AbilityExprContext) -> Result<bool, ExpressionError> {
-        /*let path = Path::from_id(self.who, T::ID);
-        let any = ctx.get_any(&path)?;
-        let value = any.downcast_ref::<T::Property>().unwrap();
-
-        Ok(self.bounds.contains(&value))*/
-        todo!()
+    fn eval(&self, ctx: &AbilityExprContext) -> Result<bool, ExpressionError> {
+        Ok(ctx.ability_def == self.asset)
     }
 
+    // The definition of the ability is not reflected, only ability contexts provide it
     fn eval_dyn(&self, _ctx: &dyn ReadContext) -> Result<bool, ExpressionError> {
-        todo!()
+        Err(ExpressionError::FailedReflect(
+            "The ability definition can only be read from an ability context".into(),
+        ))
     }
 
     fn get_dependencies(&self, _deps: &mut HashSet<Path>) {}
@@ -230,3 +230,155 @@ impl std::fmt::Debug for AbilityCondition {
         write!(f, "Is Ability {}", self.asset)
     }
 }
+
+// Reads the gameplay tags of the subject. A subject without tags has none.
+fn subject_tags<'a>(
+    ctx: &'a dyn ReadContext,
+    who: impl std::fmt::Display,
+) -> Option<&'a GameplayTags> {
+    let path = Path::new(format!("{}.{}", who, pretty_type_name::<GameplayTags>()));
+    ctx.get_any(&path).ok()?.downcast_ref::<GameplayTags>()
+}
+
+/// The subject has the gameplay tag or one of its descendants.
+#[derive(Debug, Clone)]
+pub struct HasTag<S> {
+    who: S,
+    tag: GameplayTag,
+}
+
+impl<S> HasTag<S> {
+    pub fn new(tag: impl Into<GameplayTag>, who: S) -> Self {
+        Self {
+            who,
+            tag: tag.into(),
+        }
+    }
+
+    fn test(&self, tags: &GameplayTags) -> bool {
+        tags.has_tag(&self.tag)
+    }
+}
+
+impl HasTag<EffectSubject> {
+    pub fn target(tag: impl Into<GameplayTag>) -> Self {
+        Self::new(tag, EffectSubject::Target)
+    }
+
+    pub fn source(tag: impl Into<GameplayTag>) -> Self {
+        Self::new(tag, EffectSubject::Source)
+    }
+}
+
+impl HasTag<AbilitySubject> {
+    pub fn caster(tag: impl Into<GameplayTag>) -> Self {
+        Self::new(tag, AbilitySubject::Caster)
+    }
+}
+
+/// The subject has at least one of the gameplay tags.
+#[derive(Debug, Clone)]
+pub struct HasAnyTag<S> {
+    who: S,
+    tags: Vec<GameplayTag>,
+}
+
+impl<S> HasAnyTag<S> {
+    pub fn new<T: Into<GameplayTag>>(tags: impl IntoIterator<Item = T>, who: S) -> Self {
+        Self {
+            who,
+            tags: tags.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    fn test(&self, tags: &GameplayTags) -> bool {
+        tags.has_any(&self.tags)
+    }
+}
+
+impl HasAnyTag<EffectSubject> {
+    pub fn target<T: Into<GameplayTag>>(tags: impl IntoIterator<Item = T>) -> Self {
+        Self::new(tags, EffectSubject::Target)
+    }
+
+    pub fn source<T: Into<GameplayTag>>(tags: impl IntoIterator<Item = T>) -> Self {
+        Self::new(tags, EffectSubject::Source)
+    }
+}
+
+impl HasAnyTag<AbilitySubject> {
+    pub fn caster<T: Into<GameplayTag>>(tags: impl IntoIterator<Item = T>) -> Self {
+        Self::new(tags, AbilitySubject::Caster)
+    }
+}
+
+/// The subject has all the gameplay tags.
+#[derive(Debug, Clone)]
+pub struct HasAllTags<S> {
+    who: S,
+    tags: Vec<GameplayTag>,
+}
+
+impl<S> HasAllTags<S> {
+    pub fn new<T: Into<GameplayTag>>(tags: impl IntoIterator<Item = T>, who: S) -> Self {
+        Self {
+            who,
+            tags: tags.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    fn test(&self, tags: &GameplayTags) -> bool {
+        tags.has_all(&self.tags)
+    }
+}
+
+impl HasAllTags<EffectSubject> {
+    pub fn target<T: Into<GameplayTag>>(tags: impl IntoIterator<Item = T>) -> Self {
+        Self::new(tags, EffectSubject::Target)
+    }
+
+    pub fn source<T: Into<GameplayTag>>(tags: impl IntoIterator<Item = T>) -> Self {
+        Self::new(tags, EffectSubject::Source)
+    }
+}
+
+impl HasAllTags<AbilitySubject> {
+    pub fn caster<T: Into<GameplayTag>>(tags: impl IntoIterator<Item = T>) -> Self {
+        Self::new(tags, AbilitySubject::Caster)
+    }
+}
+
+// Implements the tag conditions for a schema. A subject without tags fails every query,
+// except `HasAllTags` with no tags.
+macro_rules! impl_tag_condition {
+    ($Condition:ident, $Subject:ty, $Schema:ty, $Context:ty) => {
+        impl ExprNode<bool, $Schema> for $Condition<$Subject> {
+            fn eval(&self, ctx: &$Context) -> Result<bool, ExpressionError> {
+                self.eval_dyn(ctx)
+            }
+
+            fn eval_dyn(&self, ctx: &dyn ReadContext) -> Result<bool, ExpressionError> {
+                let tags = subject_tags(ctx, self.who);
+                Ok(self.test(tags.unwrap_or(&GameplayTags::default())))
+            }
+
+            fn get_dependencies(&self, deps: &mut HashSet<Path>) {
+                deps.insert(Path::new(pretty_type_name::<GameplayTags>()));
+            }
+        }
+
+        impl Into<BoolExpr<$Schema>> for $Condition<$Subject> {
+            fn into(self) -> BoolExpr<$Schema> {
+                let node = BoolExprNode::Boxed(Box::new(self));
+                Expr::new(Arc::new(node))
+            }
+        }
+    };
+}
+
+impl_tag_condition!(HasTag, EffectSubject, EffectExprSchema, EffectExprContext);
+impl_tag_condition!(HasTag, AbilitySubject, AbilityExprSchema, AbilityExprContext);
+impl_tag_condition!(HasAnyTag, EffectSubject, EffectExprSchema, EffectExprContext);
+impl_tag_condition!(HasAnyTag, AbilitySubject, AbilityExprSchema, AbilityExprContext);
+impl_tag_condition!(HasAllTags, EffectSubject, EffectExprSchema, EffectExprContext);
+impl_tag_condition!(HasAllTags, AbilitySubject, AbilityExprSchema, AbilityExprContext);
@@ -6,7 +6,10 @@ mod conditions;
 mod systems;
 
 use crate::schedule::EffectsSet;
-pub use conditions::{AbilityCondition, ChanceCondition, HasComponent, IsAttributeWithinBounds};
+pub use conditions::{
+    AbilityCondition, ChanceCondition, HasAllTags, HasAnyTag, HasComponent, HasTag,
+    IsAttributeWithinBounds,
+};
 
 pub struct ConditionPlugin;
 
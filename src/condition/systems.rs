@@ -1,6 +1,9 @@
 use crate::assets::EffectDef;
 use crate::context::EffectExprContext;
-use crate::effect::{Effect, EffectInactive, EffectSource, EffectTarget, EffectTicker};
+use crate::effect::{
+    Effect, EffectActivated, EffectDeactivated, EffectInactive, EffectSource, EffectTarget,
+    EffectTicker,
+};
 use crate::{AttributesRef};
 use bevy::asset::Assets;
 use bevy::ecs::relationship::Relationship;
@@ -43,7 +46,8 @@ pub fn evaluate_effect_conditions(
             continue;
         };
 
-        let Some(effect) = effects.get(&effect.0) else {
+        let handle = &effect.0;
+        let Some(effect) = effects.get(handle) else {
             error!(
                 "Effect {} has no effect definition.",
                 effect_entity_ref.id()
@@ -55,6 +59,7 @@ pub fn evaluate_effect_conditions(
             target_actor: &target_actor_ref,
             source_actor: &source_actor_ref,
             effect_holder: &effect_entity_ref,
+            params: None,
             type_registry: type_registry.0.clone(),
         };
 
@@ -71,10 +76,30 @@ pub fn evaluate_effect_conditions(
             // Effect was inactive and its conditions are now met, so activate it.
             debug!("Effect {effect_entity} is now active.");
             commands.entity(effect_entity).remove::<EffectInactive>();
+
+            let event = EffectActivated {
+                entity: effect_entity,
+                effect: effect_entity,
+                target: target.get(),
+                source: source.get(),
+                handle: handle.clone(),
+            };
+            commands.trigger(event.on(effect_entity));
+            commands.trigger(event.on(target.get()));
         } else if !should_be_active && !is_inactive {
             // Effect was active and its conditions are no longer met, so deactivate it.
             debug!("Effect {effect_entity} is now inactive.");
             commands.entity(effect_entity).insert(EffectInactive);
+
+            let event = EffectDeactivated {
+                entity: effect_entity,
+                effect: effect_entity,
+                target: target.get(),
+                source: source.get(),
+                handle: handle.clone(),
+            };
+            commands.trigger(event.on(effect_entity));
+            commands.trigger(event.on(target.get()));
         }
     }
 }
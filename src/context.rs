@@ -1,10 +1,16 @@
 use crate::actors::SpawnActorCommand;
-use crate::assets::{ActorDef, EffectDef};
+use crate::assets::{AbilityDef, ActorDef, EffectDef};
+use crate::attributes::Attribute;
 use crate::effect::global_effect::{GlobalActor, GlobalEffects};
-use crate::effect::{ApplyEffectEvent, EffectTargeting};
+use crate::effect::{
+    ApplyEffectEvent, EffectParams, EffectTargeting, RemoveEffects, RemoveEffectsCommand,
+    is_param_path,
+};
 use crate::modifier::{AbilitySubject, EffectSubject};
 use crate::registry::Registry;
 use crate::registry::actor_registry::ActorToken;
+use crate::snapshot::{ActorSnapshot, RestoreActorCommand};
+use crate::tags::{GameplayTag, add_tags, remove_tags};
 use crate::{AppAttributeBindings, AttributesMut, AttributesRef};
 use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
@@ -33,11 +39,40 @@ impl<'s, 'w> Vitality<'w, 's> {
         target: Entity,
         source: Entity,
         handle: &Handle<EffectDef>,
+    ) {
+        self.apply_effect_with_params(target, source, handle, EffectParams::default());
+    }
+
+    /// Applies an effect with magnitudes supplied by the caller.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use vitality::prelude::*;
+    /// # use vitality::assets::EffectDef;
+    /// # use vitality::context::Vitality;
+    /// # use vitality::effect::EffectParams;
+    /// attribute!(Health, f32);
+    ///
+    /// fn cast_fireball(mut ctx: Vitality, caster: Entity, target: Entity, fireball: Handle<EffectDef>) {
+    ///     // The fireball reads its damage with EffectParams::value::<f32>("damage")
+    ///     let params = EffectParams::new().with("damage", 42.0);
+    ///     ctx.apply_effect_with_params(target, caster, &fireball, params);
+    /// }
+    /// ```
+    pub fn apply_effect_with_params(
+        &mut self,
+        target: Entity,
+        source: Entity,
+        handle: &Handle<EffectDef>,
+        params: EffectParams,
     ) {
         self.commands.trigger(ApplyEffectEvent {
             entity: target,
             targeting: EffectTargeting::new(source, target),
             handle: handle.clone(),
+            params,
         });
     }
 
@@ -52,12 +87,7 @@ impl<'s, 'w> Vitality<'w, 's> {
         effect: EffectDef,
     ) -> Handle<EffectDef> {
         let handle = self.effects.add(effect);
-
-        self.commands.trigger(ApplyEffectEvent {
-            entity: target,
-            targeting: EffectTargeting::new(source, target),
-            handle: handle.clone(),
-        });
+        self.apply_effect_to_target(target, source, &handle);
         handle
     }
 
@@ -94,12 +124,56 @@ impl<'s, 'w> Vitality<'w, 's> {
         self.spawn_actor_from_handle(&handle)
     }
 
+    /// Spawns an actor from a snapshot, restoring its attributes, effects and abilities.
+    pub fn restore_actor(&mut self, snapshot: ActorSnapshot) -> EntityCommands<'_> {
+        let mut entity_commands = self.commands.spawn_empty();
+        entity_commands.queue(RestoreActorCommand { snapshot });
+        entity_commands
+    }
+
     pub fn insert_actor(&mut self, entity: Entity, handle: &Handle<ActorDef>) {
         self.commands.entity(entity).queue(SpawnActorCommand {
             handle: handle.clone(),
         });
     }
 
+    /// Removes the effects of the target selected by the request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use vitality::context::Vitality;
+    /// # use vitality::effect::RemoveEffects;
+    /// # use vitality::tags::GameplayTag;
+    /// fn dispel(target: Entity, mut ctx: Vitality) {
+    ///     // Removes a stack from 2 poisons
+    ///     let removal = RemoveEffects::matching(GameplayTag::new_static("Debuff.Poison"))
+    ///         .at_most(2)
+    ///         .stacks(1);
+    ///     ctx.remove_effects(target, removal);
+    /// }
+    /// ```
+    pub fn remove_effects(&mut self, target: Entity, removal: RemoveEffects) {
+        self.commands
+            .entity(target)
+            .queue(RemoveEffectsCommand { removal });
+    }
+
+    /// Adds a reference to a gameplay tag on the actor.
+    pub fn add_tag(&mut self, actor: Entity, tag: impl Into<GameplayTag>) {
+        let tag = tag.into();
+        self.commands
+            .queue(move |world: &mut World| add_tags(world, actor, &[tag]));
+    }
+
+    /// Removes a reference to a gameplay tag from the actor.
+    pub fn remove_tag(&mut self, actor: Entity, tag: impl Into<GameplayTag>) {
+        let tag = tag.into();
+        self.commands
+            .queue(move |world: &mut World| remove_tags(world, actor, &[tag]));
+    }
+
     pub fn add_global_effect(&mut self, handle: Handle<EffectDef>) {
         self.global_effects.push(handle);
     }
@@ -121,6 +195,9 @@ impl<'s, 'w> Vitality<'w, 's> {
     }
 }
 
+/// A deferred [`BaseValueChanged`](crate::BaseValueChanged) trigger.
+pub type BaseValueNotifier = Box<dyn FnOnce(&mut Commands) + Send + Sync>;
+
 pub struct EffectExprContextMut<'w, 's> {
     pub source_actor: &'w mut AttributesMut<'w, 's>,
     pub target_actor: Option<&'w mut AttributesMut<'w, 's>>,
@@ -128,6 +205,9 @@ pub struct EffectExprContextMut<'w, 's> {
 
     pub type_registry: TypeRegistryArc,
     pub type_bindings: AppAttributeBindings,
+
+    /// Base value changes recorded while writing to the actors.
+    pub base_value_changes: Vec<BaseValueNotifier>,
 }
 
 impl<'w, 's> EffectExprContextMut<'w, 's> {
@@ -155,6 +235,13 @@ impl<'w, 's> EffectExprContextMut<'w, 's> {
             EffectSubject::Effect => self.owner,
         }
     }
+
+    /// Triggers the [`BaseValueChanged`](crate::BaseValueChanged) events recorded by the writes.
+    pub fn notify_base_value_changes(&mut self, commands: &mut Commands) {
+        for notify in self.base_value_changes.drain(..) {
+            notify(commands);
+        }
+    }
 }
 
 impl WriteContext for EffectExprContextMut<'_, '_> {
@@ -168,11 +255,18 @@ impl WriteContext for EffectExprContextMut<'_, '_> {
 
         let (_, component, _) = split_path(&*path.0).expect("Wrong path in reflect path");
 
-        let any_to_reflect = {
+        let (any_to_reflect, notify_fn) = {
             let bindings = self.type_bindings.internal.read().unwrap();
-            *bindings.convert.get(component).unwrap()
+            (
+                *bindings.convert.get(component).unwrap(),
+                *bindings.notify_base_value_changed.get(component).unwrap(),
+            )
         };
 
+        let entity = self.entity(who);
+        let source = self.source_actor.id();
+        let effect = Some(self.owner.id());
+
         let reflect_component = {
             let registry_bindings = self.type_registry.read();
             let Some(type_registration) = registry_bindings.get_with_short_type_path(component) else {
@@ -200,7 +294,12 @@ impl WriteContext for EffectExprContextMut<'_, '_> {
             ExpressionError::FailedReflect("Type mismatch while converting expression value".into())
         })?;
 
+        let notifier = notify_fn(entity, source, effect, &*dyn_partial_reflect, &*value);
         dyn_partial_reflect.apply(value_reflect);
+
+        if let Some(notifier) = notifier {
+            self.base_value_changes.push(notifier);
+        }
         Ok(())
     }
 }
@@ -237,6 +336,9 @@ pub struct EffectExprContext<'w, 's> {
     pub source_actor: &'w AttributesRef<'w, 's>,
     pub target_actor: &'w AttributesRef<'w, 's>,
     pub effect_holder: &'w AttributesRef<'w, 's>,
+    /// The parameters of an application without an effect entity yet.
+    /// Otherwise, they are read from the [`EffectParams`] of the effect holder.
+    pub params: Option<&'w EffectParams>,
 
     pub type_registry: TypeRegistryArc,
 }
@@ -249,12 +351,36 @@ impl EffectExprContext<'_, '_> {
             EffectSubject::Effect => self.effect_holder,
         }
     }
+
+    pub fn current_value<T: Attribute>(&self, who: EffectSubject) -> Option<T::Property> {
+        self.attribute_ref(who).get::<T>().map(T::current_value)
+    }
+
+    pub fn base_value<T: Attribute>(&self, who: EffectSubject) -> Option<T::Property> {
+        self.attribute_ref(who).get::<T>().map(T::base_value)
+    }
+
+    /// The parameter supplied with the application of the effect.
+    pub fn param(&self, name: &str) -> Option<f64> {
+        self.params
+            .or_else(|| self.effect_holder.get::<EffectParams>())
+            .and_then(|params| params.get(name))
+    }
 }
 
 impl ReadContext for EffectExprContext<'_, '_> {
     fn get_any(&self, path: &Path) -> Result<&dyn Any, ExpressionError> {
         let who = EffectSubject::try_from(path)
             .map_err(|_| ExpressionError::InvalidPath(path.0.clone()))?;
+
+        if matches!(who, EffectSubject::Effect) && is_param_path(&path.0) {
+            let params = self
+                .params
+                .or_else(|| self.effect_holder.get::<EffectParams>())
+                .ok_or_else(|| ExpressionError::InvalidPath(path.0.clone()))?;
+            return Ok(params.read_path(path)?);
+        }
+
         let actor = self.attribute_ref(who);
 
         reflect_path(path, actor, &self.type_registry)
@@ -273,6 +399,8 @@ pub struct AbilityExprContext<'w, 's> {
     pub caster_ref: &'w AttributesRef<'w, 's>,
     pub ability_ref: &'w AttributesRef<'w, 's>,
     pub target_ref: &'w AttributesRef<'w, 's>,
+    /// The definition the ability was granted from.
+    pub ability_def: AssetId<AbilityDef>,
 
     pub type_registry: TypeRegistryArc,
 }
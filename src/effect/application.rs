@@ -1,9 +1,11 @@
 use crate::assets::EffectDef;
-use crate::context::EffectExprContext;
+use crate::context::{EffectExprContext, EffectExprSchema};
 use crate::effect::stacks::NotifyAddStackEvent;
 use crate::effect::timing::{EffectDuration, EffectTicker};
 use crate::effect::{
-    AppliedEffects, Effect, EffectSource, EffectStackingPolicy, EffectTarget, EffectTargeting,
+    AppliedEffects, BlockReason, Effect, EffectApplicationBlocked, EffectApplied,
+    EffectImmunities, EffectInactive, EffectParams, EffectSource, EffectStackingPolicy,
+    EffectTarget, EffectTargeting, ExecuteEffectMessage, StackingScope,
 };
 use crate::graph::NodeType;
 use crate::modifier::ModifierOf;
@@ -15,6 +17,8 @@ use bevy::prelude::*;
 use std::cmp::PartialEq;
 use bevy::ecs::resource::IsResource;
 use bevy::reflect::TypeRegistryArc;
+use express_it::expr::Expr;
+use std::time::Duration;
 
 /// Describes how the effect is applied to entities
 #[derive(Debug, Clone, Reflect, PartialEq)]
@@ -100,7 +104,7 @@ impl EffectApplicationPolicy {
         }
     }
 
-    pub fn to_bundles(&self) -> (Option<impl Bundle>, Option<impl Bundle>) {
+    pub fn to_bundles(&self) -> (Option<EffectDuration>, Option<EffectTicker>) {
         let duration = match self {
             EffectApplicationPolicy::Temporary { duration } => Some(EffectDuration::new(duration)),
             EffectApplicationPolicy::PeriodicTemporary { duration, .. } => {
@@ -126,6 +130,8 @@ pub struct ApplyEffectEvent {
     pub entity: Entity,
     pub targeting: EffectTargeting,
     pub handle: Handle<EffectDef>,
+    /// Magnitudes supplied by the caller, read by expressions as `effect.params.<name>`.
+    pub params: EffectParams,
 }
 
 impl ApplyEffectEvent {
@@ -151,23 +157,90 @@ impl ApplyEffectEvent {
             target_actor: &target_actor_ref,
             source_actor: &source_actor_ref,
             effect_holder: &source_actor_ref, // TODO: Make optional
+            params: Some(&self.params),
             type_registry: type_registry.clone(),
         };
 
-        // Determines whether the effect should activate
-        let should_apply = effect
-            .activate_conditions
-            .iter()
-            .all(|condition| condition.eval(&context).unwrap_or(false));
-
-        if !should_apply {
+        if !effect.should_activate(&context) {
             return Ok(());
         }
 
         self.apply_modifiers(actors, &mut effect.modifiers.iter(), commands);
+
+        if !effect.executions.is_empty() {
+            commands.write_message(ExecuteEffectMessage {
+                source_entity: self.targeting.source(),
+                target_entity: self.targeting.target(),
+                effect_entity: None,
+                handle: self.handle.clone(),
+                params: Some(self.params.clone()),
+            });
+        }
+
+        // Persistent effects notify their application once spawned
+        if effect.application_policy == EffectApplicationPolicy::Instant {
+            commands.trigger(EffectApplied {
+                entity: self.targeting.target(),
+                effect: None,
+                target: self.targeting.target(),
+                source: self.targeting.source(),
+                handle: self.handle.clone(),
+            });
+        }
         Ok(())
     }
 
+    /// Finds the immunity rule of the target, or of an active effect on it, blocking the effect.
+    fn find_application_block(
+        &self,
+        effect: &EffectDef,
+        actors: &Query<(Option<&AppliedEffects>, AttributesMut), (Without<Effect>, Without<IsResource>)>,
+        effects: &Query<(&Effect, &EffectSource, Has<EffectInactive>)>,
+        effect_assets: &Assets<EffectDef>,
+        type_registry: TypeRegistryArc,
+    ) -> Option<(BlockReason, Option<Entity>)> {
+        let (applied_effects, target_actor_ref) = actors.get(self.targeting.target()).ok()?;
+        // Only conditions read the source, the other rules apply without it
+        let source_actor_ref = actors
+            .get(self.targeting.source())
+            .ok()
+            .map(|(_, source_actor_ref)| source_actor_ref);
+
+        let context = source_actor_ref.as_ref().map(|source_actor_ref| EffectExprContext {
+            target_actor: &target_actor_ref,
+            source_actor: source_actor_ref,
+            effect_holder: source_actor_ref,
+            params: Some(&self.params),
+            type_registry,
+        });
+
+        let actor_rules = target_actor_ref
+            .get::<EffectImmunities>()
+            .into_iter()
+            .flat_map(|immunities| immunities.0.iter().map(|rule| (rule, None)));
+
+        // Inactive effects do not grant their immunities
+        let effect_rules = applied_effects
+            .into_iter()
+            .flat_map(|applied| applied.iter())
+            .filter_map(|effect_entity| {
+                let (applied_effect, _, is_inactive) = effects.get(effect_entity).ok()?;
+                let applied_def = effect_assets.get(&applied_effect.0)?;
+                (!is_inactive).then_some((effect_entity, applied_def))
+            })
+            .flat_map(|(effect_entity, applied_def)| {
+                applied_def
+                    .immunities
+                    .iter()
+                    .map(move |rule| (rule, Some(effect_entity)))
+            });
+
+        actor_rules.chain(effect_rules).find_map(|(rule, blocked_by)| {
+            rule.blocks(&self.handle, effect, context.as_ref())
+                .map(|reason| (reason, blocked_by))
+        })
+    }
+
     fn apply_modifiers<'a, I>(
         &self,
         _actors: &'a mut Query<(Option<&AppliedEffects>, AttributesMut), (Without<Effect>, Without<IsResource>)>,
@@ -181,6 +254,7 @@ impl ApplyEffectEvent {
                 self.targeting.source(),
                 self.targeting.target(),
                 self.entity,
+                Some(&self.params),
                 commands,
             );
         }
@@ -191,12 +265,13 @@ impl ApplyEffectEvent {
         commands: &mut Commands,
         effect: &EffectDef,
         actors: &mut Query<(Option<&AppliedEffects>, AttributesMut), (Without<Effect>, Without<IsResource>)>,
-        effects: &mut Query<&Effect>,
+        effects: &mut Query<(&Effect, &EffectSource, Has<EffectInactive>)>,
         add_stack_event: &mut MessageWriter<NotifyAddStackEvent>,
         type_registry: TypeRegistryArc,
         type_bindings: AppAttributeBindings,
     ) -> Result<(), BevyError> {
-        // We want to know whether an effect with the same handle already points to the actor
+        // We want to know whether an effect with the same handle already points to the actor,
+        // from the same source if the effect stacks per source
         let (optional_effects, _) = actors.get_mut(self.targeting.target())?;
         let effects_on_actor = match optional_effects {
             None => {
@@ -204,10 +279,14 @@ impl ApplyEffectEvent {
             }
             Some(effects_on_actor) => {
                 let effects = effects_on_actor.iter().filter_map(|effect_entity| {
-                    let Ok(other_effect) = effects.get(effect_entity) else {
+                    let Ok((other_effect, other_source, _)) = effects.get(effect_entity) else {
                         return None;
                     };
-                    if other_effect.0.id() == self.handle.id() {
+                    let same_scope = match effect.stacking_scope {
+                        StackingScope::PerTarget => true,
+                        StackingScope::PerSource => other_source.0 == self.targeting.source(),
+                    };
+                    if other_effect.0.id() == self.handle.id() && same_scope {
                         Some(effect_entity)
                     } else {
                         None
@@ -217,32 +296,44 @@ impl ApplyEffectEvent {
             }
         };
 
+        let (_, source_actor_ref) = actors.get(self.targeting.source())?;
+        let (_, target_actor_ref) = actors.get(self.targeting.target())?;
+
+        let context = EffectExprContext {
+            target_actor: &target_actor_ref,
+            source_actor: &source_actor_ref,
+            effect_holder: &source_actor_ref, // TODO: Should this be the source actor? The effect doesn't exist for instant effects.
+            params: Some(&self.params),
+            type_registry,
+        };
+
         match effect.stacking_policy {
             EffectStackingPolicy::None => {
                 // Continue spawning effect
             }
-            EffectStackingPolicy::Add { .. } | EffectStackingPolicy::RefreshDuration => {
+            EffectStackingPolicy::Add { .. }
+            | EffectStackingPolicy::AddAndRefresh { .. }
+            | EffectStackingPolicy::RefreshDuration => {
                 if effects_on_actor.len() > 0 {
                     debug!("Effect already exists on actor. Adding stacks per definition.");
+                    // Refreshed durations depend on this application
+                    let refresh_duration = effect
+                        .refresh_duration
+                        .as_ref()
+                        .or(effect.duration.as_ref())
+                        .filter(|_| effect.stacking_policy.refreshes_duration())
+                        .map(|expr| eval_duration(expr, &context))
+                        .transpose()?;
                     add_stack_event.write(NotifyAddStackEvent {
                         effect_entity: *effects_on_actor.first().unwrap(),
                         handle: self.handle.clone(),
+                        refresh_duration,
                     });
                     return Ok(());
                 }
             }
         }
 
-        let (_, source_actor_ref) = actors.get(self.targeting.source())?;
-        let (_, target_actor_ref) = actors.get(self.targeting.target())?;
-
-        let context = EffectExprContext {
-            target_actor: &target_actor_ref,
-            source_actor: &source_actor_ref,
-            effect_holder: &source_actor_ref, // TODO: Should this be the source actor? The effect doesn't exist for instant effects.
-            type_registry,
-        };
-
         // Determines whether the effect should activate
         let should_be_applied = effect
             .attach_conditions
@@ -253,6 +344,37 @@ impl ApplyEffectEvent {
             return Ok(());
         }
 
+        let effect_entity =
+            self.spawn_effect_entity(commands, effect, &context, &type_bindings)?;
+
+        let event = EffectApplied {
+            entity: effect_entity,
+            effect: Some(effect_entity),
+            target: self.targeting.target(),
+            source: self.targeting.source(),
+            handle: self.handle.clone(),
+        };
+        commands.trigger(event.on(effect_entity));
+        commands.trigger(event.on(self.targeting.target()));
+
+        Ok(())
+    }
+
+    /// Spawns the effect entity with its modifiers and triggers, without the stacking rules,
+    /// attach conditions and notifications of an application.
+    pub(crate) fn spawn_effect_entity(
+        &self,
+        commands: &mut Commands,
+        effect: &EffectDef,
+        context: &EffectExprContext,
+        type_bindings: &AppAttributeBindings,
+    ) -> Result<Entity, BevyError> {
+        // Converts the policy to components that can be added to the entity
+        let (mut duration, ticker) = effect.application_policy.to_bundles();
+        if let (Some(duration), Some(expr)) = (&mut duration, &effect.duration) {
+            duration.set_duration(eval_duration(expr, context)?);
+        }
+
         let mut effect_commands = commands.spawn_empty();
         let effect_entity = effect_commands.id();
         for effect_fn in &effect.effect_fn {
@@ -265,15 +387,26 @@ impl ApplyEffectEvent {
             EffectTarget(self.targeting.target()),
             EffectSource(self.targeting.source()),
             Effect(self.handle.clone()),
+            self.params.clone(),
         ));
 
-        // Converts the policy to components that can be added to the entity
-        let (duration, ticker) = effect.application_policy.to_bundles();
         if let Some(duration) = duration {
             effect_commands.insert(duration);
         }
-        if let Some(ticker) = ticker {
-            effect_commands.insert(ticker);
+        if let Some(mut ticker) = ticker {
+            if let Some(interval) = &effect.tick_interval {
+                match interval.eval(context) {
+                    Ok(seconds) if seconds > 0.0 => ticker.set_interval(seconds),
+                    Ok(seconds) => {
+                        warn!("Effect {:?} has invalid interval {}.", self.handle, seconds)
+                    }
+                    Err(err) => error!(
+                        "Failed to evaluate interval of effect {:?}: {}",
+                        self.handle, err
+                    ),
+                }
+            }
+            effect_commands.insert(ticker.tick_on_apply(effect.tick_on_apply));
         }
 
         // Spawn effect modifiers
@@ -281,8 +414,8 @@ impl ApplyEffectEvent {
         effect.modifiers.iter().for_each(|modifier| {
             let mut entity_commands = commands.spawn(ModifierOf(effect_entity));
             modifier.spawn_persistent_modifier(
-                source_actor_ref.id(),
-                &context,
+                context.source_actor.id(),
+                context,
                 &bindings,
                 &mut entity_commands,
             );
@@ -300,14 +433,24 @@ impl ApplyEffectEvent {
             triggers.apply(&mut entity_commands);
         }
 
-        Ok(())
+        Ok(effect_entity)
     }
 }
 
+/// Evaluates a duration of the effect in seconds, in the context of its application.
+fn eval_duration(
+    expr: &Expr<f64, EffectExprSchema>,
+    context: &EffectExprContext,
+) -> Result<Duration, BevyError> {
+    let seconds = expr.eval(context)?;
+    Duration::try_from_secs_f64(seconds.max(0.0))
+        .map_err(|err| format!("Invalid effect duration {}: {}", seconds, err).into())
+}
+
 pub(crate) fn apply_effect_event_observer(
     trigger: On<ApplyEffectEvent>,
     mut actors: Query<(Option<&AppliedEffects>, AttributesMut), (Without<Effect>, Without<IsResource>)>,
-    mut effects: Query<&Effect>,
+    mut effects: Query<(&Effect, &EffectSource, Has<EffectInactive>)>,
     effect_assets: Res<Assets<EffectDef>>,
     mut writer: MessageWriter<NotifyAddStackEvent>,
     mut commands: Commands,
@@ -318,6 +461,29 @@ pub(crate) fn apply_effect_event_observer(
         .get(&trigger.handle)
         .ok_or("No effect asset.")?;
 
+    if let Some((reason, blocked_by)) = trigger.find_application_block(
+        effect,
+        &actors,
+        &effects,
+        &effect_assets,
+        type_registry.0.clone(),
+    ) {
+        debug!(
+            "Application of effect {:?} to {} blocked: {:?}",
+            trigger.handle,
+            trigger.targeting.target(),
+            reason
+        );
+        commands.trigger(EffectApplicationBlocked {
+            entity: trigger.targeting.target(),
+            source: trigger.targeting.source(),
+            handle: trigger.handle.clone(),
+            reason,
+            blocked_by,
+        });
+        return Ok(());
+    }
+
     if effect.application_policy.should_apply_now() {
         trigger.apply_instant_effect(
             &mut actors,
@@ -345,7 +511,10 @@ pub(crate) fn apply_effect_event_observer(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::ability::{AbilityBuilder, AbilityContext, GrantedAbilities};
     use crate::actors::ActorBuilder;
+    use crate::assets::AbilityDef;
+    use crate::attribute::clamps::Clamp;
     use crate::condition::IsAttributeWithinBounds;
     use crate::context::Vitality;
     use crate::effect::builder::EffectBuilder;
@@ -353,7 +522,7 @@ mod test {
     use crate::prelude::*;
     use crate::registry::effect_registry::EffectToken;
     use crate::registry::{Registry, RegistryMut};
-    use crate::{AttributesPlugin, attribute, init_attribute};
+    use crate::{AttributesPlugin, BaseValueChanged, attribute, init_attribute};
     use bevy::ecs::system::RunSystemOnce;
 
     attribute!(TestA, f32);
@@ -434,4 +603,152 @@ mod test {
         // The new attribute value must be present
         assert_eq!(test_c.current_value(), init_value + modifier_value);
     }
+
+    #[derive(Resource, Default)]
+    struct BaseValueChanges(Vec<(Entity, u32, u32)>);
+
+    #[test]
+    fn test_instant_effect_triggers_base_value_changed() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+        app.add_plugins(init_attribute::<TestInt>);
+        app.init_resource::<BaseValueChanges>();
+        app.add_observer(
+            |trigger: On<BaseValueChanged<TestInt>>, mut changes: ResMut<BaseValueChanges>| {
+                changes.0.push((trigger.source, trigger.old, trigger.new));
+            },
+        );
+
+        app.add_systems(Startup, (prepare_effects, prepare_actor).chain());
+
+        app.update();
+
+        let mut query = app.world_mut().query::<(Entity, &TestInt)>();
+        let (actor_entity, _) = query.single(app.world()).unwrap();
+
+        app.world_mut()
+            .run_system_once(move |mut ctx: Vitality| {
+                let effect = EffectBuilder::instant()
+                    .modify::<TestInt>(10u32, ModOp::Add, EffectSubject::Target)
+                    .build();
+
+                ctx.apply_dynamic_effect_to_self(actor_entity, effect);
+            })
+            .unwrap();
+
+        app.update();
+
+        let changes = app.world().resource::<BaseValueChanges>();
+        assert_eq!(changes.0, vec![(actor_entity, 50, 60)]);
+    }
+
+    #[derive(Resource, Default)]
+    struct BaseValueOrigins(Vec<(Entity, u32, u32, Entity, Option<Entity>)>);
+
+    /// Changes TestInt with a periodic effect from another actor, a clamp and an ability cost.
+    /// Asserts that each change reports its values, source and effect.
+    #[test]
+    fn test_base_value_changed_reports_origin() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+        app.add_plugins(init_attribute::<TestInt>);
+        app.init_resource::<BaseValueOrigins>();
+        app.add_observer(
+            |trigger: On<BaseValueChanged<TestInt>>, mut origins: ResMut<BaseValueOrigins>| {
+                origins.0.push((
+                    trigger.entity,
+                    trigger.old,
+                    trigger.new,
+                    trigger.source,
+                    trigger.effect,
+                ));
+            },
+        );
+
+        app.update();
+
+        let bolt = app
+            .world_mut()
+            .run_system_once(|mut abilities: ResMut<Assets<AbilityDef>>| {
+                abilities.add(AbilityBuilder::new().with_cost::<TestInt>(3u32).build())
+            })
+            .unwrap();
+        let bolt_def = bolt.id();
+        let (actor, source) = app
+            .world_mut()
+            .run_system_once(move |mut ctx: Vitality| {
+                let actor = ActorBuilder::new()
+                    .with::<TestInt>(10)
+                    .clamp::<TestInt>(0u32, 100u32)
+                    .grant_ability(&bolt)
+                    .build();
+                let source = ActorBuilder::new().build();
+                (
+                    ctx.add_spawn_actor(actor).id(),
+                    ctx.add_spawn_actor(source).id(),
+                )
+            })
+            .unwrap();
+        app.update();
+
+        // A modifier applied by a periodic effect
+        app.world_mut()
+            .run_system_once(move |mut ctx: Vitality| {
+                ctx.apply_dynamic_effect_to_target(
+                    actor,
+                    source,
+                    EffectBuilder::every_second_permanently(1.0)
+                        .modify::<TestInt>(5u32, ModOp::Add, EffectSubject::Target)
+                        .tick_on_apply()
+                        .with_max_ticks(1)
+                        .build(),
+                );
+            })
+            .unwrap();
+        let effect = app
+            .world()
+            .get::<AppliedEffects>(actor)
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+        app.update();
+        app.update();
+
+        // A clamp lowered below the base value
+        app.world_mut()
+            .get_mut::<Clamp<TestInt>>(actor)
+            .unwrap()
+            .max_limit = 12;
+        app.world_mut()
+            .get_mut::<TestInt>(actor)
+            .unwrap()
+            .set_changed();
+        app.update();
+
+        // A cost written by the ability
+        app.world_mut()
+            .run_system_once(move |mut abilities: AbilityContext| {
+                abilities.try_activate_by_def(actor, bolt_def);
+            })
+            .unwrap();
+        app.update();
+        let ability = app
+            .world()
+            .get::<GrantedAbilities>(actor)
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+
+        let origins = &app.world().resource::<BaseValueOrigins>().0;
+        assert_eq!(
+            *origins,
+            vec![
+                (actor, 10, 15, source, Some(effect)),
+                (actor, 15, 12, actor, None),
+                (actor, 12, 9, actor, Some(ability)),
+            ]
+        );
+    }
 }
@@ -1,17 +1,22 @@
 use crate::assets::EffectDef;
 use crate::attributes::Attribute;
 use crate::condition::IsAttributeWithinBounds;
-use crate::effect::EffectStackingPolicy;
+use crate::effect::{
+    ChainTrigger, EffectExecution, EffectStackingPolicy, ImmunityRule, StackExpiration, StackScaling,
+    StackingScope,
+};
 use crate::effect::application::EffectApplicationPolicy;
-use crate::modifier::{AttributeModifier, ModOp, EffectSubject};
+use crate::modifier::{AttributeModifier, ModOp, ModifierCategories, EffectSubject};
 use crate::mutator::EntityActions;
+use crate::tags::GameplayTag;
 use bevy::ecs::system::IntoObserverSystem;
-use bevy::prelude::{Bundle, Entity, EntityCommands, EntityEvent, Name};
+use bevy::prelude::{Bundle, Entity, EntityCommands, EntityEvent, Handle, Name};
 use express_it::expr::Expr;
 use express_it::logic::{BoolExpr, BoolExprNode};
 use std::ops::RangeBounds;
 use std::sync::Arc;
 use crate::context::{ EffectExprSchema};
+use smol_str::SmolStr;
 
 pub struct EffectBuilder {
     def: EffectDef,
@@ -21,14 +26,26 @@ impl EffectBuilder {
     pub fn new(application: EffectApplicationPolicy) -> Self {
         Self {
             def: EffectDef {
+                token: None,
                 application_policy: application,
+                duration: None,
+                refresh_duration: None,
+                tick_on_apply: false,
+                max_ticks: None,
+                tick_interval: None,
                 stacking_policy: EffectStackingPolicy::None,
+                stacking_scope: StackingScope::PerTarget,
+                stack_expiration: StackExpiration::All,
+                chained_effects: vec![],
                 effect_fn: vec![],
                 activate_conditions: vec![],
                 attach_conditions: vec![],
+                granted_tags: vec![],
+                immunities: vec![],
                 on_actor_triggers: vec![],
                 on_effect_triggers: vec![],
                 modifiers: vec![],
+                executions: vec![],
             },
         }
     }
@@ -45,6 +62,22 @@ impl EffectBuilder {
         Self::new(EffectApplicationPolicy::for_seconds(duration))
     }
 
+    /// A temporary effect whose duration in seconds is evaluated when it is applied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use vitality::prelude::*;
+    /// attribute!(StunTaken, f64);
+    ///
+    /// // A two-second stun, scaled by the stun duration taken by the target
+    /// let stun = EffectBuilder::for_duration(StunTaken::lit(2.0) * StunTaken::dst())
+    ///     .build();
+    /// ```
+    pub fn for_duration(duration: impl Into<Expr<f64, EffectExprSchema>>) -> Self {
+        Self::for_seconds(0.0).with_duration(duration)
+    }
+
     pub fn every_second_permanently(interval: f32) -> Self {
         Self::new(EffectApplicationPolicy::every_seconds(interval))
     }
@@ -55,6 +88,11 @@ impl EffectBuilder {
         ))
     }
 
+    /// A periodic effect expiring after it ticked `ticks` times.
+    pub fn every_second_for_ticks(interval: f32, ticks: u32) -> Self {
+        Self::every_second_permanently(interval).with_max_ticks(ticks)
+    }
+
     /// Modifies an attribute.
     ///
     /// A [Value](crate::attributes::Value) represents the value of the change to the attribute.
@@ -81,10 +119,35 @@ impl EffectBuilder {
     ///     .build();
     /// ```
     pub fn modify<T: Attribute>(
+        self,
+        expr: impl Into<Expr<T::Property, EffectExprSchema>>,
+        op: ModOp,
+        who: EffectSubject,
+    ) -> Self {
+        self.modify_scaled::<T>(expr, op, who, StackScaling::None)
+    }
+
+    /// Modifies an attribute with a magnitude scaled by the effect's [Stacks](crate::effect::Stacks)
+    /// and [EffectIntensity](crate::effect::EffectIntensity).
+    ///
+    /// # Example
+    /// ```
+    /// # use vitality::prelude::*;
+    /// # use vitality::effect::{EffectStackingPolicy, StackScaling};
+    /// attribute!(Health, f32);
+    ///
+    /// // Each stack of poison deals 5 damage per tick.
+    /// let poison = EffectBuilder::every_second_for_duration(1.0, 10.0)
+    ///     .modify_scaled::<Health>(5.0, ModOp::Sub, EffectSubject::Target, StackScaling::Linear)
+    ///     .with_stacking_policy(EffectStackingPolicy::Add { count: 1, max_stack: 5 })
+    ///     .build();
+    /// ```
+    pub fn modify_scaled<T: Attribute>(
         mut self,
         expr: impl Into<Expr<T::Property, EffectExprSchema>>,
         op: ModOp,
         who: EffectSubject,
+        scaling: StackScaling,
     ) -> Self {
         let expr = expr.into();
         self.def.modifiers.push(Box::new(AttributeModifier::<T> {
@@ -92,10 +155,149 @@ impl EffectBuilder {
             value: T::Property::default(),
             who,
             operation: op,
+            scaling,
+            channel: None,
+            magnitude: None,
         }));
         self
     }
 
+    /// Modifies an attribute through a named channel of its [AggregationPipeline](crate::modifier::AggregationPipeline).
+    ///
+    /// # Example
+    /// ```
+    /// # use vitality::prelude::*;
+    /// attribute!(Damage, f32);
+    ///
+    /// // Added after the multipliers, given a pipeline with a "final_flat" stage.
+    /// let effect = EffectBuilder::permanent()
+    ///     .modify_channel::<Damage>(10.0, "final_flat", EffectSubject::Target)
+    ///     .build();
+    /// ```
+    pub fn modify_channel<T: Attribute>(
+        mut self,
+        expr: impl Into<Expr<T::Property, EffectExprSchema>>,
+        channel: impl Into<SmolStr>,
+        who: EffectSubject,
+    ) -> Self {
+        let expr = expr.into();
+        self.def.modifiers.push(Box::new(AttributeModifier::<T> {
+            expr: expr.clone(),
+            value: T::Property::default(),
+            who,
+            operation: ModOp::Add,
+            scaling: StackScaling::None,
+            channel: Some(channel.into()),
+            magnitude: None,
+        }));
+        self
+    }
+
+    /// Applies a variant of a modifier enum to the channel of its category.
+    /// The attribute should be initialized with
+    /// [`init_attribute_with_calculator`](crate::init_attribute_with_calculator).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use vitality::prelude::*;
+    /// # use vitality::attribute_calculator;
+    /// attribute!(Damage, f32);
+    ///
+    /// #[attribute_calculator]
+    /// enum DamageMod {
+    ///     #[additive]
+    ///     Flat(f64),
+    ///     #[increased]
+    ///     Increased(f64),
+    ///     #[multiplicative]
+    ///     More(f64),
+    /// }
+    ///
+    /// // 50% more damage
+    /// let effect = EffectBuilder::permanent()
+    ///     .modify_with::<Damage>(DamageMod::More(0.5), EffectSubject::Target)
+    ///     .build();
+    /// ```
+    pub fn modify_with<T: Attribute>(
+        mut self,
+        modifier: impl ModifierCategories,
+        who: EffectSubject,
+    ) -> Self {
+        // The magnitude is kept as is, since integer properties would truncate fractions
+        let operation = modifier.operation();
+        self.def.modifiers.push(Box::new(AttributeModifier::<T> {
+            expr: T::Property::default().into(),
+            value: T::Property::default(),
+            who,
+            operation,
+            scaling: StackScaling::None,
+            channel: Some(SmolStr::new_static(operation.channel())),
+            magnitude: Some(modifier.magnitude()),
+        }));
+        self
+    }
+
+    /// Evaluates the duration of the effect in seconds when it is applied, with the source
+    /// and the target of the application. Effects without a duration are not affected.
+    pub fn with_duration(mut self, duration: impl Into<Expr<f64, EffectExprSchema>>) -> Self {
+        self.def.duration = Some(duration.into());
+        self
+    }
+
+    /// Evaluates the duration set when a new application refreshes the effect.
+    /// See [`EffectStackingPolicy::refreshes_duration`].
+    pub fn with_refresh_duration(
+        mut self,
+        duration: impl Into<Expr<f64, EffectExprSchema>>,
+    ) -> Self {
+        self.def.refresh_duration = Some(duration.into());
+        self
+    }
+
+    /// Ticks the periodic effect as soon as it is applied, instead of after a first interval.
+    pub fn tick_on_apply(mut self) -> Self {
+        self.def.tick_on_apply = true;
+        self
+    }
+
+    /// Expires the periodic effect after it ticked `ticks` times.
+    pub fn with_max_ticks(mut self, ticks: u32) -> Self {
+        self.def.max_ticks = Some(ticks);
+        self
+    }
+
+    /// Computes the interval of the periodic effect in seconds when it is applied,
+    /// then after each tick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use vitality::prelude::*;
+    /// attribute!(Health, f32);
+    /// attribute!(Haste, f32);
+    ///
+    /// // A haste of the source below 1.0 speeds up the ticks
+    /// let poison = EffectBuilder::every_second_for_ticks(2.0, 5)
+    ///     .modify::<Health>(3.0, ModOp::Sub, EffectSubject::Target)
+    ///     .with_tick_interval(Haste::lit(2.0) * Haste::src())
+    ///     .build();
+    /// ```
+    pub fn with_tick_interval(
+        mut self,
+        interval: impl Into<Expr<f32, EffectExprSchema>>,
+    ) -> Self {
+        self.def.tick_interval = Some(interval.into());
+        self
+    }
+
+    /// Runs a calculation writing several attributes when the effect is applied,
+    /// or every tick if the effect is periodic. See [`EffectExecution`].
+    pub fn execute(mut self, execution: impl EffectExecution) -> Self {
+        self.def.executions.push(Box::new(execution));
+        self
+    }
+
     /// Attach the effect to the target entity only if the condition is met.
     ///
     /// # Examples
@@ -122,6 +324,37 @@ impl EffectBuilder {
         self
     }
 
+    /// Grants a gameplay tag to the target while the effect is active.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use vitality::prelude::*;
+    /// let stun = EffectBuilder::for_seconds(2.0)
+    ///     .grant_tag("Status.Debuff.Stun")
+    ///     .build();
+    /// ```
+    pub fn grant_tag(mut self, tag: impl Into<GameplayTag>) -> Self {
+        self.def.granted_tags.push(tag.into());
+        self
+    }
+
+    /// Makes the target immune to matching effects while the effect is active.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use vitality::prelude::*;
+    /// # use vitality::tags::GameplayTag;
+    /// let unstoppable = EffectBuilder::for_seconds(5.0)
+    ///     .grant_immunity(GameplayTag::new_static("Status.Debuff.Stun"))
+    ///     .build();
+    /// ```
+    pub fn grant_immunity(mut self, rule: impl Into<ImmunityRule>) -> Self {
+        self.def.immunities.push(rule.into());
+        self
+    }
+
     pub fn add_effect_trigger<E: EntityEvent, B: Bundle, M>(
         mut self,
         observer: impl IntoObserverSystem<E, B, M> + Clone + Send + Sync + 'static,
@@ -190,6 +423,102 @@ impl EffectBuilder {
         self
     }
 
+    pub fn with_stacking_scope(mut self, scope: StackingScope) -> Self {
+        self.def.stacking_scope = scope;
+        self
+    }
+
+    pub fn with_stack_expiration(mut self, expiration: StackExpiration) -> Self {
+        self.def.stack_expiration = expiration;
+        self
+    }
+
+    /// Applies another effect, from the same source to the same target, whenever the effect
+    /// is applied again at its maximum stack count.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use vitality::prelude::*;
+    /// # use vitality::assets::EffectDef;
+    /// # use vitality::effect::{EffectStackingPolicy, StackExpiration};
+    /// # fn example(mut assets: ResMut<Assets<EffectDef>>) {
+    /// let detonation = assets.add(EffectBuilder::instant().build());
+    ///
+    /// // Each bleed stack expires on its own, and a sixth application detonates the bleed.
+    /// let bleed = EffectBuilder::every_second_for_duration(1.0, 6.0)
+    ///     .with_stacking_policy(EffectStackingPolicy::AddAndRefresh { count: 1, max_stack: 5 })
+    ///     .with_stack_expiration(StackExpiration::OneStack)
+    ///     .on_stack_overflow(&detonation)
+    ///     .build();
+    /// # }
+    /// ```
+    pub fn on_stack_overflow(mut self, effect: &Handle<EffectDef>) -> Self {
+        self.def
+            .chained_effects
+            .push((ChainTrigger::Overflow, effect.clone()));
+        self
+    }
+
+    /// Applies another effect, from the same source to the same target, when the duration
+    /// of the effect runs out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use vitality::prelude::*;
+    /// # use vitality::assets::EffectDef;
+    /// attribute!(Health, f32);
+    ///
+    /// # fn example(mut assets: ResMut<Assets<EffectDef>>) {
+    /// let explosion = assets.add(
+    ///     EffectBuilder::instant()
+    ///         .modify::<Health>(50.0, ModOp::Sub, EffectSubject::Target)
+    ///         .build(),
+    /// );
+    ///
+    /// // The bomb explodes on its target when it was not dispelled in time.
+    /// let bomb = EffectBuilder::for_seconds(5.0)
+    ///     .on_expire(&explosion)
+    ///     .build();
+    /// # }
+    /// ```
+    pub fn on_expire(mut self, effect: &Handle<EffectDef>) -> Self {
+        self.def
+            .chained_effects
+            .push((ChainTrigger::Expired, effect.clone()));
+        self
+    }
+
+    /// Applies another effect, from the same source to the same target, when the effect
+    /// is removed, whether dispelled, expired or despawned.
+    pub fn on_remove(mut self, effect: &Handle<EffectDef>) -> Self {
+        self.def
+            .chained_effects
+            .push((ChainTrigger::Removed, effect.clone()));
+        self
+    }
+
+    /// Applies another effect, from the same source to the same target, every time the
+    /// periodic effect ticks.
+    pub fn on_tick(mut self, effect: &Handle<EffectDef>) -> Self {
+        self.def
+            .chained_effects
+            .push((ChainTrigger::Ticked, effect.clone()));
+        self
+    }
+
+    /// Applies another effect, from the same source to the same target, when the effect
+    /// reaches its maximum stack count.
+    pub fn on_max_stacks(mut self, effect: &Handle<EffectDef>) -> Self {
+        self.def
+            .chained_effects
+            .push((ChainTrigger::MaxStacks, effect.clone()));
+        self
+    }
+
     pub fn build(self) -> EffectDef {
         self.def
     }
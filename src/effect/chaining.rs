@@ -0,0 +1,143 @@
+use crate::assets::EffectDef;
+use crate::context::EffectExprContext;
+use crate::effect::{
+    ApplyEffectEvent, Effect, EffectInactive, EffectParams, EffectSource, EffectTarget,
+    EffectTargeting, EffectTicker,
+};
+use crate::AttributesRef;
+use bevy::ecs::resource::IsResource;
+use bevy::prelude::*;
+
+/// When an effect applies the effects chained to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainTrigger {
+    /// The duration of the effect ran out.
+    Expired,
+    /// The effect was removed, whether dispelled, expired or despawned.
+    Removed,
+    /// The periodic effect ticked.
+    Ticked,
+    /// The effect reached its maximum stack count.
+    MaxStacks,
+    /// The effect was applied again at its maximum stack count.
+    Overflow,
+}
+
+/// Builds the applications of the effects chained to the trigger.
+/// They keep the source, the target and the parameters of the effect.
+pub(crate) fn chained_applications(
+    effect_def: &EffectDef,
+    trigger: ChainTrigger,
+    source: Entity,
+    target: Entity,
+    params: Option<&EffectParams>,
+) -> Vec<ApplyEffectEvent> {
+    effect_def
+        .chained_effects
+        .iter()
+        .filter(|(chain_trigger, _)| *chain_trigger == trigger)
+        .map(|(_, handle)| ApplyEffectEvent {
+            entity: target,
+            targeting: EffectTargeting::new(source, target),
+            handle: handle.clone(),
+            params: params.cloned().unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Builds the applications of the effects chained to the trigger of a spawned effect.
+/// Must be called before the effect is despawned.
+pub(crate) fn chained_applications_of(
+    world: &World,
+    effect_entity: Entity,
+    trigger: ChainTrigger,
+) -> Vec<ApplyEffectEvent> {
+    let Ok(effect_ref) = world.get_entity(effect_entity) else {
+        return vec![];
+    };
+    let (Some(effect), Some(target), Some(source)) = (
+        effect_ref.get::<Effect>(),
+        effect_ref.get::<EffectTarget>(),
+        effect_ref.get::<EffectSource>(),
+    ) else {
+        return vec![];
+    };
+    let Some(effect_def) = world.resource::<Assets<EffectDef>>().get(&effect.0) else {
+        return vec![];
+    };
+    chained_applications(
+        effect_def,
+        trigger,
+        source.0,
+        target.0,
+        effect_ref.get::<EffectParams>(),
+    )
+}
+
+/// Applies the effects chained to the ticks of periodic effects.
+pub(crate) fn apply_chained_tick_effects(
+    actors: Query<AttributesRef, Without<IsResource>>,
+    effects: Query<
+        (
+            AttributesRef,
+            &Effect,
+            &EffectTicker,
+            &EffectTarget,
+            &EffectSource,
+        ),
+        (Without<EffectInactive>, Without<IsResource>),
+    >,
+    effect_assets: Res<Assets<EffectDef>>,
+    type_registry: Res<AppTypeRegistry>,
+    mut commands: Commands,
+) {
+    for (effect_ref, effect, ticker, target, source) in effects.iter() {
+        let ticks = ticker.ticks_this_frame();
+        if ticks == 0 {
+            continue;
+        }
+        let Some(effect_def) = effect_assets.get(&effect.0) else {
+            continue;
+        };
+        if !effect_def
+            .chained_effects
+            .iter()
+            .any(|(trigger, _)| *trigger == ChainTrigger::Ticked)
+        {
+            continue;
+        }
+        let Ok([source_actor_ref, target_actor_ref]) = actors.get_many([source.0, target.0]) else {
+            continue;
+        };
+
+        let context = EffectExprContext {
+            target_actor: &target_actor_ref,
+            source_actor: &source_actor_ref,
+            effect_holder: &effect_ref,
+            params: None,
+            type_registry: type_registry.0.clone(),
+        };
+
+        // Ticks skipped by the activation conditions do not chain either
+        if !effect_def.should_activate(&context) {
+            continue;
+        }
+
+        for _ in 0..ticks {
+            for application in chained_applications(
+                effect_def,
+                ChainTrigger::Ticked,
+                source.0,
+                target.0,
+                effect_ref.get::<EffectParams>(),
+            ) {
+                debug!(
+                    "Effect {} ticked, applying {:?}.",
+                    effect_ref.id(),
+                    application.handle
+                );
+                commands.trigger(application);
+            }
+        }
+    }
+}
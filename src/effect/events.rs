@@ -0,0 +1,137 @@
+use crate::assets::EffectDef;
+use crate::tags::GameplayTag;
+use bevy::prelude::*;
+
+/// Why an effect was removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectRemovalReason {
+    /// Removed by [`Vitality::remove_effects`](crate::context::Vitality::remove_effects).
+    Dispelled,
+    /// The duration of the effect ran out.
+    Expired,
+    /// The effect entity was despawned directly, or with its target.
+    Despawned,
+}
+
+/// Why the stack count of an effect changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackChangeReason {
+    /// The effect was applied again to the same target.
+    Applied,
+    /// Stacks were removed by [`Vitality::remove_effects`](crate::context::Vitality::remove_effects).
+    Dispelled,
+    /// A stack expired, see [`StackExpiration::OneStack`](crate::effect::StackExpiration::OneStack).
+    Expired,
+}
+
+// Declares a lifecycle event triggered on an effect, then on its target.
+macro_rules! effect_lifecycle_event {
+    (
+        $(#[$meta:meta])*
+        $Name:ident { $($(#[$field_meta:meta])* $field:ident: $Type:ty),* $(,)? }
+    ) => {
+        $(#[$meta])*
+        #[derive(EntityEvent, Debug, Clone)]
+        pub struct $Name {
+            /// The effect or its target.
+            pub entity: Entity,
+            pub effect: Entity,
+            pub target: Entity,
+            pub source: Entity,
+            pub handle: Handle<EffectDef>,
+            $($(#[$field_meta])* pub $field: $Type,)*
+        }
+
+        impl $Name {
+            pub(crate) fn on(&self, entity: Entity) -> Self {
+                Self {
+                    entity,
+                    ..self.clone()
+                }
+            }
+        }
+    };
+}
+
+/// Triggered on the target of an effect after it is applied, and on the effect entity if
+/// the effect is persistent.
+#[derive(EntityEvent, Debug, Clone)]
+pub struct EffectApplied {
+    /// The effect or its target.
+    pub entity: Entity,
+    /// The effect entity. Instant effects have none.
+    pub effect: Option<Entity>,
+    pub target: Entity,
+    pub source: Entity,
+    pub handle: Handle<EffectDef>,
+}
+
+impl EffectApplied {
+    pub(crate) fn on(&self, entity: Entity) -> Self {
+        Self {
+            entity,
+            ..self.clone()
+        }
+    }
+}
+
+effect_lifecycle_event! {
+    /// Triggered on an effect, then on its target, when its stack count changes.
+    EffectStacksChanged {
+        old: u32,
+        new: u32,
+        reason: StackChangeReason,
+    }
+}
+
+effect_lifecycle_event! {
+    /// Triggered on an effect, then on its target, when a new application refreshes its duration.
+    EffectRefreshed {}
+}
+
+effect_lifecycle_event! {
+    /// Triggered on an effect, then on its target, when its conditions are met again.
+    EffectActivated {}
+}
+
+effect_lifecycle_event! {
+    /// Triggered on an effect, then on its target, when its conditions are no longer met.
+    EffectDeactivated {}
+}
+
+effect_lifecycle_event! {
+    /// Triggered on an effect, then on its target, when its duration runs out.
+    /// Followed by [`EffectRemoved`].
+    EffectExpired {}
+}
+
+effect_lifecycle_event! {
+    /// Triggered on an effect, then on its target, once the effect is despawned,
+    /// whether it was dispelled, expired or despawned directly.
+    EffectRemoved {
+        reason: EffectRemovalReason,
+    }
+}
+
+/// Why the application of an effect was blocked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockReason {
+    /// The target is immune to effects granting this tag.
+    ImmuneToTag(GameplayTag),
+    /// The target is immune to this effect.
+    ImmuneToEffect,
+    /// A blocking condition of the target holds.
+    Condition,
+}
+
+/// Triggered on the target when an immunity rule blocks the application of an effect.
+#[derive(EntityEvent, Debug, Clone)]
+pub struct EffectApplicationBlocked {
+    /// The target.
+    pub entity: Entity,
+    pub source: Entity,
+    pub handle: Handle<EffectDef>,
+    pub reason: BlockReason,
+    /// The active effect granting the immunity, or none if the rule belongs to the target.
+    pub blocked_by: Option<Entity>,
+}
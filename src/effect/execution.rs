@@ -0,0 +1,268 @@
+use crate::assets::EffectDef;
+use crate::attributes::Attribute;
+use crate::context::{BaseValueNotifier, EffectExprContext};
+use crate::effect::{Effect, EffectParams, EffectSource, EffectTarget, EffectTicker};
+use crate::inspector::pretty_type_name;
+use crate::math::{AbsDiff, SaturatingAttributes};
+use crate::modifier::EffectSubject;
+use crate::systems::MarkNodeDirty;
+use crate::{AttributesMut, AttributesRef, BaseValueChanged};
+use bevy::ecs::resource::IsResource;
+use bevy::prelude::*;
+
+/// A calculation run when an instant effect is applied or a periodic effect ticks.
+///
+/// An execution reads any attribute of the source, the target and the effect, then writes several
+/// base values at once. The writes are applied together in
+/// [`EffectsSet::UpdateBaseValues`](crate::schedule::EffectsSet::UpdateBaseValues), and only if
+/// every written attribute exists.
+///
+/// # Examples
+///
+/// ```
+/// # use vitality::prelude::*;
+/// # use vitality::context::EffectExprContext;
+/// # use vitality::effect::ExecutionOutput;
+/// attribute!(Health, f32);
+/// attribute!(Attack, f32);
+/// attribute!(Armour, f32);
+/// attribute!(Lifesteal, f32);
+///
+/// let strike = EffectBuilder::instant()
+///     .execute(|ctx: &EffectExprContext, output: &mut ExecutionOutput| {
+///         let attack = ctx.current_value::<Attack>(EffectSubject::Source).unwrap_or(0.0);
+///         let armour = ctx.current_value::<Armour>(EffectSubject::Target).unwrap_or(0.0);
+///         let lifesteal = ctx.current_value::<Lifesteal>(EffectSubject::Source).unwrap_or(0.0);
+///
+///         let crit = if rand::random::<f32>() < 0.1 { 2.0 } else { 1.0 };
+///         let damage = (attack * crit - armour).max(0.0);
+///         output.sub::<Health>(EffectSubject::Target, damage);
+///         output.add::<Health>(EffectSubject::Source, damage * lifesteal);
+///     })
+///     .build();
+/// ```
+pub trait EffectExecution: Send + Sync + 'static {
+    fn execute(&self, ctx: &EffectExprContext, output: &mut ExecutionOutput);
+}
+
+impl<F> EffectExecution for F
+where
+    F: Fn(&EffectExprContext, &mut ExecutionOutput) + Send + Sync + 'static,
+{
+    fn execute(&self, ctx: &EffectExprContext, output: &mut ExecutionOutput) {
+        self(ctx, output)
+    }
+}
+
+// Writes the base value of an attribute, returning the notification of the change
+type WriteFn = dyn FnOnce(&mut AttributesMut, Entity, Option<Entity>) -> Option<BaseValueNotifier>
+    + Send
+    + Sync;
+
+struct AttributeWrite {
+    who: EffectSubject,
+    attribute: &'static str,
+    contains: fn(&AttributesRef) -> bool,
+    write: Box<WriteFn>,
+}
+
+/// The base value writes produced by an [`EffectExecution`].
+#[derive(Default)]
+pub struct ExecutionOutput {
+    writes: Vec<AttributeWrite>,
+}
+
+impl ExecutionOutput {
+    /// Replaces the base value of the attribute.
+    pub fn set<T: Attribute>(&mut self, who: EffectSubject, value: T::Property) {
+        self.write::<T>(who, move |_| value);
+    }
+
+    pub fn add<T: Attribute>(&mut self, who: EffectSubject, value: T::Property) {
+        self.write::<T>(who, move |base| base.saturating_add(value));
+    }
+
+    pub fn sub<T: Attribute>(&mut self, who: EffectSubject, value: T::Property) {
+        self.write::<T>(who, move |base| base.saturating_sub(value));
+    }
+
+    /// Computes the new base value of the attribute from its base value when the write is applied.
+    pub fn write<T: Attribute>(
+        &mut self,
+        who: EffectSubject,
+        update: impl FnOnce(T::Property) -> T::Property + Send + Sync + 'static,
+    ) {
+        self.writes.push(AttributeWrite {
+            who,
+            attribute: pretty_type_name::<T>(),
+            contains: |actor| actor.get::<T>().is_some(),
+            write: Box::new(move |actor, source, effect| {
+                let entity = actor.id();
+                let mut attribute = actor.get_mut::<T>()?;
+                let old = attribute.base_value();
+                let new = update(old);
+                if !old.are_different(new) {
+                    return None;
+                }
+                attribute.set_base_value(new);
+
+                Some(Box::new(move |commands: &mut Commands| {
+                    commands.trigger(MarkNodeDirty::<T> {
+                        entity,
+                        phantom_data: Default::default(),
+                    });
+                    commands.trigger(BaseValueChanged::<T> {
+                        phantom_data: Default::default(),
+                        old,
+                        new,
+                        entity,
+                        source,
+                        effect,
+                    });
+                }))
+            }),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+}
+
+/// Runs the executions of an effect in [`EffectsSet::UpdateBaseValues`](crate::schedule::EffectsSet::UpdateBaseValues).
+#[derive(Message)]
+pub struct ExecuteEffectMessage {
+    pub source_entity: Entity,
+    pub target_entity: Entity,
+    /// The effect entity. Instant effects have none, and the source holds them instead.
+    pub effect_entity: Option<Entity>,
+    pub handle: Handle<EffectDef>,
+    /// The parameters of an application without an effect entity.
+    pub params: Option<EffectParams>,
+}
+
+pub(crate) fn apply_effect_executions(
+    mut messages: MessageReader<ExecuteEffectMessage>,
+    mut attributes: Query<AttributesMut, Without<IsResource>>,
+    effect_assets: Res<Assets<EffectDef>>,
+    type_registry: Res<AppTypeRegistry>,
+    mut commands: Commands,
+) {
+    for message in messages.read() {
+        let Some(effect_def) = effect_assets.get(&message.handle) else {
+            error!("Failed to find effect definition for handle: {:?}", message.handle);
+            continue;
+        };
+        let holder = message.effect_entity.unwrap_or(message.source_entity);
+        let entities = [message.source_entity, message.target_entity, holder];
+
+        let writes = {
+            let Ok([source, target, effect]) = attributes.get_many(entities) else {
+                warn!("Failed to get the actors of execution {:?}", message.handle);
+                continue;
+            };
+            let context = EffectExprContext {
+                source_actor: &source,
+                target_actor: &target,
+                effect_holder: &effect,
+                params: message.params.as_ref(),
+                type_registry: type_registry.0.clone(),
+            };
+
+            let mut output = ExecutionOutput::default();
+            for execution in &effect_def.executions {
+                execution.execute(&context, &mut output);
+            }
+
+            // The writes are applied together or not at all
+            let missing = output
+                .writes
+                .iter()
+                .find(|write| !(write.contains)(context.attribute_ref(write.who)));
+            if let Some(missing) = missing {
+                error!(
+                    "Execution of {:?} writes attribute {} missing on {}.",
+                    message.handle, missing.attribute, missing.who
+                );
+                continue;
+            }
+            output.writes
+        };
+
+        let mut notifiers = vec![];
+        for write in writes {
+            let entity = match write.who {
+                EffectSubject::Source => message.source_entity,
+                EffectSubject::Target => message.target_entity,
+                EffectSubject::Effect => holder,
+            };
+            let Ok(mut actor) = attributes.get_mut(entity) else {
+                continue;
+            };
+            notifiers.extend((write.write)(
+                &mut actor,
+                message.source_entity,
+                message.effect_entity,
+            ));
+        }
+        for notify in notifiers {
+            notify(&mut commands);
+        }
+    }
+}
+
+/// Runs the executions of periodic effects when they tick.
+pub(crate) fn execute_periodic_effects(
+    actors: Query<AttributesRef, Without<IsResource>>,
+    effects: Query<
+        (
+            AttributesRef,
+            &Effect,
+            &EffectTicker,
+            &EffectTarget,
+            &EffectSource,
+        ),
+        Without<IsResource>,
+    >,
+    effect_assets: Res<Assets<EffectDef>>,
+    type_registry: Res<AppTypeRegistry>,
+    mut writer: MessageWriter<ExecuteEffectMessage>,
+) {
+    for (effect_ref, effect, ticker, target, source) in effects.iter() {
+        let ticks = ticker.ticks_this_frame();
+        if ticks == 0 {
+            continue;
+        }
+        let Some(effect_def) = effect_assets.get(&effect.0) else {
+            continue;
+        };
+        if effect_def.executions.is_empty() {
+            continue;
+        }
+        let Ok([source_actor_ref, target_actor_ref]) = actors.get_many([source.0, target.0]) else {
+            continue;
+        };
+
+        let context = EffectExprContext {
+            target_actor: &target_actor_ref,
+            source_actor: &source_actor_ref,
+            effect_holder: &effect_ref,
+            params: None,
+            type_registry: type_registry.0.clone(),
+        };
+
+        if !effect_def.should_activate(&context) {
+            continue;
+        }
+
+        for _ in 0..ticks {
+            writer.write(ExecuteEffectMessage {
+                source_entity: source.0,
+                target_entity: target.0,
+                effect_entity: Some(effect_ref.id()),
+                handle: effect.0.clone(),
+                params: None,
+            });
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::assets::EffectDef;
+use crate::context::{EffectExprContext, EffectExprSchema};
+use crate::effect::events::BlockReason;
+use crate::tags::GameplayTag;
+use bevy::prelude::*;
+use express_it::logic::BoolExpr;
+
+/// A rule blocking the application of effects to an actor.
+///
+/// Rules are declared on the actor with [`EffectImmunities`], or granted by the active effects
+/// applied to it with [`EffectBuilder::grant_immunity`](crate::effect::EffectBuilder::grant_immunity).
+#[derive(Clone)]
+pub enum ImmunityRule {
+    /// Blocks effects granting the tag or one of its descendants.
+    Tag(GameplayTag),
+    /// Blocks effects with this definition.
+    Handle(AssetId<EffectDef>),
+    /// Blocks effects while the condition holds.
+    /// The condition is evaluated with the source and target of the incoming effect,
+    /// and is skipped if the source has no attributes to read.
+    BlockIf(BoolExpr<EffectExprSchema>),
+}
+
+impl ImmunityRule {
+    /// The reason the rule blocks the incoming effect, if it does.
+    /// Conditions never block without a context to evaluate them.
+    pub fn blocks(
+        &self,
+        handle: &Handle<EffectDef>,
+        effect: &EffectDef,
+        context: Option<&EffectExprContext>,
+    ) -> Option<BlockReason> {
+        match self {
+            ImmunityRule::Tag(tag) => effect
+                .granted_tags
+                .iter()
+                .any(|granted| granted.matches(tag))
+                .then(|| BlockReason::ImmuneToTag(tag.clone())),
+            ImmunityRule::Handle(id) => (handle.id() == *id).then_some(BlockReason::ImmuneToEffect),
+            ImmunityRule::BlockIf(condition) => condition
+                .eval(context?)
+                .unwrap_or_else(|err| {
+                    error!("Failed to evaluate an immunity condition: {}", err);
+                    false
+                })
+                .then_some(BlockReason::Condition),
+        }
+    }
+}
+
+impl From<GameplayTag> for ImmunityRule {
+    fn from(tag: GameplayTag) -> Self {
+        ImmunityRule::Tag(tag)
+    }
+}
+
+impl From<&Handle<EffectDef>> for ImmunityRule {
+    fn from(handle: &Handle<EffectDef>) -> Self {
+        ImmunityRule::Handle(handle.id())
+    }
+}
+
+/// The immunity rules of an actor.
+#[derive(Component, Clone, Default)]
+pub struct EffectImmunities(pub Vec<ImmunityRule>);
@@ -0,0 +1,456 @@
+use crate::assets::EffectDef;
+use crate::context::EffectExprSchema;
+use crate::effect::{
+    EffectApplicationPolicy, EffectBuilder, EffectStackingPolicy, StackExpiration, StackScaling,
+    StackingScope,
+};
+use crate::modifier::{EffectSubject, ModOp};
+use crate::registry::effect_registry::{EffectRegistry, EffectToken};
+use crate::tags::GameplayTag;
+use crate::{AppAttributeBindings, AttributeBindings};
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use express_it::logic::BoolExpr;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use std::error::Error;
+use std::fmt::Formatter;
+use std::time::Duration;
+
+/// A serializable description of an [`EffectDef`], authored in `.effect.ron` files.
+///
+/// Attributes are referred to by their type name (e.g. `"Health"`) and must be registered
+/// with [`init_attribute`](crate::init_attribute). Tags must be registered with `register_type`.
+///
+/// ```ron
+/// (
+///     name: Some("Poison"),
+///     application: PeriodicTemporary(interval: 1.0, duration: 10.0),
+///     stacking: Add(count: 1, max_stack: 5),
+///     stacking_scope: PerSource,
+///     modifiers: [
+///         (attribute: "Health", op: Sub, value: 5.0, who: Target, scaling: Linear),
+///     ],
+///     tags: ["Poisoned"],
+///     granted_tags: ["Status.Debuff.Poison"],
+/// )
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectDescription {
+    /// The token under which the effect is registered. Defaults to the file name.
+    #[serde(default)]
+    pub token: Option<SmolStr>,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub application: ApplicationDescription,
+    /// Whether a periodic effect ticks as soon as it is applied.
+    #[serde(default)]
+    pub tick_on_apply: bool,
+    /// The number of ticks after which a periodic effect expires.
+    #[serde(default)]
+    pub max_ticks: Option<u32>,
+    #[serde(default)]
+    pub stacking: StackingDescription,
+    #[serde(default)]
+    pub stacking_scope: StackingScope,
+    #[serde(default)]
+    pub stack_expiration: StackExpiration,
+    #[serde(default)]
+    pub modifiers: Vec<ModifierDescription>,
+    #[serde(default)]
+    pub attach_conditions: Vec<ConditionDescription>,
+    #[serde(default)]
+    pub active_conditions: Vec<ConditionDescription>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Gameplay tags granted to the target while the effect is active.
+    #[serde(default)]
+    pub granted_tags: Vec<GameplayTag>,
+}
+
+/// Mirrors [`EffectApplicationPolicy`] with durations in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ApplicationDescription {
+    Instant,
+    Permanent,
+    Temporary { duration: f32 },
+    Periodic { interval: f32 },
+    PeriodicTemporary { interval: f32, duration: f32 },
+}
+
+/// Mirrors [`EffectStackingPolicy`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub enum StackingDescription {
+    #[default]
+    None,
+    Add { count: u32, max_stack: u32 },
+    AddAndRefresh { count: u32, max_stack: u32 },
+    RefreshDuration,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModifierDescription {
+    pub attribute: String,
+    pub op: ModOp,
+    pub value: f64,
+    pub who: EffectSubject,
+    #[serde(default)]
+    pub scaling: ScalingDescription,
+    /// The channel of the attribute's aggregation pipeline. Defaults to the channel of `op`.
+    #[serde(default)]
+    pub channel: Option<SmolStr>,
+}
+
+/// Mirrors the data-friendly variants of [`StackScaling`].
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub enum ScalingDescription {
+    #[default]
+    None,
+    Linear,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ConditionDescription {
+    /// The base value of the attribute is within the inclusive bounds.
+    AttributeWithinBounds {
+        attribute: String,
+        who: EffectSubject,
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+    },
+}
+
+impl ApplicationDescription {
+    /// Checks that durations are finite and not negative, and that intervals are positive.
+    fn validate(&self) -> Result<(), EffectLoaderError> {
+        let (interval, duration) = match *self {
+            ApplicationDescription::Instant | ApplicationDescription::Permanent => (None, None),
+            ApplicationDescription::Temporary { duration } => (None, Some(duration)),
+            ApplicationDescription::Periodic { interval } => (Some(interval), None),
+            ApplicationDescription::PeriodicTemporary { interval, duration } => {
+                (Some(interval), Some(duration))
+            }
+        };
+        let to_duration = |seconds: &f32| Duration::try_from_secs_f32(*seconds).ok();
+        if let Some(interval) = interval.filter(|s| to_duration(s).is_none_or(|d| d.is_zero())) {
+            let interval = format!("interval {}", interval);
+            return Err(EffectLoaderError::InvalidValue(interval));
+        }
+        if let Some(duration) = duration.filter(|s| to_duration(s).is_none()) {
+            let duration = format!("duration {}", duration);
+            return Err(EffectLoaderError::InvalidValue(duration));
+        }
+        Ok(())
+    }
+
+    pub fn to_policy(&self) -> EffectApplicationPolicy {
+        match *self {
+            ApplicationDescription::Instant => EffectApplicationPolicy::instant(),
+            ApplicationDescription::Permanent => EffectApplicationPolicy::permanent(),
+            ApplicationDescription::Temporary { duration } => {
+                EffectApplicationPolicy::for_seconds(duration)
+            }
+            ApplicationDescription::Periodic { interval } => {
+                EffectApplicationPolicy::every_seconds(interval)
+            }
+            ApplicationDescription::PeriodicTemporary { interval, duration } => {
+                EffectApplicationPolicy::every_seconds_for_duration(interval, duration)
+            }
+        }
+    }
+}
+
+impl StackingDescription {
+    /// Checks that stacking effects can hold at least one stack.
+    fn validate(&self) -> Result<(), EffectLoaderError> {
+        match *self {
+            StackingDescription::Add { max_stack: 0, .. }
+            | StackingDescription::AddAndRefresh { max_stack: 0, .. } => {
+                Err(EffectLoaderError::InvalidValue("max_stack 0".into()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn to_policy(&self) -> EffectStackingPolicy {
+        match *self {
+            StackingDescription::None => EffectStackingPolicy::None,
+            StackingDescription::Add { count, max_stack } => {
+                EffectStackingPolicy::Add { count, max_stack }
+            }
+            StackingDescription::AddAndRefresh { count, max_stack } => {
+                EffectStackingPolicy::AddAndRefresh { count, max_stack }
+            }
+            StackingDescription::RefreshDuration => EffectStackingPolicy::RefreshDuration,
+        }
+    }
+}
+
+impl ConditionDescription {
+    fn build(
+        &self,
+        bindings: &AttributeBindings,
+    ) -> Result<BoolExpr<EffectExprSchema>, EffectLoaderError> {
+        match self {
+            ConditionDescription::AttributeWithinBounds {
+                attribute,
+                who,
+                min,
+                max,
+            } => {
+                let build = bindings
+                    .bounds_condition
+                    .get(attribute.as_str())
+                    .ok_or_else(|| EffectLoaderError::UnknownAttribute(attribute.clone()))?;
+                build(*min, *max, *who)
+                    .ok_or_else(|| EffectLoaderError::InvalidValue(attribute.clone()))
+            }
+        }
+    }
+}
+
+impl EffectDescription {
+    /// Builds the effect, resolving attribute names through the bindings.
+    /// Negative or infinite durations, intervals that are not positive
+    /// and a `max_stack` of 0 are rejected as [`EffectLoaderError::InvalidValue`].
+    pub fn build(&self, bindings: &AttributeBindings) -> Result<EffectDef, EffectLoaderError> {
+        self.application.validate()?;
+        self.stacking.validate()?;
+        let mut builder = EffectBuilder::new(self.application.to_policy())
+            .with_stacking_policy(self.stacking.to_policy())
+            .with_stacking_scope(self.stacking_scope)
+            .with_stack_expiration(self.stack_expiration);
+
+        if self.tick_on_apply {
+            builder = builder.tick_on_apply();
+        }
+        if let Some(max_ticks) = self.max_ticks {
+            builder = builder.with_max_ticks(max_ticks);
+        }
+        if let Some(name) = &self.name {
+            builder = builder.name(name.clone());
+        }
+        for condition in &self.attach_conditions {
+            builder = builder.attach_if(condition.build(bindings)?);
+        }
+        for condition in &self.active_conditions {
+            builder = builder.active_while(condition.build(bindings)?);
+        }
+
+        for tag in &self.granted_tags {
+            builder = builder.grant_tag(tag.clone());
+        }
+
+        let mut def = builder.build();
+        def.token = self.token.clone().map(EffectToken::new);
+
+        for modifier in &self.modifiers {
+            let build = bindings
+                .literal_modifier
+                .get(modifier.attribute.as_str())
+                .ok_or_else(|| EffectLoaderError::UnknownAttribute(modifier.attribute.clone()))?;
+            let scaling = match modifier.scaling {
+                ScalingDescription::None => StackScaling::None,
+                ScalingDescription::Linear => StackScaling::Linear,
+            };
+            let channel = modifier.channel.clone();
+            let modifier = build(modifier.value, modifier.op, modifier.who, scaling, channel)
+                .ok_or_else(|| EffectLoaderError::InvalidValue(modifier.attribute.clone()))?;
+            def.modifiers.push(modifier);
+        }
+
+        for tag in &self.tags {
+            let tag = tag.clone();
+            def.effect_fn.push(Box::new(
+                move |effect_entity: &mut EntityCommands, _: Entity| {
+                    let tag = tag.clone();
+                    effect_entity.queue(move |entity: EntityWorldMut| insert_reflected_tag(entity, &tag));
+                },
+            ));
+        }
+
+        Ok(def)
+    }
+}
+
+// Tags are unit components, so they are inserted from their reflected default value
+fn insert_reflected_tag(mut entity: EntityWorldMut, tag: &str) {
+    let type_registry = entity.world().resource::<AppTypeRegistry>().clone();
+    let type_registry = type_registry.read();
+
+    let Some(registration) = type_registry.get_with_short_type_path(tag) else {
+        error!("Tag {} is not registered.", tag);
+        return;
+    };
+    let (Some(reflect_component), Some(reflect_default)) = (
+        registration.data::<ReflectComponent>(),
+        registration.data::<ReflectDefault>(),
+    ) else {
+        error!("Tag {} does not reflect Component and Default.", tag);
+        return;
+    };
+
+    let component = reflect_default.default();
+    reflect_component.insert(&mut entity, component.as_partial_reflect(), &type_registry);
+}
+
+#[derive(Debug)]
+pub enum EffectLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    UnknownAttribute(String),
+    /// A value of the attribute or setting cannot be used.
+    InvalidValue(String),
+}
+
+impl std::fmt::Display for EffectLoaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EffectLoaderError::Io(err) => write!(f, "Could not read effect: {}", err),
+            EffectLoaderError::Ron(err) => write!(f, "Could not parse effect: {}", err),
+            EffectLoaderError::UnknownAttribute(attribute) => {
+                write!(f, "Attribute {} is not registered.", attribute)
+            }
+            EffectLoaderError::InvalidValue(value) => write!(f, "Invalid value for {}.", value),
+        }
+    }
+}
+
+impl Error for EffectLoaderError {}
+
+impl From<std::io::Error> for EffectLoaderError {
+    fn from(err: std::io::Error) -> Self {
+        EffectLoaderError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for EffectLoaderError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        EffectLoaderError::Ron(err)
+    }
+}
+
+/// Loads [`EffectDef`] from `.effect.ron` files.
+#[derive(TypePath)]
+pub struct EffectDefLoader {
+    bindings: AppAttributeBindings,
+}
+
+impl FromWorld for EffectDefLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            bindings: world.resource::<AppAttributeBindings>().clone(),
+        }
+    }
+}
+
+impl AssetLoader for EffectDefLoader {
+    type Asset = EffectDef;
+    type Settings = ();
+    type Error = EffectLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &Self::Settings,
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut description = ron::de::from_bytes::<EffectDescription>(&bytes)?;
+
+        if description.token.is_none() {
+            let file_name = load_context
+                .path()
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.trim_end_matches(".effect.ron"));
+            description.token = file_name.map(SmolStr::new);
+        }
+
+        let bindings = self.bindings.internal.read().unwrap();
+        description.build(&bindings)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["effect.ron"]
+    }
+}
+
+/// Registers loaded effects in the [`EffectRegistry`] under their token.
+pub(crate) fn register_loaded_effects(
+    mut events: MessageReader<AssetEvent<EffectDef>>,
+    mut effect_assets: ResMut<Assets<EffectDef>>,
+    mut registry: ResMut<EffectRegistry>,
+) {
+    for event in events.read() {
+        let AssetEvent::LoadedWithDependencies { id } = event else {
+            continue;
+        };
+        let Some(token) = effect_assets.get(*id).and_then(|def| def.token.clone()) else {
+            continue;
+        };
+        let Some(handle) = effect_assets.get_strong_handle(*id) else {
+            continue;
+        };
+
+        debug!("Registering loaded effect {}.", token);
+        registry.add(token, handle);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    attribute!(Health, f32);
+
+    #[test]
+    fn test_effect_description_resolves_attributes() {
+        let mut bindings = AttributeBindings::default();
+        bindings.add::<Health>();
+
+        let description: EffectDescription = ron::de::from_str(
+            r#"(
+                token: Some("poison"),
+                application: PeriodicTemporary(interval: 1.0, duration: 10.0),
+                stacking: Add(count: 1, max_stack: 5),
+                modifiers: [(attribute: "Health", op: Sub, value: 5.0, who: Target, scaling: Linear)],
+                active_conditions: [AttributeWithinBounds(attribute: "Health", who: Target, min: Some(1.0))],
+            )"#,
+        )
+        .unwrap();
+
+        let def = description.build(&bindings).unwrap();
+        assert_eq!(def.token, Some(EffectToken::new_static("poison")));
+        assert_eq!(def.modifiers.len(), 1);
+        assert_eq!(def.activate_conditions.len(), 1);
+        assert!(def.application_policy.is_periodic());
+
+        let unknown: EffectDescription = ron::de::from_str(
+            r#"(application: Instant, modifiers: [(attribute: "Mana", op: Add, value: 1.0, who: Target)])"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            unknown.build(&bindings),
+            Err(EffectLoaderError::UnknownAttribute(_))
+        ));
+
+        for malformed in [
+            "(application: Temporary(duration: -1.0))",
+            "(application: Periodic(interval: 0.0))",
+            "(application: PeriodicTemporary(interval: NaN, duration: 10.0))",
+            "(application: Permanent, stacking: Add(count: 1, max_stack: 0))",
+        ] {
+            let description: EffectDescription = ron::de::from_str(malformed).unwrap();
+            assert!(matches!(
+                description.build(&bindings),
+                Err(EffectLoaderError::InvalidValue(_))
+            ));
+        }
+    }
+}
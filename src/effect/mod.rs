@@ -1,27 +1,60 @@
 mod application;
 mod builder;
+mod chaining;
+mod events;
+mod execution;
 pub mod global_effect;
+mod immunity;
+mod loader;
+mod params;
+mod removal;
 mod stacks;
 mod targeting;
 mod timing;
 
 use crate::assets::EffectDef;
 use crate::effect::application::apply_effect_event_observer;
+use crate::effect::chaining::apply_chained_tick_effects;
+use crate::effect::execution::{apply_effect_executions, execute_periodic_effects};
+use crate::effect::loader::register_loaded_effects;
+use crate::effect::removal::on_remove_effect;
 use crate::effect::stacks::{NotifyAddStackEvent, read_add_stack_event};
-use crate::effect::timing::{tick_effect_durations, tick_effect_tickers};
+use crate::effect::timing::tick_effect_durations;
 use crate::prelude::Attribute;
 use crate::schedule::EffectsSet;
 use bevy::app::{App, Plugin};
 use bevy::asset::Handle;
 use bevy::ecs::query::QueryData;
-use bevy::prelude::{Component, Deref, Entity, Event, IntoScheduleConfigs, Reflect, Update};
+use bevy::prelude::{Component, Deref, Entity, IntoScheduleConfigs, Reflect, Update};
 use std::marker::PhantomData;
 
 pub use crate::effect::builder::EffectBuilder;
 pub use application::{ApplyEffectEvent, EffectApplicationPolicy};
+pub use chaining::ChainTrigger;
+pub use events::{
+    BlockReason, EffectActivated, EffectApplicationBlocked, EffectApplied, EffectDeactivated,
+    EffectExpired, EffectRefreshed, EffectRemovalReason, EffectRemoved, EffectStacksChanged,
+    StackChangeReason,
+};
+pub use execution::{EffectExecution, ExecuteEffectMessage, ExecutionOutput};
+pub use immunity::{EffectImmunities, ImmunityRule};
+pub(crate) use params::{PARAMS_SEGMENT, is_param_path};
+pub use params::{EffectParam, EffectParams};
 pub use global_effect::GlobalEffects;
-pub use stacks::{EffectStackingPolicy, Stacks};
+pub use loader::{
+    ApplicationDescription, ConditionDescription, EffectDefLoader, EffectDescription,
+    EffectLoaderError, ModifierDescription, ScalingDescription, StackingDescription,
+};
+pub use removal::{
+    EffectFilter, RemovalMode, RemoveEffects, RemoveEffectsCommand, remove_effects,
+};
+pub(crate) use stacks::on_change_stacks_mark_scaled_modifiers_dirty;
+pub use stacks::{
+    EffectIntensity, EffectStackingPolicy, StackExpiration, StackScaling, StackingOutcome,
+    StackingScope, Stacks,
+};
 pub use targeting::EffectTargeting;
+pub(crate) use timing::tick_effect_tickers;
 pub use timing::{EffectDuration, EffectTicker};
 
 pub struct EffectsPlugin;
@@ -31,25 +64,31 @@ impl Plugin for EffectsPlugin {
         app.add_systems(Update, tick_effect_tickers.in_set(EffectsSet::Prepare))
             .add_systems(Update, tick_effect_durations.in_set(EffectsSet::Prepare))
             .add_systems(Update, read_add_stack_event.in_set(EffectsSet::Prepare))
+            .add_systems(
+                Update,
+                (execute_periodic_effects, apply_chained_tick_effects)
+                    .after(tick_effect_tickers)
+                    .in_set(EffectsSet::Prepare),
+            )
+            .add_systems(
+                Update,
+                apply_effect_executions.in_set(EffectsSet::UpdateBaseValues),
+            )
+            .add_systems(Update, register_loaded_effects.in_set(EffectsSet::First))
             .add_observer(apply_effect_event_observer)
-            .add_message::<NotifyAddStackEvent>();
+            .add_observer(on_remove_effect)
+            .register_type::<EffectParams>()
+            .add_message::<NotifyAddStackEvent>()
+            .add_message::<ExecuteEffectMessage>();
     }
 }
 
-pub enum EffectStatus {
-    Active,
-    Inactive,
-}
-
 #[derive(Clone, Copy, Debug, Reflect)]
 pub enum Target {
     SelfEntity,
     TargetEntity,
 }
 
-#[derive(Event)]
-pub struct OnEffectStatusChangeEvent(pub EffectStatus);
-
 #[derive(Component, Debug, Default)]
 #[component(storage = "SparseSet")]
 pub struct EffectInactive;
@@ -0,0 +1,145 @@
+use crate::attributes::Value;
+use crate::context::{EffectExprContext, EffectExprSchema};
+use crate::modifier::EffectSubject;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use express_it::context::{Path, ReadContext};
+use express_it::expr::{Expr, ExprNode, ExpressionError};
+use smol_str::SmolStr;
+use std::collections::HashSet;
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// The path segment under which expressions read the parameters, as in `effect.params.damage`.
+pub(crate) const PARAMS_SEGMENT: &str = "params";
+
+/// Named magnitudes supplied by the caller when applying an effect.
+///
+/// Persistent effects keep their parameters on the effect entity, so their expressions read the
+/// same values whenever they are re-evaluated. When an effect stacks, the parameters of its first
+/// application are kept.
+///
+/// # Examples
+///
+/// ```
+/// # use vitality::prelude::*;
+/// # use vitality::effect::EffectParams;
+/// attribute!(Health, f32);
+///
+/// let fireball = EffectBuilder::instant()
+///     .modify::<Health>(EffectParams::value::<f32>("damage"), ModOp::Sub, EffectSubject::Target)
+///     .build();
+///
+/// let params = EffectParams::new().with("damage", 42.0);
+/// ```
+#[derive(Component, Clone, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component, Default)]
+pub struct EffectParams(HashMap<SmolStr, f64>);
+
+impl EffectParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: impl Into<SmolStr>, value: f64) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    pub fn insert(&mut self, name: impl Into<SmolStr>, value: f64) {
+        self.0.insert(name.into(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.0.get(name).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SmolStr, f64)> {
+        self.0.iter().map(|(name, value)| (name, *value))
+    }
+
+    /// An expression reading the parameter of the effect, converted to `P`.
+    pub fn value<P: Value>(name: impl Into<SmolStr>) -> Expr<P, EffectExprSchema> {
+        Expr::new(Arc::new(EffectParam::<P> {
+            name: name.into(),
+            phantom_data: PhantomData,
+        }))
+    }
+
+    // Reads a path such as `effect.params.damage`
+    pub(crate) fn read_path(&self, path: &Path) -> Result<&f64, ExpressionError> {
+        let mut segments = path.0.split('.');
+        let (Some(_), Some(PARAMS_SEGMENT), Some(name), None) = (
+            segments.next(),
+            segments.next(),
+            segments.next(),
+            segments.next(),
+        ) else {
+            return Err(ExpressionError::InvalidPath(path.0.clone()));
+        };
+        self.0
+            .get(name)
+            .ok_or_else(|| ExpressionError::InvalidPath(path.0.clone()))
+    }
+}
+
+impl<K: Into<SmolStr>, const N: usize> From<[(K, f64); N]> for EffectParams {
+    fn from(params: [(K, f64); N]) -> Self {
+        Self(
+            params
+                .into_iter()
+                .map(|(name, value)| (name.into(), value))
+                .collect(),
+        )
+    }
+}
+
+/// Whether the path reads a parameter, as in `effect.params.damage`.
+pub(crate) fn is_param_path(path: &str) -> bool {
+    path.split('.').nth(1) == Some(PARAMS_SEGMENT)
+}
+
+/// Reads a parameter of the effect. See [`EffectParams::value`].
+pub struct EffectParam<P> {
+    name: SmolStr,
+    phantom_data: PhantomData<fn() -> P>,
+}
+
+impl<P> Debug for EffectParam<P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}",
+            EffectSubject::Effect,
+            PARAMS_SEGMENT,
+            self.name
+        )
+    }
+}
+
+impl<P: Value> ExprNode<P, EffectExprSchema> for EffectParam<P> {
+    fn eval(&self, ctx: &EffectExprContext) -> Result<P, ExpressionError> {
+        self.eval_dyn(ctx)
+    }
+
+    fn eval_dyn(&self, ctx: &dyn ReadContext) -> Result<P, ExpressionError> {
+        let path = Path::new(format!("{:?}", self));
+        let value = ctx
+            .get_any(&path)?
+            .downcast_ref::<f64>()
+            .ok_or_else(|| ExpressionError::InvalidPath(path.0.clone()))?;
+        P::from_f64(*value).ok_or_else(|| {
+            ExpressionError::FailedReflect(
+                format!("Cannot convert parameter {} from {}", self.name, value).into(),
+            )
+        })
+    }
+
+    // Parameters never change once the effect is applied
+    fn get_dependencies(&self, _deps: &mut HashSet<Path>) {}
+}
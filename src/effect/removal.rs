@@ -0,0 +1,281 @@
+use crate::assets::EffectDef;
+use crate::attributes::Attribute;
+use crate::effect::chaining::chained_applications;
+use crate::effect::events::{
+    EffectRemovalReason, EffectRemoved, EffectStacksChanged, StackChangeReason,
+};
+use crate::effect::{
+    AppliedEffects, ChainTrigger, Effect, EffectParams, EffectSource, EffectTarget, Stacks,
+};
+use crate::registry::effect_registry::{EffectRegistry, EffectToken};
+use crate::tags::GameplayTag;
+use bevy::prelude::*;
+use std::sync::Arc;
+
+type EffectPredicate = dyn Fn(&EntityRef, &EffectDef) -> bool + Send + Sync;
+
+/// Selects the effects removed by [`Vitality::remove_effects`](crate::context::Vitality::remove_effects).
+#[derive(Clone)]
+pub enum EffectFilter {
+    /// Every effect on the target.
+    Any,
+    /// Effects with this definition.
+    Handle(AssetId<EffectDef>),
+    /// Effects with the definition registered under this token.
+    Token(EffectToken),
+    /// Effects applied by this source.
+    Source(Entity),
+    /// Effects granting the tag or one of its descendants to their target.
+    ///
+    /// Only the [`granted_tags`](EffectDef::granted_tags) of the definition are matched, whether
+    /// the effect is active or not. Tag components inserted on the effect entity itself are not,
+    /// use a [`Predicate`](EffectFilter::Predicate) to select them.
+    Tag(GameplayTag),
+    /// Effects accepted by the predicate, given the effect entity and its definition.
+    Predicate(Arc<EffectPredicate>),
+}
+
+impl EffectFilter {
+    pub fn predicate(
+        predicate: impl Fn(&EntityRef, &EffectDef) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        EffectFilter::Predicate(Arc::new(predicate))
+    }
+
+    pub fn matches(
+        &self,
+        effect_ref: &EntityRef,
+        effect: &Effect,
+        effect_def: &EffectDef,
+        registry: &EffectRegistry,
+    ) -> bool {
+        match self {
+            EffectFilter::Any => true,
+            EffectFilter::Handle(id) => effect.id() == *id,
+            EffectFilter::Token(token) => registry
+                .get(token)
+                .is_some_and(|handle| handle.id() == effect.id()),
+            EffectFilter::Source(source) => effect_ref
+                .get::<EffectSource>()
+                .is_some_and(|effect_source| effect_source.0 == *source),
+            EffectFilter::Tag(tag) => effect_def
+                .granted_tags
+                .iter()
+                .any(|granted| granted.matches(tag)),
+            EffectFilter::Predicate(predicate) => predicate(effect_ref, effect_def),
+        }
+    }
+}
+
+impl From<&Handle<EffectDef>> for EffectFilter {
+    fn from(handle: &Handle<EffectDef>) -> Self {
+        EffectFilter::Handle(handle.id())
+    }
+}
+
+impl From<EffectToken> for EffectFilter {
+    fn from(token: EffectToken) -> Self {
+        EffectFilter::Token(token)
+    }
+}
+
+impl From<GameplayTag> for EffectFilter {
+    fn from(tag: GameplayTag) -> Self {
+        EffectFilter::Tag(tag)
+    }
+}
+
+/// Whether whole effects or some of their stacks are removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalMode {
+    Effect,
+    /// Removes this many stacks, and the effect with its last stack.
+    /// Removing no stacks removes nothing.
+    Stacks(u32),
+}
+
+/// A request to remove the effects of a target.
+///
+/// # Examples
+///
+/// ```
+/// # use vitality::effect::{EffectFilter, RemoveEffects};
+/// # use vitality::tags::GameplayTag;
+/// // Dispel 2 magic debuffs
+/// let dispel = RemoveEffects::matching(GameplayTag::new_static("Debuff.Magic")).at_most(2);
+/// ```
+#[derive(Clone)]
+pub struct RemoveEffects {
+    pub filter: EffectFilter,
+    pub max_count: Option<usize>,
+    pub mode: RemovalMode,
+}
+
+impl RemoveEffects {
+    pub fn matching(filter: impl Into<EffectFilter>) -> Self {
+        Self {
+            filter: filter.into(),
+            max_count: None,
+            mode: RemovalMode::Effect,
+        }
+    }
+
+    /// Removes at most `count` effects.
+    pub fn at_most(mut self, count: usize) -> Self {
+        self.max_count = Some(count);
+        self
+    }
+
+    /// Removes `stacks` stacks from each effect instead of the whole effect.
+    pub fn stacks(mut self, stacks: u32) -> Self {
+        self.mode = RemovalMode::Stacks(stacks);
+        self
+    }
+}
+
+pub struct RemoveEffectsCommand {
+    pub removal: RemoveEffects,
+}
+
+impl EntityCommand for RemoveEffectsCommand {
+    type Out = ();
+
+    fn apply(self, mut entity: EntityWorldMut) {
+        let target = entity.id();
+        entity.world_scope(|world| {
+            remove_effects(world, target, &self.removal);
+        });
+    }
+}
+
+/// Removes the effects of the target selected by the request.
+/// Returns how many effects were removed or lost stacks.
+pub fn remove_effects(world: &mut World, target: Entity, removal: &RemoveEffects) -> usize {
+    // Removing no stacks would count toward the maximum without changing anything
+    if removal.mode == RemovalMode::Stacks(0) {
+        return 0;
+    }
+    let Some(applied_effects) = world.get::<AppliedEffects>(target) else {
+        return 0;
+    };
+
+    let effect_assets = world.resource::<Assets<EffectDef>>();
+    let registry = world.resource::<EffectRegistry>();
+    let selected = applied_effects
+        .iter()
+        .filter(|effect_entity| {
+            let effect_ref = world.entity(*effect_entity);
+            let Some(effect) = effect_ref.get::<Effect>() else {
+                return false;
+            };
+            let Some(effect_def) = effect_assets.get(&effect.0) else {
+                return false;
+            };
+            removal
+                .filter
+                .matches(&effect_ref, effect, effect_def, registry)
+        })
+        .take(removal.max_count.unwrap_or(usize::MAX))
+        .collect::<Vec<_>>();
+
+    for &effect_entity in &selected {
+        let effect_ref = world.entity(effect_entity);
+        let (Some(effect), Some(source)) =
+            (effect_ref.get::<Effect>(), effect_ref.get::<EffectSource>())
+        else {
+            continue;
+        };
+        let handle = effect.0.clone();
+        let source = source.0;
+        let stacks = effect_ref
+            .get::<Stacks>()
+            .map(|stacks| stacks.base_value())
+            .unwrap_or(1);
+
+        match removal.mode {
+            RemovalMode::Stacks(count) if count < stacks => {
+                debug!("Removing {} stacks from effect {}.", count, effect_entity);
+                let new = stacks - count;
+                let mut effect_stacks = world.get_mut::<Stacks>(effect_entity).unwrap();
+                effect_stacks.set_base_value(new);
+                effect_stacks.set_current_value(new);
+
+                let event = EffectStacksChanged {
+                    entity: effect_entity,
+                    effect: effect_entity,
+                    target,
+                    source,
+                    handle,
+                    old: stacks,
+                    new,
+                    reason: StackChangeReason::Dispelled,
+                };
+                world.trigger(event.on(effect_entity));
+                world.trigger(event.on(target));
+            }
+            _ => {
+                debug!("Removing effect {} from {}.", effect_entity, target);
+                let mut effect_mut = world.entity_mut(effect_entity);
+                effect_mut.insert(EffectRemoval(EffectRemovalReason::Dispelled));
+                effect_mut.despawn();
+            }
+        }
+    }
+
+    selected.len()
+}
+
+/// Why an effect is being despawned, for the [`EffectRemoved`] event.
+/// Effects despawned without one are reported as [`EffectRemovalReason::Despawned`].
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub(crate) struct EffectRemoval(pub EffectRemovalReason);
+
+/// Notifies the removal of an effect, then applies the effects chained to it,
+/// however the effect is despawned.
+/// Chained effects are applied once the effect is gone, so they never stack onto it.
+pub(crate) fn on_remove_effect(
+    trigger: On<Remove, Effect>,
+    effects: Query<(
+        &Effect,
+        &EffectSource,
+        &EffectTarget,
+        Option<&EffectParams>,
+        Option<&EffectRemoval>,
+    )>,
+    targets: Query<(), With<AppliedEffects>>,
+    effect_assets: Res<Assets<EffectDef>>,
+    mut commands: Commands,
+) {
+    let effect_entity = trigger.event_target();
+    let Ok((effect, source, target, params, removal)) = effects.get(effect_entity) else {
+        return;
+    };
+    let event = EffectRemoved {
+        entity: effect_entity,
+        effect: effect_entity,
+        target: target.0,
+        source: source.0,
+        handle: effect.0.clone(),
+        reason: removal.map_or(EffectRemovalReason::Despawned, |removal| removal.0),
+    };
+    commands.trigger(event.on(effect_entity));
+    commands.trigger(event.on(target.0));
+
+    // Effects despawned with their target have nothing left to apply to
+    if !targets.contains(target.0) {
+        return;
+    }
+    let Some(effect_def) = effect_assets.get(&effect.0) else {
+        return;
+    };
+    for application in chained_applications(
+        effect_def,
+        ChainTrigger::Removed,
+        source.0,
+        target.0,
+        params,
+    ) {
+        commands.trigger(application);
+    }
+}
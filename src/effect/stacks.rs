@@ -1,18 +1,66 @@
-use express_it::expr::ExprSchema;
+use express_it::expr::{Expr, ExprSchema};
 use crate::assets::EffectDef;
 use crate::attribute;
+use crate::context::{EffectExprContext, EffectExprSchema};
+use crate::effect::chaining::chained_applications;
 use crate::effect::timing::EffectDuration;
-use crate::effect::Effect;
+use crate::effect::{
+    ChainTrigger, Effect, EffectParams, EffectRefreshed, EffectSource, EffectStacksChanged,
+    EffectTarget, StackChangeReason,
+};
+use crate::modifier::{AttributeModifier, OwnedModifiers};
 use crate::prelude::Attribute;
+use crate::systems::MarkNodeDirty;
 use crate::{attribute_impl, ReflectAccessAttribute};
 use bevy::prelude::*;
 use num_traits::AsPrimitive;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 pub enum EffectStackingPolicy {
     None, // Each effect is independently added to the entity
     Add { count: u32, max_stack: u32 },
+    /// Adds stacks and refreshes the duration of the effect.
+    AddAndRefresh { count: u32, max_stack: u32 },
     RefreshDuration, // The effect overrides previous applications
-                     //RefreshDurationWithOverflow, // The effect overrides previous applications
+}
+
+impl EffectStackingPolicy {
+    pub fn max_stack(&self) -> Option<u32> {
+        match self {
+            EffectStackingPolicy::Add { max_stack, .. }
+            | EffectStackingPolicy::AddAndRefresh { max_stack, .. } => Some(*max_stack),
+            EffectStackingPolicy::None | EffectStackingPolicy::RefreshDuration => None,
+        }
+    }
+
+    /// Whether stacking resets the duration of the effect.
+    pub fn refreshes_duration(&self) -> bool {
+        matches!(
+            self,
+            EffectStackingPolicy::AddAndRefresh { .. } | EffectStackingPolicy::RefreshDuration
+        )
+    }
+}
+
+/// Which applications of an effect stack together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StackingScope {
+    /// Applications from every source stack on a single effect.
+    #[default]
+    PerTarget,
+    /// Each source stacks its own effect on the target.
+    PerSource,
+}
+
+/// What happens when the duration of a stacked effect runs out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StackExpiration {
+    /// The effect expires with all its stacks.
+    #[default]
+    All,
+    /// A single stack is removed and the duration restarts, until the last stack expires.
+    OneStack,
 }
 attribute!(EffectIntensity, f32);
 
@@ -24,22 +72,83 @@ impl Default for Stacks {
     }
 }
 
+/// How the [`Stacks`] and [`EffectIntensity`] of an effect scale the magnitude of a modifier.
+#[derive(Clone, Default)]
+pub enum StackScaling {
+    /// The magnitude ignores stacks and intensity.
+    #[default]
+    None,
+    /// The magnitude is multiplied by the stack count and the intensity of the effect.
+    Linear,
+    /// The magnitude is multiplied by an expression, usually over `effect.Stacks`.
+    Custom(Expr<f64, EffectExprSchema>),
+}
+
+impl StackScaling {
+    pub fn is_scaled(&self) -> bool {
+        !matches!(self, StackScaling::None)
+    }
+
+    /// The factor applied to the modifier's magnitude.
+    /// The context's effect holder must be the effect entity owning the modifier.
+    pub fn factor(&self, ctx: &EffectExprContext) -> f64 {
+        match self {
+            StackScaling::None => 1.0,
+            StackScaling::Linear => {
+                let stacks: f64 = ctx
+                    .effect_holder
+                    .get::<Stacks>()
+                    .map(|stacks| stacks.current_value().as_())
+                    .unwrap_or(1.0);
+                let intensity: f64 = ctx
+                    .effect_holder
+                    .get::<EffectIntensity>()
+                    .map(|intensity| intensity.current_value().as_())
+                    .unwrap_or(1.0);
+                stacks * intensity
+            }
+            StackScaling::Custom(expr) => expr.eval(ctx).unwrap_or_else(|err| {
+                error!("Failed to evaluate stack scaling: {}", err);
+                1.0
+            }),
+        }
+    }
+}
+
+/// What applying a stacking policy did to the effect.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct StackingOutcome {
+    /// The old and new stack counts, if they changed.
+    pub stacked: Option<(u32, u32)>,
+    pub refreshed: bool,
+    /// The effect was already at its maximum stack count.
+    pub overflowed: bool,
+}
+
 impl Stacks {
-    /// Applies the appropriate stacking policy to an effect
+    /// Applies the appropriate stacking policy to an effect.
+    /// Refreshed durations are replaced by `refresh_duration` if any.
     pub fn apply_stacking_policy(
         policy: &EffectStackingPolicy,
         effect_entity: Entity,
+        refresh_duration: Option<Duration>,
         stacks: &mut Query<&mut Stacks, With<Effect>>,
         durations: &mut Query<&mut EffectDuration, With<Effect>>,
-    ) {
+    ) -> StackingOutcome {
+        let mut outcome = StackingOutcome::default();
         match policy {
-            EffectStackingPolicy::Add { count, max_stack } => {
+            EffectStackingPolicy::Add { count, max_stack }
+            | EffectStackingPolicy::AddAndRefresh { count, max_stack } => {
                 // Apply additive stacking, increasing stack count up to max
                 if let Ok(mut stack_count) = stacks.get_mut(effect_entity) {
-                    let mut base_stacks = stack_count.base_value();
-                    base_stacks += count;
-                    stack_count.set_base_value(base_stacks.clamp(1, max_stack.as_()));
-                    stack_count.set_current_value(base_stacks.clamp(1.as_(), max_stack.as_()));
+                    let old = stack_count.base_value();
+                    let new = (old + count).clamp(1, *max_stack);
+                    outcome.overflowed = old >= *max_stack;
+                    if new != old {
+                        stack_count.set_base_value(new);
+                        stack_count.set_current_value(new);
+                        outcome.stacked = Some((old, new));
+                    }
                 } else {
                     error!(
                         "Failed to find component Stacks for entity: {:?}",
@@ -47,17 +156,7 @@ impl Stacks {
                     );
                 }
             }
-            EffectStackingPolicy::RefreshDuration => {
-                // Reset duration for overridden effects
-                if let Ok(mut duration) = durations.get_mut(effect_entity) {
-                    duration.reset();
-                } else {
-                    error!(
-                        "Failed to find component EffectApplication for entity: {:?}",
-                        effect_entity
-                    );
-                }
-            }
+            EffectStackingPolicy::RefreshDuration => {}
             EffectStackingPolicy::None => {
                 error!(
                     "Effect stacking should not be triggered for effect entity {:?} with incompatible policy (None)",
@@ -65,6 +164,25 @@ impl Stacks {
                 );
             }
         }
+
+        if policy.refreshes_duration() {
+            // Reset duration for overridden effects
+            if let Ok(mut duration) = durations.get_mut(effect_entity) {
+                if let Some(refresh_duration) = refresh_duration {
+                    duration.set_duration(refresh_duration);
+                }
+                duration.reset();
+                outcome.refreshed = true;
+            } else if matches!(policy, EffectStackingPolicy::RefreshDuration) {
+                // Permanent effects stacking with AddAndRefresh have no duration to refresh
+                error!(
+                    "Failed to find component EffectApplication for entity: {:?}",
+                    effect_entity
+                );
+            }
+        }
+
+        outcome
     }
 }
 
@@ -72,13 +190,17 @@ impl Stacks {
 pub struct NotifyAddStackEvent {
     pub effect_entity: Entity,
     pub handle: Handle<EffectDef>,
+    /// The duration evaluated by the application, if the effect has a duration expression.
+    pub refresh_duration: Option<Duration>,
 }
 
 pub(crate) fn read_add_stack_event(
     mut event_reader: MessageReader<NotifyAddStackEvent>,
     mut stacks: Query<&mut Stacks, With<Effect>>,
     mut applications: Query<&mut EffectDuration, With<Effect>>,
+    relations: Query<(&EffectTarget, &EffectSource, &EffectParams)>,
     effect_assets: Res<Assets<EffectDef>>,
+    mut commands: Commands,
 ) {
     for ev in event_reader.read() {
         let effect_definition = match effect_assets.get(&ev.handle) {
@@ -91,11 +213,101 @@ pub(crate) fn read_add_stack_event(
             }
         };
 
-        Stacks::apply_stacking_policy(
+        let outcome = Stacks::apply_stacking_policy(
             &effect_definition.stacking_policy,
             ev.effect_entity,
+            ev.refresh_duration,
             &mut stacks,
             &mut applications,
         );
+
+        let Ok((target, source, params)) = relations.get(ev.effect_entity) else {
+            continue;
+        };
+        if let Some((old, new)) = outcome.stacked {
+            let event = EffectStacksChanged {
+                entity: ev.effect_entity,
+                effect: ev.effect_entity,
+                target: target.0,
+                source: source.0,
+                handle: ev.handle.clone(),
+                old,
+                new,
+                reason: StackChangeReason::Applied,
+            };
+            commands.trigger(event.on(ev.effect_entity));
+            commands.trigger(event.on(target.0));
+
+            if Some(new) == effect_definition.stacking_policy.max_stack() {
+                for application in chained_applications(
+                    effect_definition,
+                    ChainTrigger::MaxStacks,
+                    source.0,
+                    target.0,
+                    Some(params),
+                ) {
+                    debug!(
+                        "Effect {} reached its maximum stacks, applying {:?}.",
+                        ev.effect_entity, application.handle
+                    );
+                    commands.trigger(application);
+                }
+            }
+        }
+        if outcome.refreshed {
+            let event = EffectRefreshed {
+                entity: ev.effect_entity,
+                effect: ev.effect_entity,
+                target: target.0,
+                source: source.0,
+                handle: ev.handle.clone(),
+            };
+            commands.trigger(event.on(ev.effect_entity));
+            commands.trigger(event.on(target.0));
+        }
+        if outcome.overflowed {
+            for application in chained_applications(
+                effect_definition,
+                ChainTrigger::Overflow,
+                source.0,
+                target.0,
+                Some(params),
+            ) {
+                debug!(
+                    "Effect {} overflowed, applying {:?}.",
+                    ev.effect_entity, application.handle
+                );
+                commands.trigger(application);
+            }
+        }
+    }
+}
+
+/// Scaled modifiers depend on the stacks and intensity of their effect.
+/// When those change, the effect must be recalculated for the attribute.
+pub(crate) fn on_change_stacks_mark_scaled_modifiers_dirty<T: Attribute>(
+    effects: Query<
+        (Entity, &OwnedModifiers),
+        (
+            With<Effect>,
+            Or<(Changed<Stacks>, Changed<EffectIntensity>)>,
+        ),
+    >,
+    modifiers: Query<&AttributeModifier<T>>,
+    mut commands: Commands,
+) {
+    for (effect_entity, owned_modifiers) in effects.iter() {
+        let has_scaled_modifier = owned_modifiers.iter().any(|modifier_entity| {
+            modifiers
+                .get(modifier_entity)
+                .is_ok_and(|modifier| modifier.scaling.is_scaled())
+        });
+
+        if has_scaled_modifier {
+            commands.trigger(MarkNodeDirty::<T> {
+                entity: effect_entity,
+                phantom_data: Default::default(),
+            });
+        }
     }
 }
@@ -1,6 +1,17 @@
-use crate::effect::EffectInactive;
+use crate::assets::EffectDef;
+use crate::attributes::Attribute;
+use crate::effect::chaining::chained_applications_of;
+use crate::effect::removal::EffectRemoval;
+use crate::effect::{
+    ChainTrigger, Effect, EffectExpired, EffectInactive, EffectRemovalReason, EffectSource,
+    EffectStacksChanged, EffectTarget, StackChangeReason, StackExpiration, Stacks,
+};
+use crate::context::EffectExprContext;
+use crate::AttributesRef;
+use bevy::ecs::resource::IsResource;
 use bevy::prelude::*;
 use bevy::time::Timer;
+use std::time::Duration;
 
 #[derive(Component, Deref, DerefMut)]
 pub struct EffectDuration(pub Timer);
@@ -12,11 +23,55 @@ impl EffectDuration {
 }
 
 #[derive(Component, Deref, DerefMut)]
-pub struct EffectTicker(pub Timer);
+pub struct EffectTicker {
+    #[deref]
+    pub timer: Timer,
+    // How many times the effect ticked since it was applied
+    ticks: u32,
+    // Ticks due this frame, including the periods carried over by long frames
+    due: u32,
+    // Whether the effect ticks as soon as it is applied
+    tick_on_apply: bool,
+}
 
 impl EffectTicker {
     pub(crate) fn new(timer: &Timer) -> EffectTicker {
-        Self(timer.clone())
+        Self {
+            timer: timer.clone(),
+            ticks: 0,
+            due: 0,
+            tick_on_apply: false,
+        }
+    }
+
+    /// How many times the effect ticks this frame.
+    /// Several periods can elapse during a long frame, none of them is lost.
+    pub fn ticks_this_frame(&self) -> u32 {
+        self.due
+    }
+
+    /// How many times the effect ticked since it was applied.
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    pub(crate) fn tick_on_apply(mut self, tick_on_apply: bool) -> Self {
+        self.tick_on_apply = tick_on_apply;
+        self
+    }
+
+    /// Changes the interval of the next periods, keeping the elapsed time.
+    /// Intervals that are not a valid duration are ignored.
+    pub(crate) fn set_interval(&mut self, seconds: f32) {
+        match Duration::try_from_secs_f32(seconds) {
+            Ok(interval) => self.timer.set_duration(interval),
+            Err(err) => warn!("Invalid effect interval {}: {}", seconds, err),
+        }
+    }
+
+    pub(crate) fn restore(&mut self, ticks: u32) {
+        self.ticks = ticks;
+        self.tick_on_apply = false;
     }
 }
 
@@ -26,30 +81,186 @@ impl EffectTicker {
 /// excluding those with an `EffectInactive` component, and progresses their timers.
 /// This is done in parallel for performance optimization.
 pub fn tick_effect_durations(
-    mut query: Query<(Entity, &mut EffectDuration), Without<EffectInactive>>,
+    mut query: Query<(Entity, &Effect, &Stacks, &mut EffectDuration), Without<EffectInactive>>,
+    effect_assets: Res<Assets<EffectDef>>,
     time: Res<Time>,
     par_commands: ParallelCommands,
 ) {
     query
         .par_iter_mut()
-        .for_each(|(entity, mut effect_duration)| {
+        .for_each(|(entity, effect, stacks, mut effect_duration)| {
             effect_duration.0.tick(time.delta());
 
-            // Remove expired effects
-            if effect_duration.is_finished() {
+            if !effect_duration.is_finished() {
+                return;
+            }
+
+            let expires_one_stack = effect_assets.get(&effect.0).is_some_and(|effect_def| {
+                effect_def.stack_expiration == StackExpiration::OneStack
+            });
+
+            if expires_one_stack && stacks.base_value() > 1 {
+                debug!("Effect stack expired on {}.", entity);
+                effect_duration.reset();
+                par_commands.command_scope(|mut commands| {
+                    commands.queue(move |world: &mut World| expire_stack(world, entity));
+                });
+            } else {
+                // Remove expired effects
                 debug!("Effect expired on {}.", entity);
                 par_commands.command_scope(|mut commands| {
-                    commands.entity(entity).despawn();
+                    commands.queue(move |world: &mut World| expire_effect(world, entity));
                 });
             }
         });
 }
 
+/// Removes one stack of the effect and notifies the change.
+pub(crate) fn expire_stack(world: &mut World, effect_entity: Entity) {
+    let Ok(mut effect_mut) = world.get_entity_mut(effect_entity) else {
+        return;
+    };
+    let Some(mut stacks) = effect_mut.get_mut::<Stacks>() else {
+        return;
+    };
+    let old = stacks.base_value();
+    let new = old.saturating_sub(1).max(1);
+    stacks.set_base_value(new);
+    stacks.set_current_value(new);
+
+    let (Some(effect), Some(target), Some(source)) = (
+        effect_mut.get::<Effect>(),
+        effect_mut.get::<EffectTarget>(),
+        effect_mut.get::<EffectSource>(),
+    ) else {
+        return;
+    };
+    let event = EffectStacksChanged {
+        entity: effect_entity,
+        effect: effect_entity,
+        target: target.0,
+        source: source.0,
+        handle: effect.0.clone(),
+        old,
+        new,
+        reason: StackChangeReason::Expired,
+    };
+    world.trigger(event.on(effect_entity));
+    world.trigger(event.on(event.target));
+}
+
+/// Notifies the expiration of the effect, then despawns it as expired.
+pub(crate) fn expire_effect(world: &mut World, effect_entity: Entity) {
+    let Ok(effect_ref) = world.get_entity(effect_entity) else {
+        return;
+    };
+    let (Some(effect), Some(target), Some(source)) = (
+        effect_ref.get::<Effect>(),
+        effect_ref.get::<EffectTarget>(),
+        effect_ref.get::<EffectSource>(),
+    ) else {
+        world.despawn(effect_entity);
+        return;
+    };
+
+    let expired = EffectExpired {
+        entity: effect_entity,
+        effect: effect_entity,
+        target: target.0,
+        source: source.0,
+        handle: effect.0.clone(),
+    };
+    // Chained effects are applied once the effect is gone, so they never stack onto it
+    let chained = chained_applications_of(world, effect_entity, ChainTrigger::Expired);
+
+    world.trigger(expired.on(effect_entity));
+    world.trigger(expired.on(expired.target));
+    // Observers of the expiration may have despawned the effect already
+    if let Ok(mut effect_mut) = world.get_entity_mut(effect_entity) {
+        effect_mut.insert(EffectRemoval(EffectRemovalReason::Expired));
+        effect_mut.despawn();
+    }
+
+    for application in chained {
+        world.trigger(application);
+    }
+}
+
+/// Advances the tickers of all active periodic effects and counts their ticks this frame.
+///
+/// Effects limited to a number of ticks expire the frame after their last tick.
+/// Interval expressions are re-evaluated after each tick.
 pub fn tick_effect_tickers(
-    mut query: Query<&mut EffectTicker, Without<EffectInactive>>,
+    mut query: Query<
+        (
+            AttributesRef,
+            &Effect,
+            &EffectSource,
+            &EffectTarget,
+            &mut EffectTicker,
+        ),
+        (Without<EffectInactive>, Without<IsResource>),
+    >,
+    actors: Query<AttributesRef, Without<IsResource>>,
+    effect_assets: Res<Assets<EffectDef>>,
+    type_registry: Res<AppTypeRegistry>,
     time: Res<Time>,
+    par_commands: ParallelCommands,
 ) {
-    query.par_iter_mut().for_each(|mut effect_ticker| {
-        effect_ticker.0.tick(time.delta());
-    });
+    query
+        .par_iter_mut()
+        .for_each(|(effect_ref, effect, source, target, mut ticker)| {
+            let Some(effect_def) = effect_assets.get(&effect.0) else {
+                return;
+            };
+
+            let entity = effect_ref.id();
+            if effect_def
+                .max_ticks
+                .is_some_and(|max_ticks| ticker.ticks >= max_ticks)
+            {
+                debug!("Effect {} ticked for the last time.", entity);
+                ticker.due = 0;
+                par_commands.command_scope(|mut commands| {
+                    commands.queue(move |world: &mut World| expire_effect(world, entity));
+                });
+                return;
+            }
+
+            ticker.timer.tick(time.delta());
+            let mut due = ticker.timer.times_finished_this_tick();
+            if std::mem::take(&mut ticker.tick_on_apply) {
+                due += 1;
+            }
+            if let Some(max_ticks) = effect_def.max_ticks {
+                due = due.min(max_ticks - ticker.ticks);
+            }
+            ticker.due = due;
+            ticker.ticks += due;
+
+            let Some(interval) = &effect_def.tick_interval else {
+                return;
+            };
+            if due == 0 {
+                return;
+            }
+            let Ok([source_actor_ref, target_actor_ref]) = actors.get_many([source.0, target.0])
+            else {
+                return;
+            };
+            let context = EffectExprContext {
+                target_actor: &target_actor_ref,
+                source_actor: &source_actor_ref,
+                effect_holder: &effect_ref,
+                params: None,
+                type_registry: type_registry.0.clone(),
+            };
+            match interval.eval(&context) {
+                Ok(seconds) if seconds > 0.0 => ticker.set_interval(seconds),
+                Ok(seconds) => warn!("Effect {} has invalid interval {}.", entity, seconds),
+                Err(err) => {
+                    error!("Failed to evaluate interval of effect {}: {}", entity, err)
+                }
+            }
+        });
 }
@@ -1,6 +1,10 @@
 extern crate core;
+// Lets macros refer to `::vitality` inside the crate.
+extern crate self as vitality;
 
-use crate::effect::{AttributeDependency, Stacks};
+use crate::effect::{
+    on_change_stacks_mark_scaled_modifiers_dirty, AttributeDependency, EffectIntensity, Stacks,
+};
 use bevy::prelude::*;
 use std::any::{Any, TypeId};
 use std::error::Error;
@@ -22,33 +26,42 @@ pub mod inspector;
 pub mod math;
 pub mod modifier;
 pub mod mutator;
+pub mod parser;
 pub mod registry;
 mod schedule;
+pub mod snapshot;
 mod systems;
+pub mod tags;
 mod trigger;
 
-use crate::ability::{Ability, AbilityCooldown, AbilityOf, AbilityPlugin, GrantedAbilities};
+use crate::ability::{
+    Ability, AbilityCharges, AbilityCooldown, AbilityOf, AbilityPlugin, ActiveAbility, Charges,
+    CooldownGroups, CooldownReduction, GrantedAbilities,
+};
 use crate::assets::{AbilityDef, ActorDef, EffectDef};
 use crate::attributes::{
     on_add_attribute, on_change_notify_attribute_dependencies, on_change_notify_attribute_parents,
     ReflectAccessAttribute,
 };
 use crate::condition::ConditionPlugin;
+use crate::tags::GameplayTagsPlugin;
 use crate::effect::global_effect::GlobalEffectPlugin;
 use crate::effect::{
-    AppliedEffects, Effect, EffectDuration, EffectSource, EffectSources, EffectTarget,
-    EffectTicker, EffectsPlugin,
+    AppliedEffects, Effect, EffectDefLoader, EffectDuration, EffectSource, EffectSources,
+    EffectTarget, EffectTicker, EffectsPlugin, tick_effect_tickers,
 };
 use crate::graph::NodeType;
 use crate::inspector::pretty_type_name;
 use crate::modifier::{
-    apply_modifier_events, ApplyAttributeModifierMessage, AttributeCalculatorCached, ModifierOf,
+    apply_modifier_events, AggregationPipeline, ApplyAttributeModifierMessage,
+    AttributeCalculatorCached, ModifierCategories, ModifierOf,
 };
 use crate::prelude::*;
 use crate::registry::RegistryPlugin;
 use crate::schedule::EffectsSet;
 use crate::systems::{
-    apply_periodic_effect, mark_node_dirty_observer, update_attribute, update_current_value_system,
+    apply_periodic_effect, mark_node_dirty_observer, on_remove_effect_mark_target_dirty,
+    update_attribute, update_current_value_system,
 };
 use bevy::ecs::world::{EntityMutExcept, EntityRefExcept};
 use bevy::platform::collections::hash_map::Entry;
@@ -70,8 +83,18 @@ pub mod prelude {
 }
 
 use crate::attribute::clamps::{apply_clamps, update_clamps, Clamp};
+use crate::attribute::derived::{on_add_derived_attribute, recalculate_derived_attribute};
+use crate::context::BaseValueNotifier;
+use crate::math::AbsDiff;
+use num_traits::{AsPrimitive, FromPrimitive};
+use crate::condition::IsAttributeWithinBounds;
+use crate::effect::StackScaling;
+use crate::modifier::modifier::Modifier;
+use express_it::logic::BoolExpr;
+use std::ops::Bound;
 use crate::modifier::modifier::update_modifier_when_dependencies_changed;
 
+pub use attributes_macro::attribute_calculator;
 pub use express_it;
 pub use num_traits;
 use smol_str::SmolStr;
@@ -85,15 +108,22 @@ impl Plugin for AttributesPlugin {
                 AbilityPlugin,
                 ConditionPlugin,
                 EffectsPlugin,
+                GameplayTagsPlugin,
                 GlobalEffectPlugin,
                 RegistryPlugin,
             ))
-            .add_plugins(init_attribute::<Stacks>)
+            .add_plugins((
+                init_attribute::<Stacks>,
+                init_attribute::<EffectIntensity>,
+                init_attribute::<Charges>,
+                init_attribute::<CooldownReduction>,
+            ))
             .init_schedule(PreUpdate)
             .init_schedule(PostUpdate)
             .init_asset::<ActorDef>()
             .init_asset::<EffectDef>()
             .init_asset::<AbilityDef>()
+            .init_asset_loader::<EffectDefLoader>()
             .register_type::<AppliedEffects>()
             .register_type::<EffectTarget>()
             .register_type::<NodeType>();
@@ -129,18 +159,74 @@ pub struct AttributeBindings {
     type_id_map: HashMap<SmolStr, TypeId>,
     convert: HashMap<SmolStr, fn(&dyn Any) -> Option<&dyn Reflect>>,
     how_to_insert_dependency: HashMap<SmolStr, fn(Entity, &mut EntityCommands)>,
+    notify_base_value_changed: HashMap<SmolStr, BaseValueChangedFn>,
+    read_base_value: HashMap<SmolStr, fn(&EntityRef) -> Option<f64>>,
+    as_f64: HashMap<SmolStr, fn(&dyn Any) -> Option<f64>>,
+    write_base_value: HashMap<SmolStr, fn(&mut EntityWorldMut, f64)>,
+    literal_modifier: HashMap<SmolStr, LiteralModifierFn>,
+    pipelines: HashMap<TypeId, Arc<AggregationPipeline>>,
+    bounds_condition: HashMap<SmolStr, BoundsConditionFn>,
 }
 
+// Builds the deferred BaseValueChanged<T> trigger when all we know is the attribute ID
+type BaseValueChangedFn = fn(
+    Entity,
+    Entity,
+    Option<Entity>,
+    &dyn PartialReflect,
+    &dyn Any,
+) -> Option<BaseValueNotifier>;
+
+// Builds a modifier with a literal magnitude when all we know is the attribute ID
+type LiteralModifierFn =
+    fn(f64, ModOp, EffectSubject, StackScaling, Option<SmolStr>) -> Option<Box<dyn Modifier>>;
+
+// Builds an inclusive bounds condition when all we know is the attribute ID
+type BoundsConditionFn =
+    fn(Option<f64>, Option<f64>, EffectSubject) -> Option<BoolExpr<EffectExprSchema>>;
+
 impl AttributeBindings {
     fn add<T: Attribute>(&mut self) {
         let name = pretty_type_name::<T>();
 
         self.bind_type_id::<T>();
 
+        // Starts with the default pipeline, which init_attribute_with_pipeline replaces afterwards
+        self.pipelines.entry(TypeId::of::<T>()).or_default();
+
         self.convert.insert(name.clone().into(), Self::convert_fn::<T>);
 
         self.how_to_insert_dependency
             .insert(name.clone().into(), Self::dependency_fn::<T>);
+
+        self.notify_base_value_changed
+            .insert(name.clone().into(), Self::base_value_changed_fn::<T>);
+
+        self.read_base_value
+            .insert(name.clone().into(), Self::read_base_value_fn::<T>);
+
+        self.as_f64.insert(name.clone().into(), Self::as_f64_fn::<T>);
+
+        self.write_base_value
+            .insert(name.clone().into(), Self::write_base_value_fn::<T>);
+
+        self.literal_modifier
+            .insert(name.clone().into(), Self::literal_modifier_fn::<T>);
+
+        self.bounds_condition
+            .insert(name.clone().into(), Self::bounds_condition_fn::<T>);
+    }
+
+    /// The aggregation pipeline of attribute `T`, or the default [`ModOp`] pipeline.
+    pub fn pipeline<T: Attribute>(&self) -> Arc<AggregationPipeline> {
+        self.pipelines
+            .get(&TypeId::of::<T>())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn set_pipeline<T: Attribute>(&mut self, pipeline: AggregationPipeline) {
+        self.pipelines.insert(TypeId::of::<T>(), Arc::new(pipeline));
     }
 
     // Binds the AttributeId to a specific TypeId used for reflection
@@ -173,6 +259,82 @@ impl AttributeBindings {
     fn dependency_fn<T: Attribute>(entity: Entity, commands: &mut EntityCommands) {
         commands.insert(AttributeDependency::<T>::new(entity));
     }
+
+    // Converts a property read through reflection when all we know is the attribute ID
+    fn as_f64_fn<T: Attribute>(any: &dyn Any) -> Option<f64> {
+        any.downcast_ref::<T::Property>().map(|value| value.as_())
+    }
+
+    // Reads the base value of an attribute when all we know is the attribute ID
+    fn read_base_value_fn<T: Attribute>(entity: &EntityRef) -> Option<f64> {
+        entity.get::<T>().map(|attribute| attribute.base_value().as_())
+    }
+
+    // Writes the base value of an attribute when all we know is the attribute ID.
+    // The current value is reset to the base value until the effect tree is recalculated.
+    fn write_base_value_fn<T: Attribute>(entity: &mut EntityWorldMut, value: f64) {
+        let Some(value) = T::Property::from_f64(value) else {
+            error!("Cannot convert {} to {}.", value, pretty_type_name::<T>());
+            return;
+        };
+        if let Some(mut attribute) = entity.get_mut::<T>() {
+            attribute.set_base_value(value);
+            attribute.set_current_value(value);
+        }
+    }
+
+    fn literal_modifier_fn<T: Attribute>(
+        value: f64,
+        operation: ModOp,
+        who: EffectSubject,
+        scaling: StackScaling,
+        channel: Option<SmolStr>,
+    ) -> Option<Box<dyn Modifier>> {
+        let value = T::Property::from_f64(value)?;
+        let mut modifier = AttributeModifier::<T>::new(value, operation, who, T::lit(value));
+        modifier.scaling = scaling;
+        modifier.channel = channel;
+        Some(Box::new(modifier))
+    }
+
+    fn bounds_condition_fn<T: Attribute>(
+        min: Option<f64>,
+        max: Option<f64>,
+        who: EffectSubject,
+    ) -> Option<BoolExpr<EffectExprSchema>> {
+        let to_bound = |value: Option<f64>| match value {
+            Some(value) => T::Property::from_f64(value).map(Bound::Included),
+            None => Some(Bound::Unbounded),
+        };
+        let bounds = (to_bound(min)?, to_bound(max)?);
+        Some(IsAttributeWithinBounds::<T>::new(bounds, who).into())
+    }
+
+    // Compares the old and new base values and prepares the matching BaseValueChanged<T> trigger
+    fn base_value_changed_fn<T: Attribute>(
+        entity: Entity,
+        source: Entity,
+        effect: Option<Entity>,
+        old: &dyn PartialReflect,
+        new: &dyn Any,
+    ) -> Option<BaseValueNotifier> {
+        let old = *old.try_downcast_ref::<T::Property>()?;
+        let new = *new.downcast_ref::<T::Property>()?;
+        if !old.are_different(new) {
+            return None;
+        }
+
+        Some(Box::new(move |commands: &mut Commands| {
+            commands.trigger(BaseValueChanged::<T> {
+                phantom_data: Default::default(),
+                old,
+                new,
+                entity,
+                source,
+                effect,
+            });
+        }))
+    }
 }
 
 pub fn init_attribute<T: Attribute>(app: &mut App) {
@@ -193,12 +355,18 @@ pub fn init_attribute<T: Attribute>(app: &mut App) {
 
     app.add_systems(
         Update,
-        apply_periodic_effect::<T>.in_set(EffectsSet::Prepare),
+        apply_periodic_effect::<T>
+            .after(tick_effect_tickers)
+            .in_set(EffectsSet::Prepare),
     );
 
     app.add_systems(
         Update,
-        apply_modifier_events::<T>.in_set(EffectsSet::UpdateBaseValues),
+        (
+            apply_modifier_events::<T>,
+            on_change_stacks_mark_scaled_modifiers_dirty::<T>,
+        )
+            .in_set(EffectsSet::UpdateBaseValues),
     );
 
     app.add_systems(
@@ -217,10 +385,13 @@ pub fn init_attribute<T: Attribute>(app: &mut App) {
     );
 
     app.add_observer(mark_node_dirty_observer::<T>);
+    app.add_observer(on_remove_effect_mark_target_dirty::<T>);
     app.add_observer(on_add_attribute::<T>);
     app.add_observer(update_attribute::<T>);
     app.add_observer(update_modifier_when_dependencies_changed::<T>);
     app.add_observer(update_clamps::<T>);
+    app.add_observer(on_add_derived_attribute::<T>);
+    app.add_observer(recalculate_derived_attribute::<T>);
 
     debug!(
         "Registered Systems for attribute: {}.",
@@ -228,6 +399,46 @@ pub fn init_attribute<T: Attribute>(app: &mut App) {
     );
 }
 
+/// Registers attribute `T` with a custom [`AggregationPipeline`] in place of the [`ModOp`] pipeline.
+///
+/// # Example
+/// ```
+/// # use bevy::prelude::*;
+/// # use vitality::prelude::*;
+/// # use vitality::{AttributesPlugin, init_attribute_with_pipeline};
+/// # use vitality::modifier::{AggregationPipeline, StageKind};
+/// attribute!(Damage, f32);
+///
+/// App::new().add_plugins((
+///     AttributesPlugin,
+///     init_attribute_with_pipeline::<Damage>(
+///         AggregationPipeline::default()
+///             .stage("final_flat", StageKind::Add)
+///             .stage("override", StageKind::Override),
+///     ),
+/// ));
+/// ```
+pub fn init_attribute_with_pipeline<T: Attribute>(
+    pipeline: AggregationPipeline,
+) -> impl Fn(&mut App) + Send + Sync + 'static {
+    move |app: &mut App| {
+        init_attribute::<T>(app);
+        app.world_mut()
+            .resource_mut::<AppAttributeBindings>()
+            .internal
+            .write()
+            .unwrap()
+            .set_pipeline::<T>(pipeline.clone());
+    }
+}
+
+/// Initializes an attribute aggregated with the categories of a modifier enum,
+/// see [`ModifierCategories`](crate::modifier::ModifierCategories).
+pub fn init_attribute_with_calculator<T: Attribute, C: ModifierCategories>()
+-> impl Fn(&mut App) + Send + Sync + 'static {
+    init_attribute_with_pipeline::<T>(C::pipeline())
+}
+
 pub type AttributesMut<'w, 's> = EntityMutExcept<
     'w,
     's,
@@ -241,10 +452,16 @@ pub type AttributesMut<'w, 's> = EntityMutExcept<
         EffectTarget,
         AppliedEffects,
         EffectSources,
-        Ability,
-        GrantedAbilities,
-        AbilityOf,
-        AbilityCooldown,
+        // and abilities
+        (
+            Ability,
+            GrantedAbilities,
+            AbilityOf,
+            AbilityCooldown,
+            AbilityCharges,
+            ActiveAbility,
+            CooldownGroups,
+        ),
         ModifierOf,
     ),
 >;
@@ -262,10 +479,16 @@ pub type AttributesRef<'w, 's> = EntityRefExcept<
         EffectTarget,
         AppliedEffects,
         EffectSources,
-        Ability,
-        GrantedAbilities,
-        AbilityOf,
-        AbilityCooldown,
+        // and abilities
+        (
+            Ability,
+            GrantedAbilities,
+            AbilityOf,
+            AbilityCooldown,
+            AbilityCharges,
+            ActiveAbility,
+            CooldownGroups,
+        ),
         ModifierOf,
     ),
 >;
@@ -284,12 +507,18 @@ impl<T> Default for Dirty<T> {
     }
 }
 
+/// Triggered on an actor whenever the base value of attribute `T` changes.
+///
+/// `entity` is the actor whose attribute changed. `source` is the actor responsible for the change
+/// and `effect` the effect (or ability) entity that carried it, if any.
 #[derive(EntityEvent, Debug)]
 pub struct BaseValueChanged<T: Attribute> {
     pub phantom_data: PhantomData<T>,
     pub old: T::Property,
     pub new: T::Property,
     pub entity: Entity,
+    pub source: Entity,
+    pub effect: Option<Entity>,
 }
 
 #[derive(EntityEvent, Debug)]
@@ -0,0 +1,251 @@
+use crate::{AppAttributeBindings, AttributesRef};
+use crate::attribute::clamps::{clamp_partial, Clamp};
+use crate::context::EffectExprContext;
+use crate::effect::{AppliedEffects, EffectSource, EffectStatusParam, EffectTarget};
+use crate::graph::NodeType;
+use crate::inspector::pretty_type_name;
+use crate::modifier::{AggregationPipeline, AttributeCalculator, ModOp, OwnedModifiers};
+use crate::prelude::*;
+use bevy::ecs::resource::IsResource;
+use bevy::ecs::system::SystemParam;
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+use std::fmt::{Display, Formatter};
+
+/// Why a modifier does not contribute to the current value of an attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The effect's activation conditions are not met.
+    Inactive,
+    /// The effect is periodic and modifies the base value on each tick instead.
+    Periodic,
+}
+
+/// A single modifier and the value it contributes.
+#[derive(Debug, Clone)]
+pub struct ModifierBreakdown<T: Attribute> {
+    pub modifier: Entity,
+    pub operation: ModOp,
+    /// The evaluated value, scaled by the effect's stacks and intensity.
+    pub value: T::Property,
+    pub skipped: Option<SkipReason>,
+}
+
+/// An effect applied to the actor and its modifiers for the attribute.
+#[derive(Debug, Clone)]
+pub struct EffectBreakdown<T: Attribute> {
+    pub effect: Entity,
+    pub source: Entity,
+    pub name: Option<String>,
+    pub skipped: Option<SkipReason>,
+    pub modifiers: Vec<ModifierBreakdown<T>>,
+}
+
+/// Explains how the current value of attribute `T` on an actor is computed.
+#[derive(Debug, Clone)]
+pub struct AttributeBreakdown<T: Attribute> {
+    pub actor: Entity,
+    pub base_value: T::Property,
+    /// The limits of the base value, if the attribute is clamped.
+    pub clamp: Option<(T::Property, T::Property)>,
+    pub effects: Vec<EffectBreakdown<T>>,
+    /// The value computed from the base value and all contributing modifiers.
+    pub result: T::Property,
+}
+
+impl<T: Attribute> AttributeBreakdown<T> {
+    /// Iterates over the modifiers that contribute to the result.
+    pub fn contributing(&self) -> impl Iterator<Item = &ModifierBreakdown<T>> {
+        self.effects
+            .iter()
+            .flat_map(|effect| effect.modifiers.iter())
+            .filter(|modifier| modifier.skipped.is_none())
+    }
+}
+
+impl<T: Attribute> Display for AttributeBreakdown<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.clamp {
+            None => writeln!(f, "{} (base {})", pretty_type_name::<T>(), self.base_value)?,
+            Some((min, max)) => writeln!(
+                f,
+                "{} (base {}, clamped to {}..={})",
+                pretty_type_name::<T>(),
+                self.base_value,
+                min,
+                max
+            )?,
+        }
+        for effect in &self.effects {
+            let name = effect.name.as_deref().unwrap_or("Effect");
+            match effect.skipped {
+                None => writeln!(f, "  {}({})", name, effect.effect)?,
+                Some(reason) => writeln!(f, "  {}({}) [{:?}]", name, effect.effect, reason)?,
+            }
+            for modifier in &effect.modifiers {
+                writeln!(f, "    {}{}", modifier.operation, modifier.value)?;
+            }
+        }
+        write!(f, "  = {}", self.result)
+    }
+}
+
+/// Reads the effect tree of actors to explain the values of their attributes.
+///
+/// Used for tooltips and balance debugging.
+#[derive(SystemParam)]
+pub struct AttributeExplainer<'w, 's> {
+    attribute_refs: Query<'w, 's, AttributesRef<'static, 'static>, Without<IsResource>>,
+    nodes: Query<'w, 's, &'static NodeType>,
+    statuses: Query<'w, 's, EffectStatusParam>,
+    applied_effects: Query<'w, 's, &'static AppliedEffects>,
+    effects: Query<
+        'w,
+        's,
+        (
+            &'static EffectSource,
+            &'static EffectTarget,
+            &'static OwnedModifiers,
+            Option<&'static Name>,
+        ),
+    >,
+    type_registry: Res<'w, AppTypeRegistry>,
+    type_bindings: Res<'w, AppAttributeBindings>,
+}
+
+impl AttributeExplainer<'_, '_> {
+    /// Returns the base value, every effect and modifier affecting attribute `T` and the final result.
+    pub fn explain<T: Attribute>(&self, actor: Entity) -> Result<AttributeBreakdown<T>, BevyError> {
+        let actor_ref = self.attribute_refs.get(actor)?;
+        let base_value = actor_ref
+            .get::<T>()
+            .ok_or(format!(
+                "Could not find attribute {} on entity {}.",
+                pretty_type_name::<T>(),
+                actor
+            ))?
+            .base_value();
+        let clamp = actor_ref
+            .get::<Clamp<T>>()
+            .map(|clamp| (clamp.min_limit, clamp.max_limit));
+
+        let pipeline = self.type_bindings.internal.read().unwrap().pipeline::<T>();
+        let mut calculator = AttributeCalculator::<T>::default();
+        let mut effects = vec![];
+        let mut visited = HashSet::default();
+        self.visit_node(actor, None, &pipeline, &mut calculator, &mut effects, &mut visited);
+
+        // The base value is kept within its limits before the modifiers apply
+        let clamped_base =
+            clamp.map_or(base_value, |(min, max)| clamp_partial(base_value, min, max));
+        Ok(AttributeBreakdown {
+            actor,
+            base_value,
+            clamp,
+            effects,
+            result: calculator.eval(clamped_base, &pipeline),
+        })
+    }
+
+    /// Walks the effect tree like the attribute update does.
+    /// Nodes below an inactive or periodic node are reported with the same reason.
+    fn visit_node<T: Attribute>(
+        &self,
+        node: Entity,
+        inherited: Option<SkipReason>,
+        pipeline: &AggregationPipeline,
+        calculator: &mut AttributeCalculator<T>,
+        effects: &mut Vec<EffectBreakdown<T>>,
+        visited: &mut HashSet<Entity>,
+    ) {
+        if !visited.insert(node) {
+            return;
+        }
+        let Ok(node_type) = self.nodes.get(node) else {
+            return;
+        };
+        let skipped = inherited.or_else(|| {
+            let status = self.statuses.get(node).ok()?;
+            if status.is_inactive() {
+                Some(SkipReason::Inactive)
+            } else if status.is_periodic() {
+                Some(SkipReason::Periodic)
+            } else {
+                None
+            }
+        });
+
+        match node_type {
+            NodeType::Actor => {
+                for effect in self.applied_effects.get(node).into_iter().flat_map(|e| e.iter()) {
+                    self.visit_node(effect, skipped, pipeline, calculator, effects, visited);
+                }
+            }
+            NodeType::Effect => {
+                if let Some(effect) = self.explain_effect(node, skipped, pipeline, calculator) {
+                    effects.push(effect);
+                }
+            }
+        }
+    }
+
+    /// Explains the modifiers of an effect, skipping those that cannot be read.
+    fn explain_effect<T: Attribute>(
+        &self,
+        effect_entity: Entity,
+        skipped: Option<SkipReason>,
+        pipeline: &AggregationPipeline,
+        calculator: &mut AttributeCalculator<T>,
+    ) -> Option<EffectBreakdown<T>> {
+        let (source, target, owned_modifiers, name) = self.effects.get(effect_entity).ok()?;
+        let Ok([source_ref, target_ref, effect_ref]) =
+            self.attribute_refs.get_many([source.0, target.0, effect_entity])
+        else {
+            warn!("{}: Could not read the actors of the effect.", effect_entity);
+            return None;
+        };
+
+        let context = EffectExprContext {
+            source_actor: &source_ref,
+            target_actor: &target_ref,
+            effect_holder: &effect_ref,
+            params: None,
+            type_registry: self.type_registry.0.clone(),
+        };
+
+        let mut modifiers = vec![];
+        for modifier_entity in owned_modifiers.iter() {
+            let Ok(modifier_ref) = self.attribute_refs.get(modifier_entity) else {
+                continue;
+            };
+            let Some(modifier) = modifier_ref.get::<AttributeModifier<T>>() else {
+                continue;
+            };
+            let modifier = modifier.scaled(&context);
+
+            if skipped.is_none() {
+                let calc = AttributeCalculator::convert(&modifier, pipeline).unwrap_or_default();
+                calculator.combine_in_place(&calc);
+            }
+
+            modifiers.push(ModifierBreakdown {
+                modifier: modifier_entity,
+                operation: modifier.operation,
+                value: modifier.value,
+                skipped,
+            });
+        }
+
+        if modifiers.is_empty() {
+            return None;
+        }
+
+        Some(EffectBreakdown {
+            effect: effect_entity,
+            source: source.0,
+            name: name.map(|name| name.to_string()),
+            skipped,
+            modifiers,
+        })
+    }
+}
@@ -1,18 +1,53 @@
-use crate::math::SaturatingAttributes;
 use crate::prelude::{Attribute, AttributeModifier};
 use bevy::prelude::*;
-use express_it::expr::ExpressionError;
-use num_traits::{AsPrimitive, Bounded, FromPrimitive, Zero};
-use serde::Serialize;
+use num_traits::{AsPrimitive, Bounded, FromPrimitive};
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
 use std::fmt::{Debug, Display, Formatter};
+use std::marker::PhantomData;
 
-#[derive(Debug, Clone, Copy, Reflect, Serialize)]
+#[derive(Debug, Clone, Copy, Reflect, Serialize, Deserialize)]
 pub enum ModOp {
+    /// Replaces the value. Same as an override with priority 0.
     Set,
     Add,
     Sub,
     Increase,
     Mul,
+    Div,
+    /// Caps the value.
+    Min,
+    /// Floors the value.
+    Max,
+    /// Adds after the multipliers.
+    PostAdd,
+    /// Replaces the value. The highest priority wins, then the highest value.
+    Override { priority: i32 },
+}
+
+impl ModOp {
+    /// The channel of the default [`AggregationPipeline`] targeted by the operation.
+    pub fn channel(&self) -> &'static str {
+        match self {
+            ModOp::Set | ModOp::Override { .. } => "set",
+            ModOp::Add => "add",
+            ModOp::Sub => "sub",
+            ModOp::Increase => "increase",
+            ModOp::Mul => "mul",
+            ModOp::Div => "div",
+            ModOp::Min => "min",
+            ModOp::Max => "max",
+            ModOp::PostAdd => "post_add",
+        }
+    }
+
+    /// The priority of the operation when resolving overrides.
+    pub fn priority(&self) -> i32 {
+        match self {
+            ModOp::Override { priority } => *priority,
+            _ => 0,
+        }
+    }
 }
 
 impl Display for ModOp {
@@ -23,11 +58,16 @@ impl Display for ModOp {
             ModOp::Sub => write!(f, "-"),
             ModOp::Increase => write!(f, "+*"),
             ModOp::Mul => write!(f, "*"),
+            ModOp::Div => write!(f, "/"),
+            ModOp::Min => write!(f, "<="),
+            ModOp::Max => write!(f, ">="),
+            ModOp::PostAdd => write!(f, "+>"),
+            ModOp::Override { priority } => write!(f, "=[{}]", priority),
         }
     }
 }
 
-#[derive(Component, Clone, Copy, Reflect, Debug)]
+#[derive(Component, Clone, Reflect, Debug)]
 pub struct AttributeCalculatorCached<T: Attribute> {
     #[reflect(ignore)]
     pub calculator: AttributeCalculator<T>,
@@ -41,118 +81,291 @@ impl<T: Attribute> Default for AttributeCalculatorCached<T> {
     }
 }
 
-#[derive(Debug, Clone, Copy, Reflect)]
+/// A calculator accumulates the modifiers of each channel.
+/// The attribute's [`AggregationPipeline`] then folds the channels into the final value.
+///
+/// Channels are stored by their index in the pipeline the modifiers were converted with.
+#[derive(Debug, Clone)]
 pub struct AttributeCalculator<T: Attribute> {
-    pub(crate) set: Option<T::Property>,
-    pub(crate) additive: T::Property,
-    pub(crate) subtractive: T::Property,
-    pub(crate) increase: f64,
-    pub(crate) multiplier: f64,
+    channels: Vec<ChannelValue>,
+    phantom_data: PhantomData<T>,
 }
 
-impl<T: Attribute> AttributeCalculator<T> {
-    pub fn eval(&self, base_value: T::Property) -> T::Property {
-        if let Some(set) = self.set {
-            return set;
-        }
-
-        // Additions/Substraction
-        let addition_result: T::Property = base_value.saturating_add(self.additive);
-        let subtraction_result: f64 = addition_result.saturating_sub(self.subtractive).as_();
-
-        // Increase and Decrease
-        let add_multi_result = subtraction_result * (1.0 + self.increase);
-
-        // Apply multipliers
-        let result = add_multi_result * self.multiplier;
+/// The accumulated modifiers of a channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelValue {
+    /// The sum of the values, used by additive stages.
+    pub sum: f64,
+    /// The product of the values, used by multiplicative stages.
+    pub product: f64,
+    /// The smallest value, used by cap stages.
+    pub min: f64,
+    /// The largest value, used by floor stages.
+    pub max: f64,
+    /// The winning priority and value, used by override stages.
+    pub overriding: Option<(i32, f64)>,
+}
 
-        // Clamp the result to property's min/max values
-        let min = T::Property::min_value();
-        let max = T::Property::max_value();
+impl Default for ChannelValue {
+    fn default() -> Self {
+        Self {
+            sum: 0.0,
+            product: 1.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            overriding: None,
+        }
+    }
+}
 
-        let min_f: f64 = min.as_();
-        let max_f: f64 = max.as_();
+impl ChannelValue {
+    fn from_value(value: f64, priority: i32) -> Self {
+        Self {
+            sum: value,
+            product: value,
+            min: value,
+            max: value,
+            overriding: Some((priority, value)),
+        }
+    }
 
-        let clamped_value = result.clamp(min_f, max_f);
-        T::Property::from_f64(clamped_value).unwrap()
+    // Every field is resolved independently of the order in which modifiers are combined
+    fn combine(&mut self, other: &ChannelValue) {
+        self.sum += other.sum;
+        self.product *= other.product;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.overriding = match (self.overriding, other.overriding) {
+            (Some(a), Some(b)) => {
+                let b_wins = b.0.cmp(&a.0).then(b.1.total_cmp(&a.1)).is_gt();
+                Some(if b_wins { b } else { a })
+            }
+            (a, b) => a.or(b),
+        };
     }
+}
 
-    pub fn combine(self, other: AttributeCalculator<T>) -> AttributeCalculator<T> {
-        // If either has a set value, the last one wins (or you could define other logic)
-        let set = self.set.or(other.set);
+impl<T: Attribute> AttributeCalculator<T> {
+    /// Computes the value of the attribute by running the base value through the pipeline.
+    pub fn eval(&self, base_value: T::Property, pipeline: &AggregationPipeline) -> T::Property {
+        // The result is clamped to property's min/max values
+        let min: f64 = T::Property::min_value().as_();
+        let max: f64 = T::Property::max_value().as_();
 
-        // Combine additive values
-        let additive = self.additive + other.additive;
-        let subtractive = self.subtractive + other.subtractive;
+        let mut value: f64 = base_value.as_();
+        for (stage, &index) in pipeline.stages.iter().zip(&pipeline.stage_channels) {
+            let Some(channel) = self.channels.get(index) else {
+                continue;
+            };
+            match stage.kind {
+                StageKind::Override => {
+                    if let Some((_, overriding)) = channel.overriding {
+                        value = overriding;
+                        break;
+                    }
+                }
+                // Flat stages saturate like the property would
+                StageKind::Add => value = (value + channel.sum).clamp(min, max),
+                StageKind::Sub => value = (value - channel.sum).clamp(min, max),
+                StageKind::Increase => value *= 1.0 + channel.sum,
+                StageKind::Multiply => value *= channel.product,
+                // Division by zero is ignored
+                StageKind::Divide if channel.product != 0.0 => value /= channel.product,
+                StageKind::Divide => {}
+                StageKind::Min => value = value.min(channel.min),
+                StageKind::Max => value = value.max(channel.max),
+            }
+        }
 
-        // Combine increased values (they stack additively)
-        let increase = self.increase + other.increase;
+        T::Property::from_f64(value.clamp(min, max)).unwrap()
+    }
 
-        // Combine more values (they stack multiplicatively)
-        let multiplier = self.multiplier * other.multiplier;
+    /// The accumulated modifiers of a channel of the pipeline.
+    pub fn channel(&self, pipeline: &AggregationPipeline, name: &str) -> Option<&ChannelValue> {
+        self.channels.get(pipeline.channel_index(name)?)
+    }
 
-        AttributeCalculator::<T> {
-            set,
-            additive,
-            subtractive,
-            increase,
-            multiplier,
-        }
+    pub fn combine(mut self, other: AttributeCalculator<T>) -> AttributeCalculator<T> {
+        self.combine_in_place(&other);
+        self
     }
 
     /// Combines another AttributeCalculator into this one in-place.
-    /// - set: Uses this calculator's set value if present, otherwise uses other's
-    /// - additive: Adds other's additive value to this one
-    /// - increased: Adds other's increased value to this one
-    /// - more: Multiplies this calculator's more value by other's
+    /// Matching channels are combined, and overrides are resolved by priority then value.
     pub fn combine_in_place(&mut self, other: &AttributeCalculator<T>) {
-        self.set = self.set.or(other.set);
-        self.additive += other.additive;
-        self.subtractive += other.subtractive;
-        self.increase += other.increase;
-        self.multiplier *= other.multiplier;
+        if self.channels.len() < other.channels.len() {
+            self.channels.resize(other.channels.len(), ChannelValue::default());
+        }
+        for (value, other_value) in self.channels.iter_mut().zip(&other.channels) {
+            value.combine(other_value);
+        }
     }
 
+    /// Converts a modifier into a calculator for the pipeline.
+    /// Returns `None` if the pipeline has no stage for the modifier's channel.
     pub fn convert(
         modifier: &AttributeModifier<T>,
-        //context: &dyn ReadContext,
-    ) -> Result<Self, ExpressionError> {
-        let value = modifier.value; //.eval_dyn(context)?;
-
-        let calculator = match modifier.operation {
-            ModOp::Set => Self {
-                set: Some(value),
-                ..default()
-            },
-            ModOp::Add => Self {
-                additive: value,
-                ..default()
-            },
-            ModOp::Sub => Self {
-                subtractive: value,
-                ..default()
-            },
-            ModOp::Increase => Self {
-                increase: value.as_(),
-                ..default()
-            },
-            ModOp::Mul => Self {
-                multiplier: value.as_(),
-                ..default()
-            },
-        };
-        Ok(calculator)
+        pipeline: &AggregationPipeline,
+    ) -> Option<Self> {
+        let index = pipeline.channel_index(modifier.channel_name())?;
+
+        // Missing channels hold the default value, which leaves their stage unchanged
+        let mut channels = vec![ChannelValue::default(); index + 1];
+        channels[index] =
+            ChannelValue::from_value(modifier.magnitude(), modifier.operation.priority());
+
+        Some(Self {
+            channels,
+            phantom_data: Default::default(),
+        })
     }
 }
 
 impl<T: Attribute> Default for AttributeCalculator<T> {
     fn default() -> Self {
         Self {
-            set: None,
-            additive: T::Property::zero(),
-            subtractive: T::Property::zero(),
-            increase: 0.0,
-            multiplier: 1.0,
+            channels: vec![],
+            phantom_data: Default::default(),
+        }
+    }
+}
+
+/// How a stage of an [`AggregationPipeline`] folds its channel into the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StageKind {
+    /// Replaces the value and skips the remaining stages.
+    Override,
+    /// Adds the sum of the channel.
+    Add,
+    /// Subtracts the sum of the channel.
+    Sub,
+    /// Multiplies by one plus the sum of the channel.
+    Increase,
+    /// Multiplies by the product of the channel.
+    Multiply,
+    /// Divides by the product of the channel.
+    Divide,
+    /// Caps the value to the smallest value of the channel.
+    Min,
+    /// Floors the value to the largest value of the channel.
+    Max,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregationStage {
+    pub channel: SmolStr,
+    pub kind: StageKind,
+}
+
+/// The ordered stages that compute the current value of an attribute from its base value.
+///
+/// Modifiers target a channel by name. The default pipeline has one channel per [`ModOp`],
+/// so custom pipelines should keep those channels for [`ModOp`] modifiers to apply.
+/// Modifiers of channels missing from the pipeline are ignored with a warning.
+///
+/// # Example
+/// ```
+/// # use vitality::modifier::{AggregationPipeline, StageKind};
+/// // Flat bonuses applied after the multipliers
+/// let pipeline = AggregationPipeline::default().stage("final_flat", StageKind::Add);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "PipelineStages", into = "PipelineStages")]
+pub struct AggregationPipeline {
+    stages: Vec<AggregationStage>,
+    /// The distinct channel names, interned as the stages are added.
+    channels: Vec<SmolStr>,
+    /// The channel index of each stage.
+    stage_channels: Vec<usize>,
+}
+
+impl AggregationPipeline {
+    /// A pipeline without stages. The current value is the base value.
+    pub fn empty() -> Self {
+        Self {
+            stages: vec![],
+            channels: vec![],
+            stage_channels: vec![],
         }
     }
+
+    /// Appends a stage to the pipeline.
+    pub fn stage(mut self, channel: impl Into<SmolStr>, kind: StageKind) -> Self {
+        let channel = channel.into();
+        let index = match self.channel_index(&channel) {
+            Some(index) => index,
+            None => {
+                self.channels.push(channel.clone());
+                self.channels.len() - 1
+            }
+        };
+        self.stage_channels.push(index);
+        self.stages.push(AggregationStage { channel, kind });
+        self
+    }
+
+    pub fn stages(&self) -> &[AggregationStage] {
+        &self.stages
+    }
+
+    /// The index of a channel, if a stage of the pipeline reads it.
+    pub fn channel_index(&self, name: &str) -> Option<usize> {
+        self.channels.iter().position(|channel| channel == name)
+    }
+}
+
+// The serialized form of a pipeline, whose channels are interned again when deserialized
+#[derive(Serialize, Deserialize)]
+struct PipelineStages {
+    stages: Vec<AggregationStage>,
+}
+
+impl From<PipelineStages> for AggregationPipeline {
+    fn from(value: PipelineStages) -> Self {
+        value
+            .stages
+            .into_iter()
+            .fold(Self::empty(), |pipeline, stage| pipeline.stage(stage.channel, stage.kind))
+    }
+}
+
+impl From<AggregationPipeline> for PipelineStages {
+    fn from(value: AggregationPipeline) -> Self {
+        Self {
+            stages: value.stages,
+        }
+    }
+}
+
+impl Default for AggregationPipeline {
+    /// Set → Add → Sub → Increase → Mul → Div → PostAdd → Max → Min
+    fn default() -> Self {
+        Self::empty()
+            .stage(ModOp::Set.channel(), StageKind::Override)
+            .stage(ModOp::Add.channel(), StageKind::Add)
+            .stage(ModOp::Sub.channel(), StageKind::Sub)
+            .stage(ModOp::Increase.channel(), StageKind::Increase)
+            .stage(ModOp::Mul.channel(), StageKind::Multiply)
+            .stage(ModOp::Div.channel(), StageKind::Divide)
+            .stage(ModOp::PostAdd.channel(), StageKind::Add)
+            .stage(ModOp::Max.channel(), StageKind::Max)
+            .stage(ModOp::Min.channel(), StageKind::Min)
+    }
+}
+
+/// A modifier enum whose variants are sorted into categories, usually generated with
+/// [`attribute_calculator`](crate::attribute_calculator).
+///
+/// The pipeline becomes the aggregation strategy of attributes initialized with
+/// [`init_attribute_with_calculator`](crate::init_attribute_with_calculator), and variants are
+/// applied with [`EffectBuilder::modify_with`](crate::effect::EffectBuilder::modify_with).
+pub trait ModifierCategories: Send + Sync + 'static {
+    /// The pipeline folding the categories into the current value.
+    fn pipeline() -> AggregationPipeline;
+
+    /// The operation of the variant's category. Its channel receives the magnitude.
+    fn operation(&self) -> ModOp;
+
+    /// The value accumulated in the channel.
+    fn magnitude(&self) -> f64;
 }
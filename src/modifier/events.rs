@@ -1,11 +1,12 @@
 use bevy::ecs::resource::IsResource;
 use crate::context::EffectExprContext;
+use crate::effect::EffectParams;
 use crate::inspector::pretty_type_name;
 use crate::math::AbsDiff;
-use crate::modifier::calculator::AttributeCalculator;
+use crate::modifier::calculator::{AggregationPipeline, AttributeCalculator};
 use crate::prelude::*;
 use crate::systems::MarkNodeDirty;
-use crate::{AttributesMut};
+use crate::{AppAttributeBindings, AttributesMut, BaseValueChanged};
 use bevy::prelude::*;
 use bevy::reflect::TypeRegistryArc;
 
@@ -15,6 +16,8 @@ pub struct ApplyAttributeModifierMessage<T: Attribute> {
     pub target_entity: Entity,
     pub effect_entity: Entity,
     pub modifier: AttributeModifier<T>,
+    /// The parameters of an application without an effect entity.
+    pub params: Option<EffectParams>,
 }
 
 pub fn apply_modifier_events<T: Attribute>(
@@ -22,20 +25,25 @@ pub fn apply_modifier_events<T: Attribute>(
     mut attributes: Query<AttributesMut, Without<IsResource>>,
     mut commands: Commands,
     type_registry: Res<AppTypeRegistry>,
+    type_bindings: Res<AppAttributeBindings>,
 ) {
+    let pipeline = type_bindings.internal.read().unwrap().pipeline::<T>();
+
     for ev in event_reader.read() {
-        let has_changed = apply_modifier(
+        let base_value_changed = apply_modifier(
             &ev,
             &mut attributes,
             type_registry.0.clone(),
+            &pipeline,
         )
-        .unwrap_or(false);
+        .unwrap_or(None);
 
-        if has_changed {
+        if let Some(base_value_changed) = base_value_changed {
             commands.trigger(MarkNodeDirty::<T> {
                 entity: ev.target_entity,
                 phantom_data: Default::default(),
             });
+            commands.trigger(base_value_changed);
         }
     }
 }
@@ -44,9 +52,14 @@ pub fn apply_modifier<T: Attribute>(
     trigger: &ApplyAttributeModifierMessage<T>,
     attributes: &mut Query<AttributesMut, Without<IsResource>>,
     type_registry: TypeRegistryArc,
-) -> Result<bool, BevyError> {
-    let query = [trigger.source_entity, trigger.target_entity];
-    let [source, target] = attributes.get_many(query)?;
+    pipeline: &AggregationPipeline,
+) -> Result<Option<BaseValueChanged<T>>, BevyError> {
+    let query = [
+        trigger.source_entity,
+        trigger.target_entity,
+        trigger.effect_entity,
+    ];
+    let [source, target, effect] = attributes.get_many(query)?;
 
     let base_value = target
         .get::<T>()
@@ -61,29 +74,43 @@ pub fn apply_modifier<T: Attribute>(
     let context = EffectExprContext {
         source_actor: &source,
         target_actor: &target, // Needs to be fixed.
-        effect_holder: &source,
+        effect_holder: &effect,
+        params: trigger.params.as_ref(),
         type_registry: type_registry.clone(),
     };
     let mut modifier = trigger.modifier.clone();
     modifier.update_value(&context);
+    let modifier = modifier.scaled(&context);
 
     // Apply the modifier
-    let Ok(calculator) = AttributeCalculator::<T>::convert(&modifier) else {
-        return Err(format!("Could not convert modifier {} to calculator.", modifier,).into());
+    let Some(calculator) = AttributeCalculator::<T>::convert(&modifier, pipeline) else {
+        warn!(
+            "Ignoring modifier {}: the pipeline has no channel {:?}.",
+            modifier,
+            modifier.channel_name()
+        );
+        return Ok(None);
     };
-    let new_base_value = calculator.eval(base_value);
+    let new_base_value = calculator.eval(base_value, pipeline);
 
-    let has_changed = new_base_value.are_different(base_value);
-    if has_changed {
-        let mut attributes_mut = attributes.get_mut(trigger.target_entity)?;
+    if !new_base_value.are_different(base_value) {
+        return Ok(None);
+    }
 
-        let mut attribute = attributes_mut.get_mut::<T>().ok_or(format!(
-            "Could not find attribute {} on entity {}.",
-            pretty_type_name::<T>(),
-            trigger.target_entity
-        ))?;
+    let mut attributes_mut = attributes.get_mut(trigger.target_entity)?;
+    let mut attribute = attributes_mut.get_mut::<T>().ok_or(format!(
+        "Could not find attribute {} on entity {}.",
+        pretty_type_name::<T>(),
+        trigger.target_entity
+    ))?;
+    attribute.set_base_value(new_base_value);
 
-        attribute.set_base_value(new_base_value);
-    }
-    Ok(has_changed)
+    Ok(Some(BaseValueChanged {
+        phantom_data: Default::default(),
+        old: base_value,
+        new: new_base_value,
+        entity: trigger.target_entity,
+        source: trigger.source_entity,
+        effect: Some(trigger.effect_entity),
+    }))
 }
@@ -1,3 +1,4 @@
+mod breakdown;
 mod calculator;
 mod events;
 pub mod modifier;
@@ -5,7 +6,13 @@ pub mod modifier;
 use crate::inspector::pretty_type_name;
 use crate::prelude::*;
 use bevy::prelude::{Component, Entity, EntityCommands, Reflect, reflect_trait};
-pub use calculator::{AttributeCalculator, AttributeCalculatorCached, ModOp};
+pub use breakdown::{
+    AttributeBreakdown, AttributeExplainer, EffectBreakdown, ModifierBreakdown, SkipReason,
+};
+pub use calculator::{
+    AggregationPipeline, AggregationStage, AttributeCalculator, AttributeCalculatorCached,
+    ChannelValue, ModOp, ModifierCategories, StageKind,
+};
 pub use events::{ApplyAttributeModifierMessage, apply_modifier_events};
 use express_it::context::Path;
 pub use modifier::AttributeModifier;
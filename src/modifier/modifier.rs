@@ -1,5 +1,5 @@
 use crate::context::{split_path, EffectExprContextMut, EffectExprContext, EffectExprSchema};
-use crate::effect::{EffectSource, EffectTarget};
+use crate::effect::{EffectParams, EffectSource, EffectTarget, StackScaling};
 use crate::inspector::pretty_type_name;
 use crate::math::AbsDiff;
 use crate::modifier::calculator::ModOp;
@@ -9,10 +9,11 @@ use crate::modifier::{
 use crate::modifier::{EffectSubject, ReflectAccessModifier};
 use crate::prelude::*;
 use crate::systems::MarkNodeDirty;
-use crate::{AttributeBindings, AttributesRef};
+use crate::{AttributeBindings, AttributesRef, BaseValueChanged};
 use bevy::prelude::*;
 use bevy::reflect::TypeRegistryArc;
 use express_it::expr::{Expr, ExprNode, SelectExprNodeImpl};
+use num_traits::{AsPrimitive, FromPrimitive};
 use std::collections::HashSet;
 use std::fmt::Display;
 use smol_str::SmolStr;
@@ -38,11 +39,13 @@ pub trait Modifier: Send + Sync {
 
     /// Sends a message to apply the message at the end of the schedule together with all other mods.
     /// Good for damage, heals, etc.
+    /// The parameters are those of an application without an effect entity.
     fn apply_delayed(
         &self,
         source: Entity,
         target: Entity,
         effect: Entity,
+        params: Option<&EffectParams>,
         commands: &mut Commands,
     );
 }
@@ -57,6 +60,14 @@ pub struct AttributeModifier<T: Attribute> {
     pub value: T::Property,
     pub who: EffectSubject,
     pub operation: ModOp,
+    #[reflect(ignore)]
+    pub scaling: StackScaling,
+    /// The channel of the aggregation pipeline. Defaults to the channel of the operation.
+    #[reflect(ignore)]
+    pub channel: Option<SmolStr>,
+    /// The value accumulated in the channel when the property cannot hold it, such as a
+    /// fractional multiplier of an integer attribute. Takes precedence over `value`.
+    pub magnitude: Option<f64>,
 }
 
 impl<T> AttributeModifier<T>
@@ -74,13 +85,39 @@ where
             value,
             who,
             operation: modifier,
+            scaling: StackScaling::None,
+            channel: None,
+            magnitude: None,
         }
     }
 
+    /// The value the modifier accumulates in its channel.
+    pub fn magnitude(&self) -> f64 {
+        self.magnitude.unwrap_or_else(|| self.value.as_())
+    }
+
+    /// The channel of the aggregation pipeline receiving the modifier.
+    pub fn channel_name(&self) -> &str {
+        self.channel.as_deref().unwrap_or(self.operation.channel())
+    }
+
     pub fn update_value(&mut self, ctx: &EffectExprContext) {
         let new_val = self.expr.inner.eval(ctx).unwrap_or(T::Property::default());
         self.value = new_val;
     }
+
+    /// Returns a copy of the modifier with its value scaled by the stacks and intensity of the effect.
+    /// The context's effect holder must be the effect entity owning the modifier.
+    pub fn scaled(&self, ctx: &EffectExprContext) -> Self {
+        let mut modifier = self.clone();
+        if self.scaling.is_scaled() {
+            let factor = self.scaling.factor(ctx);
+            let value: f64 = self.value.as_();
+            modifier.value = T::Property::from_f64(value * factor).unwrap_or(self.value);
+            modifier.magnitude = self.magnitude.map(|magnitude| magnitude * factor);
+        }
+        modifier
+    }
 }
 
 impl<T> Modifier for AttributeModifier<T>
@@ -95,6 +132,16 @@ where
         type_bindings: &AttributeBindings,
         commands: &mut EntityCommands,
     ) {
+        // Modifiers of channels missing from the pipeline would never apply
+        if type_bindings.pipeline::<T>().channel_index(self.channel_name()).is_none() {
+            warn!(
+                "Ignoring modifier of {}: the pipeline has no channel {:?}.",
+                pretty_type_name::<T>(),
+                self.channel_name()
+            );
+            return;
+        }
+
         let value = match self.expr.eval(ctx) {
             Ok(value) => value,
             Err(err) => {
@@ -108,6 +155,9 @@ where
             value,
             who: self.who,
             operation: self.operation,
+            scaling: self.scaling.clone(),
+            channel: self.channel.clone(),
+            magnitude: self.magnitude,
         };
         let display = modifier.to_string();
 
@@ -135,10 +185,17 @@ where
             source_actor: &context.source_actor.as_readonly(),
             target_actor: &context.source_actor.as_readonly(), // Needs to be fixed.
             effect_holder: &context.owner.as_readonly(),
+            params: None,
             type_registry: type_registry.clone(),
         };
 
-        let Ok(calc) = AttributeCalculator::<T>::convert(self) else {
+        let pipeline = context.type_bindings.internal.read().unwrap().pipeline::<T>();
+        let Some(calc) = AttributeCalculator::<T>::convert(self, &pipeline) else {
+            warn!(
+                "Ignoring modifier {}: the pipeline has no channel {:?}.",
+                self,
+                self.channel_name()
+            );
             return false;
         };
         let Some(attribute) = immutable_context
@@ -147,20 +204,39 @@ where
         else {
             return false;
         };
-        let new_val = calc.eval(attribute.base_value());
+        let new_val = calc.eval(attribute.base_value(), &pipeline);
+
+        let entity = context.entity(self.who);
+        let source = context.source_actor.id();
+        let effect = Some(context.owner.id());
 
         let attributes_mut = context.attribute_mut(self.who);
         // Apply the modifier
-        if let Some(mut attribute) = attributes_mut.get_mut::<T>() {
+        let old_val = if let Some(mut attribute) = attributes_mut.get_mut::<T>() {
             // Ensure that the modifier meaningfully changed the value before we trigger the event.
-            let has_changed = new_val.are_different(attribute.current_value());
-            if has_changed {
-                attribute.set_base_value(new_val);
+            let old_val = attribute.base_value();
+            if !new_val.are_different(attribute.current_value()) {
+                return false;
             }
-            has_changed
+            attribute.set_base_value(new_val);
+            old_val
         } else {
             panic!("Could not find attribute {}", pretty_type_name::<T>());
-        }
+        };
+
+        context
+            .base_value_changes
+            .push(Box::new(move |commands: &mut Commands| {
+                commands.trigger(BaseValueChanged::<T> {
+                    phantom_data: Default::default(),
+                    old: old_val,
+                    new: new_val,
+                    entity,
+                    source,
+                    effect,
+                });
+            }));
+        true
     }
 
     fn apply_delayed(
@@ -168,6 +244,7 @@ where
         source: Entity,
         target: Entity,
         effect: Entity,
+        params: Option<&EffectParams>,
         commands: &mut Commands,
     ) {
         commands.write_message(ApplyAttributeModifierMessage::<T> {
@@ -175,6 +252,7 @@ where
             target_entity: target,
             effect_entity: effect,
             modifier: self.clone(),
+            params: params.cloned(),
         });
     }
 }
@@ -184,14 +262,16 @@ where
     T: Attribute,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Mod<{}>({}{}) {}",
-            pretty_type_name::<T>(),
-            self.operation,
-            self.value,
-            self.who,
-        )
+        write!(f, "Mod<{}>({}", pretty_type_name::<T>(), self.operation)?;
+        match self.magnitude {
+            Some(magnitude) => write!(f, "{}", magnitude)?,
+            None => write!(f, "{}", self.value)?,
+        }
+        write!(f, ") {}", self.who)?;
+        if let Some(channel) = &self.channel {
+            write!(f, " [{}]", channel)?;
+        }
+        Ok(())
     }
 }
 
@@ -205,7 +285,7 @@ pub struct RecalculateExpression {
 pub fn update_modifier_when_dependencies_changed<T: Attribute>(
     trigger: On<RecalculateExpression>,
     mut modifiers: Query<(&mut AttributeModifier<T>, &ModifierOf)>,
-    effects: Query<(&EffectSource, &EffectTarget)>,
+    effects: Query<(&EffectSource, &EffectTarget, Option<&EffectParams>)>,
     actors: Query<AttributesRef, Without<AttributeModifier<T>>>,
     type_registry: Res<AppTypeRegistry>,
     mut commands: Commands,
@@ -213,13 +293,14 @@ pub fn update_modifier_when_dependencies_changed<T: Attribute>(
     let Ok((mut modifier, effect_id)) = modifiers.get_mut(trigger.modifier_entity) else {
         return;
     };
-    let (source, target) = effects.get(effect_id.0).unwrap();
+    let (source, target, params) = effects.get(effect_id.0).unwrap();
     let [source_ref, target_ref] = actors.get_many([source.0, target.0]).unwrap();
 
     let context = EffectExprContext {
         target_actor: &target_ref,
         source_actor: &source_ref,
         effect_holder: &source_ref,
+        params,
         type_registry: type_registry.0.clone(),
     };
 
@@ -0,0 +1,518 @@
+use crate::AttributeBindings;
+use crate::attributes::Value;
+use crate::context::{
+    AbilityExprContext, AbilityExprSchema, ActorExprContext, ActorExprSchema, EffectExprContext,
+    EffectExprSchema, split_path,
+};
+use crate::effect::PARAMS_SEGMENT;
+use crate::modifier::{AbilitySubject, ActorSubject, EffectSubject};
+use express_it::context::{Path, ReadContext};
+use express_it::expr::{Expr, ExprNode, ExprSchema, ExpressionError};
+use std::any::Any;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Parses text expressions such as `source.Strength.current_value * 2 + target.Armour.base_value`.
+///
+/// Paths are `subject.Attribute[.current_value|.base_value]`. The subject must be an alias accepted
+/// by the schema and the attribute must be registered with [`init_attribute`](crate::init_attribute).
+/// The field defaults to `current_value`. Effect parameters are read with `effect.params.name`.
+///
+/// # Example
+/// ```
+/// # use vitality::prelude::*;
+/// # use vitality::AppAttributeBindings;
+/// # use vitality::parser::parse_expr;
+/// # let bindings = AppAttributeBindings::default();
+/// let bindings = bindings.internal.read().unwrap();
+/// let expr = parse_expr::<f32, EffectExprSchema>("2 * (3 + 4)", &bindings).unwrap();
+/// ```
+pub fn parse_expr<P, S>(source: &str, bindings: &AttributeBindings) -> Result<Expr<P, S>, ParseError>
+where
+    P: Value,
+    S: ParseSchema,
+    ParsedExpr<P, S>: ExprNode<P, S>,
+{
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+        source_len: source.len(),
+        bindings,
+        validate_subject: S::validate_subject,
+        validate_param: S::validate_param,
+    };
+
+    let root = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(ParseError::new("Unexpected token", token.span.clone()));
+    }
+
+    Ok(Expr::new(Arc::new(ParsedExpr::<P, S> {
+        source: source.into(),
+        root,
+        phantom_data: Default::default(),
+    })))
+}
+
+/// A schema whose paths can be parsed from text.
+pub trait ParseSchema: ExprSchema + Send + Sync + 'static {
+    fn validate_subject(path: &Path) -> Result<(), String>;
+
+    /// Checks a path reading a parameter of the application, as in `effect.params.name`.
+    /// Only schemas with parameters accept them.
+    fn validate_param(_path: &Path) -> Result<(), String> {
+        Err("Parameters are only available in effect expressions".into())
+    }
+}
+
+impl ParseSchema for EffectExprSchema {
+    fn validate_subject(path: &Path) -> Result<(), String> {
+        EffectSubject::try_from(path).map(|_| ())
+    }
+
+    fn validate_param(path: &Path) -> Result<(), String> {
+        match EffectSubject::try_from(path) {
+            Ok(EffectSubject::Effect) => Ok(()),
+            _ => Err("Parameters are read from the effect, as in 'effect.params.name'".into()),
+        }
+    }
+}
+
+impl ParseSchema for AbilityExprSchema {
+    fn validate_subject(path: &Path) -> Result<(), String> {
+        AbilitySubject::try_from(path).map(|_| ())
+    }
+}
+
+impl ParseSchema for ActorExprSchema {
+    fn validate_subject(path: &Path) -> Result<(), String> {
+        ActorSubject::try_from(path).map(|_| ())
+    }
+}
+
+/// An error with the byte range of the offending text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Range<usize>,
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>, span: Range<usize>) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at {}..{}",
+            self.message, self.span.start, self.span.end
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// An expression parsed from text. Evaluates in `f64` and converts the result to `P`.
+pub struct ParsedExpr<P, S> {
+    source: Arc<str>,
+    root: Node,
+    phantom_data: PhantomData<fn() -> (P, S)>,
+}
+
+impl<P, S> ParsedExpr<P, S> {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+impl<P: Value, S> ParsedExpr<P, S> {
+    fn eval_read(&self, ctx: &dyn ReadContext) -> Result<P, ExpressionError> {
+        let value = self.root.eval(ctx)?;
+        P::from_f64(value).ok_or_else(|| {
+            ExpressionError::FailedReflect(
+                format!("Cannot convert {} from '{}'", value, self.source).into(),
+            )
+        })
+    }
+}
+
+impl<P, S> Debug for ParsedExpr<P, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Parsed({})", self.source)
+    }
+}
+
+impl<P, S> Display for ParsedExpr<P, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+macro_rules! impl_parsed_expr_node {
+    ( $Schema:ty, $Context:ident ) => {
+        impl<P: Value> ExprNode<P, $Schema> for ParsedExpr<P, $Schema> {
+            fn eval(&self, ctx: &$Context) -> Result<P, ExpressionError> {
+                self.eval_read(ctx)
+            }
+
+            fn eval_dyn(&self, ctx: &dyn ReadContext) -> Result<P, ExpressionError> {
+                self.eval_read(ctx)
+            }
+
+            fn get_dependencies(&self, deps: &mut HashSet<Path>) {
+                self.root.get_dependencies(deps);
+            }
+        }
+    };
+}
+
+impl_parsed_expr_node!(EffectExprSchema, EffectExprContext);
+impl_parsed_expr_node!(AbilityExprSchema, AbilityExprContext);
+impl_parsed_expr_node!(ActorExprSchema, ActorExprContext);
+
+#[derive(Clone, Copy, Debug)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+enum Node {
+    Lit(f64),
+    Attribute {
+        path: Path,
+        // Converts the attribute's property to f64
+        read: fn(&dyn Any) -> Option<f64>,
+    },
+    /// A parameter of the effect application, see [`EffectParams`](crate::effect::EffectParams).
+    Param(Path),
+    Neg(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn eval(&self, ctx: &dyn ReadContext) -> Result<f64, ExpressionError> {
+        match self {
+            Node::Lit(value) => Ok(*value),
+            Node::Attribute { path, read } => {
+                let any = ctx.get_any(path)?;
+                read(any).ok_or_else(|| ExpressionError::InvalidPath(path.0.clone()))
+            }
+            Node::Param(path) => ctx
+                .get_any(path)?
+                .downcast_ref::<f64>()
+                .copied()
+                .ok_or_else(|| ExpressionError::InvalidPath(path.0.clone())),
+            Node::Neg(node) => Ok(-node.eval(ctx)?),
+            Node::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(ctx)?, rhs.eval(ctx)?);
+                Ok(match op {
+                    BinaryOp::Add => lhs + rhs,
+                    BinaryOp::Sub => lhs - rhs,
+                    BinaryOp::Mul => lhs * rhs,
+                    BinaryOp::Div => lhs / rhs,
+                })
+            }
+        }
+    }
+
+    fn get_dependencies(&self, deps: &mut HashSet<Path>) {
+        match self {
+            // Parameters never change once the effect is applied
+            Node::Lit(_) | Node::Param(_) => {}
+            Node::Attribute { path, .. } => {
+                deps.insert(path.clone());
+            }
+            Node::Neg(node) => node.get_dependencies(deps),
+            Node::Binary(_, lhs, rhs) => {
+                lhs.get_dependencies(deps);
+                rhs.get_dependencies(deps);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Number(f64),
+    Path(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    LParen,
+    RParen,
+}
+
+struct Token {
+    kind: TokenKind,
+    span: Range<usize>,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        let single = match c {
+            '+' => Some(TokenKind::Plus),
+            '-' => Some(TokenKind::Minus),
+            '*' => Some(TokenKind::Star),
+            '/' => Some(TokenKind::Slash),
+            '(' => Some(TokenKind::LParen),
+            ')' => Some(TokenKind::RParen),
+            _ => None,
+        };
+        if let Some(kind) = single {
+            chars.next();
+            tokens.push(Token {
+                kind,
+                span: start..start + 1,
+            });
+            continue;
+        }
+
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let is_number = c.is_ascii_digit() || c == '.';
+        let is_path = c.is_alphabetic() || c == '_';
+        if !is_number && !is_path {
+            return Err(ParseError::new(
+                format!("Unexpected character '{}'", c),
+                start..start + c.len_utf8(),
+            ));
+        }
+
+        let mut end = start;
+        while let Some(&(index, c)) = chars.peek() {
+            let continues = if is_number {
+                c.is_ascii_digit() || c == '.'
+            } else {
+                c.is_alphanumeric() || c == '_' || c == '.'
+            };
+            if !continues {
+                break;
+            }
+            end = index + c.len_utf8();
+            chars.next();
+        }
+
+        let text = &source[start..end];
+        let kind = if is_number {
+            let value = text
+                .parse::<f64>()
+                .map_err(|_| ParseError::new(format!("Invalid number '{}'", text), start..end))?;
+            TokenKind::Number(value)
+        } else {
+            TokenKind::Path(text.to_string())
+        };
+        tokens.push(Token {
+            kind,
+            span: start..end,
+        });
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    source_len: usize,
+    bindings: &'a AttributeBindings,
+    validate_subject: fn(&Path) -> Result<(), String>,
+    validate_param: fn(&Path) -> Result<(), String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn end_span(&self) -> Range<usize> {
+        self.source_len..self.source_len
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Node, ParseError> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match self.peek().map(|token| &token.kind) {
+                Some(TokenKind::Plus) => BinaryOp::Add,
+                Some(TokenKind::Minus) => BinaryOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.next();
+            let rhs = self.term()?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    // term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Node, ParseError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek().map(|token| &token.kind) {
+                Some(TokenKind::Star) => BinaryOp::Mul,
+                Some(TokenKind::Slash) => BinaryOp::Div,
+                _ => return Ok(lhs),
+            };
+            self.next();
+            let rhs = self.unary()?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    // unary := '-' unary | primary
+    fn unary(&mut self) -> Result<Node, ParseError> {
+        if matches!(self.peek().map(|token| &token.kind), Some(TokenKind::Minus)) {
+            self.next();
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    // primary := number | path | '(' expr ')'
+    fn primary(&mut self) -> Result<Node, ParseError> {
+        let end_span = self.end_span();
+        let Some(token) = self.next() else {
+            return Err(ParseError::new("Unexpected end of expression", end_span));
+        };
+        let span = token.span.clone();
+
+        match token.kind.clone() {
+            TokenKind::Number(value) => Ok(Node::Lit(value)),
+            TokenKind::Path(text) => self.path(&text, span),
+            TokenKind::LParen => {
+                let node = self.expr()?;
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::RParen,
+                        ..
+                    }) => Ok(node),
+                    Some(token) => Err(ParseError::new("Expected ')'", token.span.clone())),
+                    None => Err(ParseError::new("Expected ')'", end_span)),
+                }
+            }
+            _ => Err(ParseError::new("Expected a number, a path or '('", span)),
+        }
+    }
+
+    fn path(&self, text: &str, span: Range<usize>) -> Result<Node, ParseError> {
+        let (subject, attribute, field) = split_path(text)
+            .map_err(|_| ParseError::new("Expected a path like 'subject.Attribute'", span.clone()))?;
+
+        let subject_span = span.start..span.start + subject.len();
+        let attribute_start = subject_span.end + 1;
+        let attribute_span = attribute_start..attribute_start + attribute.len();
+
+        if attribute == PARAMS_SEGMENT {
+            let Some(name) = field else {
+                return Err(ParseError::new(
+                    "Expected a parameter name",
+                    span.end..span.end,
+                ));
+            };
+            let path = Path::new(format!("{}.{}.{}", subject, attribute, name));
+            (self.validate_param)(&path).map_err(|err| ParseError::new(err, subject_span))?;
+            return Ok(Node::Param(path));
+        }
+
+        let field = field.unwrap_or("current_value");
+        if field != "current_value" && field != "base_value" {
+            let field_start = attribute_span.end + 1;
+            return Err(ParseError::new(
+                format!("Unknown field '{}', expected current_value or base_value", field),
+                field_start..span.end,
+            ));
+        }
+
+        let path = Path::new(format!("{}.{}.{}", subject, attribute, field));
+        (self.validate_subject)(&path).map_err(|err| ParseError::new(err, subject_span))?;
+
+        let Some(read) = self.bindings.as_f64.get(attribute) else {
+            return Err(ParseError::new(
+                format!("Unknown attribute '{}'", attribute),
+                attribute_span,
+            ));
+        };
+
+        Ok(Node::Attribute { path, read: *read })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    attribute!(Strength, u32);
+    attribute!(Armour, f32);
+
+    fn bindings() -> AttributeBindings {
+        let mut bindings = AttributeBindings::default();
+        bindings.add::<Strength>();
+        bindings.add::<Armour>();
+        bindings
+    }
+
+    #[test]
+    fn test_parse_expr_validates_paths() {
+        let bindings = bindings();
+
+        let expr = parse_expr::<f32, EffectExprSchema>(
+            "source.Strength.current_value * 2 + target.Armour.base_value",
+            &bindings,
+        )
+        .unwrap();
+        let mut deps = HashSet::default();
+        expr.inner.get_dependencies(&mut deps);
+        assert_eq!(deps.len(), 2);
+
+        let err = parse_expr::<f32, EffectExprSchema>("caster.Strength", &bindings).unwrap_err();
+        assert_eq!(err.span, 0..6);
+        assert!(parse_expr::<f32, AbilityExprSchema>("caster.Strength", &bindings).is_ok());
+
+        let err = parse_expr::<f32, EffectExprSchema>("1 + src.Mana", &bindings).unwrap_err();
+        assert_eq!(err.span, 8..12);
+
+        let err = parse_expr::<f32, EffectExprSchema>("(1 + 2", &bindings).unwrap_err();
+        assert_eq!(err.span, 6..6);
+
+        let expr =
+            parse_expr::<f32, EffectExprSchema>("effect.params.damage * 2", &bindings).unwrap();
+        let mut deps = HashSet::default();
+        expr.inner.get_dependencies(&mut deps);
+        assert!(deps.is_empty());
+
+        let err =
+            parse_expr::<f32, EffectExprSchema>("target.params.damage", &bindings).unwrap_err();
+        assert_eq!(err.span, 0..6);
+
+        let err =
+            parse_expr::<f32, AbilityExprSchema>("effect.params.damage", &bindings).unwrap_err();
+        assert_eq!(err.span, 0..6);
+        assert!(parse_expr::<f32, ActorExprSchema>("effect.params.damage", &bindings).is_err());
+    }
+}
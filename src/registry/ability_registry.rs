@@ -2,9 +2,10 @@ use crate::assets::AbilityDef;
 use bevy::asset::Handle;
 use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
 
-#[derive(Clone, PartialEq, Eq, Hash, Reflect)]
+#[derive(Clone, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
 pub struct AbilityToken(SmolStr);
 
 impl AbilityToken {
@@ -41,9 +42,15 @@ impl AbilityRegistry {
         self.map.insert(token, handle);
     }
 
-    pub fn get(&self, token: &AbilityToken) -> &Handle<AbilityDef> {
+    pub fn get(&self, token: &AbilityToken) -> Option<&Handle<AbilityDef>> {
+        self.map.get(token)
+    }
+
+    /// Finds the token under which the definition was registered.
+    pub fn token(&self, id: impl Into<AssetId<AbilityDef>>) -> Option<&AbilityToken> {
+        let id = id.into();
         self.map
-            .get(token)
-            .expect(format!("{:?} not registered", token).as_str())
+            .iter()
+            .find_map(|(token, handle)| (handle.id() == id).then_some(token))
     }
 }
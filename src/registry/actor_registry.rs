@@ -1,9 +1,10 @@
 use std::collections::HashMap;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
 use crate::assets::ActorDef;
 
-#[derive(Default, Clone, PartialEq, Eq, Hash, Reflect)]
+#[derive(Default, Clone, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
 pub struct ActorToken(SmolStr);
 
 impl ActorToken {
@@ -45,4 +46,12 @@ impl ActorRegistry {
             .get(&token)
             .expect(format!("{:?} not registered", token).as_str())
     }
+
+    /// Finds the token under which the definition was registered.
+    pub fn token(&self, id: impl Into<AssetId<ActorDef>>) -> Option<&ActorToken> {
+        let id = id.into();
+        self.map
+            .iter()
+            .find_map(|(token, handle)| (handle.id() == id).then_some(token))
+    }
 }
@@ -2,9 +2,10 @@ use crate::assets::EffectDef;
 use bevy::asset::Handle;
 use bevy::platform::collections::HashMap;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
 
-#[derive(Clone, PartialEq, Eq, Hash, Reflect)]
+#[derive(Clone, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
 pub struct EffectToken(SmolStr);
 
 impl EffectToken {
@@ -44,4 +45,12 @@ impl EffectRegistry {
     pub fn get(&self, token: &EffectToken) -> Option<&Handle<EffectDef>> {
         self.map.get(token)
     }
+
+    /// Finds the token under which the definition was registered.
+    pub fn token(&self, id: impl Into<AssetId<EffectDef>>) -> Option<&EffectToken> {
+        let id = id.into();
+        self.map
+            .iter()
+            .find_map(|(token, handle)| (handle.id() == id).then_some(token))
+    }
 }
@@ -32,7 +32,10 @@ impl Registry<'_> {
     }
 
     pub fn ability(&self, name: &AbilityToken) -> Handle<AbilityDef> {
-        self.ability_registry.get(name).clone()
+        self.ability_registry
+            .get(name)
+            .expect("ability not found")
+            .clone()
     }
 
     pub fn actor(&self, name: &ActorToken) -> Handle<ActorDef> {
@@ -68,7 +71,10 @@ impl RegistryMut<'_> {
     }
 
     pub fn ability(&self, name: &AbilityToken) -> Handle<AbilityDef> {
-        self.ability_registry.get(name).clone()
+        self.ability_registry
+            .get(name)
+            .expect("Ability must exist.")
+            .clone()
     }
 
     pub fn add_actor(&mut self, name: ActorToken, actor: ActorDef) {
@@ -0,0 +1,417 @@
+use crate::ability::{
+    Ability, AbilityCharges, AbilityCooldown, AbilityOf, Charges, CooldownGroup, CooldownGroups,
+    GrantAbilityCommand, GrantedAbilities,
+};
+use crate::actors::{insert_actor_components, Actor};
+use crate::assets::{ActorDef, EffectDef};
+use crate::context::EffectExprContext;
+use crate::effect::global_effect::GlobalActor;
+use crate::effect::{
+    AppliedEffects, ApplyEffectEvent, Effect, EffectDuration, EffectInactive, EffectParams,
+    EffectSource, EffectTargeting, EffectTicker, Stacks,
+};
+use crate::prelude::*;
+use crate::registry::ability_registry::{AbilityRegistry, AbilityToken};
+use crate::registry::actor_registry::{ActorRegistry, ActorToken};
+use crate::registry::effect_registry::{EffectRegistry, EffectToken};
+use crate::{AppAttributeBindings, AttributesRef};
+use bevy::ecs::resource::IsResource;
+use bevy::ecs::system::RunSystemOnce;
+use bevy::ecs::world::CommandQueue;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// A serializable copy of an actor, its live effects and its granted abilities.
+///
+/// Definitions are saved through their registry tokens, so every effect and ability that should
+/// survive a save must be registered. Effects are restored as they were saved, without running
+/// their application again. Their source is kept while it exists, otherwise they are restored
+/// as self-applied. Global effects are skipped since they are re-applied to every spawned actor.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ActorSnapshot {
+    pub actor: ActorToken,
+    /// Base values by attribute name.
+    pub attributes: BTreeMap<String, f64>,
+    pub effects: Vec<EffectSnapshot>,
+    pub abilities: Vec<AbilitySnapshot>,
+    /// The cooldown groups of the actor by name.
+    #[serde(default)]
+    pub cooldown_groups: BTreeMap<String, TimerSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EffectSnapshot {
+    pub effect: EffectToken,
+    /// The bits of the entity that applied the effect, if it is not the actor.
+    #[serde(default)]
+    pub source: Option<u64>,
+    pub duration: Option<TimerSnapshot>,
+    pub ticker: Option<TimerSnapshot>,
+    /// How many times the periodic effect ticked.
+    #[serde(default)]
+    pub ticks: u32,
+    pub stacks: u32,
+    pub inactive: bool,
+    /// The parameters of the application, by name.
+    #[serde(default)]
+    pub params: BTreeMap<String, f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AbilitySnapshot {
+    pub ability: AbilityToken,
+    pub cooldown: Option<TimerSnapshot>,
+    #[serde(default)]
+    pub charges: Option<ChargesSnapshot>,
+}
+
+/// The charges left on an ability and the progress of the next one.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ChargesSnapshot {
+    pub charges: u32,
+    pub recharge: TimerSnapshot,
+}
+
+/// The duration and elapsed time of a timer, in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct TimerSnapshot {
+    pub duration: f32,
+    pub elapsed: f32,
+}
+
+impl TimerSnapshot {
+    pub fn capture(timer: &Timer) -> Self {
+        Self {
+            duration: timer.duration().as_secs_f32(),
+            elapsed: timer.elapsed_secs(),
+        }
+    }
+
+    /// Fails without changing the timer if a saved time is negative or not finite.
+    pub fn restore(&self, timer: &mut Timer) -> Result<(), BevyError> {
+        let duration = Duration::try_from_secs_f32(self.duration)
+            .map_err(|err| format!("Invalid saved duration {}: {}", self.duration, err))?;
+        let elapsed = Duration::try_from_secs_f32(self.elapsed)
+            .map_err(|err| format!("Invalid saved elapsed time {}: {}", self.elapsed, err))?;
+        timer.set_duration(duration);
+        timer.set_elapsed(elapsed);
+        Ok(())
+    }
+}
+
+impl ActorSnapshot {
+    /// Captures the state of an actor.
+    pub fn capture(world: &World, actor: Entity) -> Result<Self, BevyError> {
+        let actor_ref = world.get_entity(actor)?;
+        let actor_def = actor_ref.get::<Actor>().ok_or("Entity is not an actor.")?;
+        let actor_token = world
+            .resource::<ActorRegistry>()
+            .token(&actor_def.0)
+            .ok_or("Actor definition is not registered.")?
+            .clone();
+
+        let attributes = {
+            let bindings = world.resource::<AppAttributeBindings>();
+            let bindings = bindings.internal.read().unwrap();
+            bindings
+                .read_base_value
+                .iter()
+                .filter_map(|(name, read)| read(&actor_ref).map(|value| (name.to_string(), value)))
+                .collect()
+        };
+
+        let effect_registry = world.resource::<EffectRegistry>();
+        let mut effects = vec![];
+        for effect_entity in actor_ref.get::<AppliedEffects>().into_iter().flat_map(|e| e.iter()) {
+            let effect_ref = world.get_entity(effect_entity)?;
+            let Some(effect) = effect_ref.get::<Effect>() else {
+                continue;
+            };
+
+            // Global effects are re-applied when the actor is restored
+            let is_global = effect_ref
+                .get::<EffectSource>()
+                .is_some_and(|source| world.get::<GlobalActor>(source.0).is_some());
+            if is_global {
+                continue;
+            }
+
+            let Some(token) = effect_registry.token(&effect.0) else {
+                warn!("Effect {} is not registered and cannot be saved.", effect_entity);
+                continue;
+            };
+
+            effects.push(EffectSnapshot {
+                effect: token.clone(),
+                source: effect_ref
+                    .get::<EffectSource>()
+                    .map(|source| source.0)
+                    .filter(|source| *source != actor)
+                    .map(Entity::to_bits),
+                duration: effect_ref
+                    .get::<EffectDuration>()
+                    .map(|duration| TimerSnapshot::capture(&duration.0)),
+                ticker: effect_ref
+                    .get::<EffectTicker>()
+                    .map(|ticker| TimerSnapshot::capture(&ticker.timer)),
+                ticks: effect_ref
+                    .get::<EffectTicker>()
+                    .map(|ticker| ticker.ticks())
+                    .unwrap_or(0),
+                stacks: effect_ref
+                    .get::<Stacks>()
+                    .map(|stacks| stacks.base_value())
+                    .unwrap_or(1),
+                inactive: effect_ref.contains::<EffectInactive>(),
+                params: effect_ref
+                    .get::<EffectParams>()
+                    .into_iter()
+                    .flat_map(|params| params.iter())
+                    .map(|(name, value)| (name.to_string(), value))
+                    .collect(),
+            });
+        }
+
+        let ability_registry = world.resource::<AbilityRegistry>();
+        let mut abilities = vec![];
+        for ability_entity in actor_ref.get::<GrantedAbilities>().into_iter().flat_map(|a| a.iter()) {
+            let ability_ref = world.get_entity(ability_entity)?;
+            let Some(ability) = ability_ref.get::<Ability>() else {
+                continue;
+            };
+            let Some(token) = ability_registry.token(&ability.0) else {
+                warn!("Ability {} is not registered and cannot be saved.", ability_entity);
+                continue;
+            };
+
+            abilities.push(AbilitySnapshot {
+                ability: token.clone(),
+                cooldown: ability_ref
+                    .get::<AbilityCooldown>()
+                    .map(|cooldown| TimerSnapshot::capture(cooldown.timer())),
+                charges: ability_ref
+                    .get::<AbilityCharges>()
+                    .zip(ability_ref.get::<Charges>())
+                    .map(|(ability_charges, charges)| ChargesSnapshot {
+                        charges: charges.base_value(),
+                        recharge: TimerSnapshot::capture(ability_charges.timer()),
+                    }),
+            });
+        }
+
+        let cooldown_groups = actor_ref
+            .get::<CooldownGroups>()
+            .into_iter()
+            .flat_map(|groups| groups.iter())
+            .map(|(group, timer)| (group.to_string(), TimerSnapshot::capture(timer)))
+            .collect();
+
+        Ok(Self {
+            actor: actor_token,
+            attributes,
+            effects,
+            abilities,
+            cooldown_groups,
+        })
+    }
+
+    fn restore(&self, world: &mut World, actor_entity: Entity) -> Result<(), BevyError> {
+        let handle = world.resource::<ActorRegistry>().get(&self.actor).clone();
+
+        // Insert the actor without the effects and abilities of its definition
+        let mut queue = world.resource_scope(
+            |world, actor_assets: Mut<Assets<ActorDef>>| -> Result<CommandQueue, BevyError> {
+                let actor_def = actor_assets.get(&handle).ok_or("Missing actor asset.")?;
+
+                let mut queue = CommandQueue::default();
+                let mut commands = Commands::new(&mut queue, world);
+                insert_actor_components(&mut commands, actor_entity, &handle, actor_def);
+                Ok(queue)
+            },
+        )?;
+        queue.apply(world);
+
+        // Restore the base values
+        {
+            let bindings = world.resource::<AppAttributeBindings>().clone();
+            let bindings = bindings.internal.read().unwrap();
+            let mut actor = world.entity_mut(actor_entity);
+            for (name, value) in &self.attributes {
+                let Some(write) = bindings.write_base_value.get(name.as_str()) else {
+                    warn!("Attribute {} is not registered and cannot be restored.", name);
+                    continue;
+                };
+                write(&mut actor, *value);
+            }
+        }
+
+        for saved in &self.abilities {
+            let Some(handle) = world.resource::<AbilityRegistry>().get(&saved.ability).cloned()
+            else {
+                warn!("{:?} is not registered and cannot be restored.", saved.ability);
+                continue;
+            };
+
+            let ability_entity = world.spawn(AbilityOf(actor_entity)).id();
+            GrantAbilityCommand {
+                parent: actor_entity,
+                handle,
+            }
+            .apply(world.entity_mut(ability_entity));
+            world.flush();
+
+            if let Some(cooldown) = saved.cooldown {
+                if let Some(mut ability_cooldown) = world.get_mut::<AbilityCooldown>(ability_entity) {
+                    cooldown.restore(ability_cooldown.timer_mut())?;
+                }
+            }
+
+            if let Some(saved_charges) = saved.charges {
+                if let Some(mut ability_charges) = world.get_mut::<AbilityCharges>(ability_entity) {
+                    ability_charges.restore(&saved_charges.recharge)?;
+                }
+                if let Some(mut charges) = world.get_mut::<Charges>(ability_entity) {
+                    charges.set_base_value(saved_charges.charges);
+                    charges.set_current_value(saved_charges.charges);
+                }
+            }
+        }
+
+        if !self.cooldown_groups.is_empty() {
+            world
+                .entity_mut(actor_entity)
+                .insert_if_new(CooldownGroups::default());
+            let mut groups = world
+                .get_mut::<CooldownGroups>(actor_entity)
+                .ok_or("Missing cooldown groups.")?;
+            for (group, timer) in &self.cooldown_groups {
+                timer.restore(groups.timer_mut(CooldownGroup::from(group.as_str())))?;
+            }
+        }
+
+        for saved in &self.effects {
+            let Some(handle) = world.resource::<EffectRegistry>().get(&saved.effect).cloned() else {
+                warn!("{:?} is not registered and cannot be restored.", saved.effect);
+                continue;
+            };
+
+            // Sources that no longer exist, such as after loading a save, become the actor
+            let source = saved
+                .source
+                .and_then(Entity::try_from_bits)
+                .filter(|source| world.get::<Actor>(*source).is_some())
+                .unwrap_or(actor_entity);
+            let application = ApplyEffectEvent {
+                entity: actor_entity,
+                targeting: EffectTargeting::new(source, actor_entity),
+                handle,
+                params: saved
+                    .params
+                    .iter()
+                    .fold(EffectParams::new(), |params, (name, value)| {
+                        params.with(name.as_str(), *value)
+                    }),
+            };
+            let effect_entity = world.run_system_once_with(spawn_saved_effect, application)??;
+            saved.restore(&mut world.entity_mut(effect_entity))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl EffectSnapshot {
+    fn restore(&self, effect: &mut EntityWorldMut) -> Result<(), BevyError> {
+        if let Some(saved) = self.duration {
+            if let Some(mut duration) = effect.get_mut::<EffectDuration>() {
+                saved.restore(&mut duration.0)?;
+            }
+        }
+        if let Some(saved) = self.ticker {
+            if let Some(mut ticker) = effect.get_mut::<EffectTicker>() {
+                saved.restore(&mut ticker.timer)?;
+                ticker.restore(self.ticks);
+            }
+        }
+        if let Some(mut stacks) = effect.get_mut::<Stacks>() {
+            stacks.set_base_value(self.stacks);
+            stacks.set_current_value(self.stacks);
+        }
+        if self.inactive {
+            effect.insert(EffectInactive);
+        } else {
+            effect.remove::<EffectInactive>();
+        }
+        Ok(())
+    }
+}
+
+/// Spawns a saved effect without the checks and events of a new application.
+fn spawn_saved_effect(
+    In(application): In<ApplyEffectEvent>,
+    actors: Query<AttributesRef, Without<IsResource>>,
+    effect_assets: Res<Assets<EffectDef>>,
+    mut commands: Commands,
+    type_registry: Res<AppTypeRegistry>,
+    type_bindings: Res<AppAttributeBindings>,
+) -> Result<Entity, BevyError> {
+    let effect = effect_assets
+        .get(&application.handle)
+        .ok_or("Missing effect asset.")?;
+    let [source_ref, target_ref] = actors.get_many([
+        application.targeting.source(),
+        application.targeting.target(),
+    ])?;
+    let context = EffectExprContext {
+        target_actor: &target_ref,
+        source_actor: &source_ref,
+        effect_holder: &source_ref,
+        params: Some(&application.params),
+        type_registry: type_registry.0.clone(),
+    };
+    application.spawn_effect_entity(&mut commands, effect, &context, &type_bindings)
+}
+
+/// Restores an [`ActorSnapshot`] onto an empty entity.
+pub struct RestoreActorCommand {
+    pub snapshot: ActorSnapshot,
+}
+
+impl EntityCommand for RestoreActorCommand {
+    type Out = ();
+
+    fn apply(self, mut entity: EntityWorldMut) -> () {
+        let actor_entity = entity.id();
+        debug!("Restoring actor {} {:?}", actor_entity, self.snapshot.actor);
+
+        entity.world_scope(|world| {
+            if let Err(err) = self.snapshot.restore(world, actor_entity) {
+                error!("Failed to restore actor {}: {}", actor_entity, err);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_timer_snapshot_rejects_invalid_times() {
+        let mut timer = Timer::from_seconds(2.0, TimerMode::Once);
+        let saved: TimerSnapshot = ron::from_str("(duration: 5.0, elapsed: 1.0)").unwrap();
+        saved.restore(&mut timer).unwrap();
+        assert_eq!(Duration::from_secs(4), timer.remaining());
+
+        for corrupt in [
+            "(duration: -1.0, elapsed: 0.0)",
+            "(duration: 5.0, elapsed: NaN)",
+        ] {
+            let saved: TimerSnapshot = ron::from_str(corrupt).unwrap();
+            assert!(saved.restore(&mut timer).is_err());
+            assert_eq!(Duration::from_secs(4), timer.remaining());
+        }
+    }
+}
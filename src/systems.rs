@@ -42,6 +42,25 @@ pub fn mark_node_dirty_observer<T: Attribute>(
         .try_insert(Dirty::<T>::default());
 }
 
+/// Marks the target of a removed effect dirty so its attributes no longer include the effect.
+pub fn on_remove_effect_mark_target_dirty<T: Attribute>(
+    trigger: On<Remove, Effect>,
+    targets: Query<&EffectTarget>,
+    attributes: Query<(), With<T>>,
+    mut commands: Commands,
+) {
+    let Ok(target) = targets.get(trigger.event_target()) else {
+        return;
+    };
+    if !attributes.contains(target.0) {
+        return;
+    }
+    commands.trigger(MarkNodeDirty::<T> {
+        entity: target.0,
+        phantom_data: Default::default(),
+    });
+}
+
 /// Navigates the tree descendants to update the tree attribute values
 /// Effects that have a periodic timer application must be ignored in the current value calculations
 pub fn update_current_value_system<T: Attribute>(
@@ -119,7 +138,7 @@ fn update_effect_tree_attributes<T: Attribute>(
     if !dirty_nodes.contains(current_entity) {
         match attributes.get(current_entity) {
             Ok(attribute) => {
-                return attribute.calculator_cache.calculator;
+                return attribute.calculator_cache.calculator.clone();
             }
             _ => {} // Continue traversing the tree.
         }
@@ -153,10 +172,24 @@ fn update_effect_tree_attributes<T: Attribute>(
             calculator
         }
         NodeType::Effect => {
-            let Ok((modifier_entities, _source, _target)) = effects.get(current_entity) else {
+            let Ok((modifier_entities, source, target)) = effects.get(current_entity) else {
                 // Effect has no modifiers, return default calculator.
                 return AttributeCalculator::default();
             };
+            let Ok([source_ref, target_ref, effect_ref]) =
+                attribute_refs.get_many([source.0, target.0, current_entity])
+            else {
+                error!("{}: Error getting effect actors.", current_entity);
+                return AttributeCalculator::default();
+            };
+            let context = EffectExprContext {
+                source_actor: &source_ref,
+                target_actor: &target_ref,
+                effect_holder: &effect_ref,
+                params: None,
+                type_registry: type_registry.clone(),
+            };
+            let pipeline = type_bindings.internal.read().unwrap().pipeline::<T>();
 
             let calculator = modifier_entities
                 .iter()
@@ -173,7 +206,10 @@ fn update_effect_tree_attributes<T: Attribute>(
                         return None;
                     }
 
-                    let calc = AttributeCalculator::convert(modifier).unwrap_or_default();
+                    // Scale by the effect's stacks and intensity
+                    let modifier = modifier.scaled(&context);
+                    let calc =
+                        AttributeCalculator::convert(&modifier, &pipeline).unwrap_or_default();
 
                     Some(calc)
                 })
@@ -188,7 +224,7 @@ fn update_effect_tree_attributes<T: Attribute>(
     // Signal to update the attribute
     commands.trigger(UpdateAttributeSignal::<T> {
         entity: current_entity,
-        calculator: node_calculator,
+        calculator: node_calculator.clone(),
     });
 
     // Cleans the node
@@ -207,12 +243,15 @@ pub fn update_attribute<T: Attribute>(
     trigger: On<UpdateAttributeSignal<T>>,
     mut attributes: Query<(AttributeQueryData<T>, Option<&AttributeDependents<T>>)>,
     mut commands: Commands,
+    type_bindings: Res<AppAttributeBindings>,
 ) {
     if let Ok((mut attribute, dependencies)) = attributes.get_mut(trigger.event_target()) {
-        attribute.calculator_cache.calculator = trigger.event().calculator;
+        attribute.calculator_cache.calculator = trigger.event().calculator.clone();
         let old_value = attribute.attribute.current_value();
 
-        let should_notify_observers = attribute.update_attribute(&trigger.event().calculator);
+        let pipeline = type_bindings.internal.read().unwrap().pipeline::<T>();
+        let should_notify_observers =
+            attribute.update_attribute(&trigger.event().calculator, &pipeline);
         if should_notify_observers {
             commands.trigger(CurrentValueChanged::<T> {
                 entity: trigger.event_target(),
@@ -247,51 +286,46 @@ pub fn apply_periodic_effect<T: Attribute>(
     effect_assets: Res<Assets<EffectDef>>,
     type_registry: Res<AppTypeRegistry>,
 ) {
-    for (effect_ref, effect, timer, owned_modifiers, target, source) in effects.iter() {
-        if !timer.just_finished() {
+    for (effect_ref, effect, ticker, owned_modifiers, target, source) in effects.iter() {
+        let ticks = ticker.ticks_this_frame();
+        if ticks == 0 {
             continue;
         }
 
-        let effect_def = effect_assets
-            .get(&effect.0)
-            .ok_or("No effect asset.")
-            .unwrap();
-
-        let source_actor_ref = actors.get(source.0).unwrap();
-        let target_actor_ref = actors.get(target.0).unwrap();
+        let Some(effect_def) = effect_assets.get(&effect.0) else {
+            continue;
+        };
+        let Ok([source_actor_ref, target_actor_ref]) = actors.get_many([source.0, target.0]) else {
+            continue;
+        };
 
         let context = EffectExprContext {
             target_actor: &target_actor_ref,
             source_actor: &source_actor_ref,
             effect_holder: &effect_ref,
+            params: None,
             type_registry: type_registry.0.clone(),
         };
 
-        // Determines whether the effect should activate
-        let should_be_activated = effect_def
-            .activate_conditions
-            .iter()
-            .all(|condition| condition.eval(&context).unwrap_or(false));
-
-        if !should_be_activated {
+        if !effect_def.should_activate(&context) {
             continue;
         }
 
-        // Timer has triggered. Grab modifiers and apply them.
-        for children in owned_modifiers.iter() {
-            let Ok(attribute_modifier) = modifiers.get(children) else {
-                continue;
-            };
-
-            // Clone the modifier so we can apply the stack count to it.
-            let applied_modifier = attribute_modifier.clone();
+        // Timer has triggered. Grab modifiers and apply them once per tick.
+        for _ in 0..ticks {
+            for children in owned_modifiers.iter() {
+                let Ok(attribute_modifier) = modifiers.get(children) else {
+                    continue;
+                };
 
-            event_writer.write(ApplyAttributeModifierMessage {
-                source_entity: source.0,
-                target_entity: target.0,
-                effect_entity: effect_ref.id(),
-                modifier: applied_modifier,
-            });
+                event_writer.write(ApplyAttributeModifierMessage {
+                    source_entity: source.0,
+                    target_entity: target.0,
+                    effect_entity: effect_ref.id(),
+                    modifier: attribute_modifier.clone(),
+                    params: None,
+                });
+            }
         }
     }
 }
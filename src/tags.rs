@@ -0,0 +1,271 @@
+use crate::assets::EffectDef;
+use crate::effect::{Effect, EffectInactive, EffectTarget};
+use crate::schedule::EffectsSet;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+/// A hierarchical gameplay tag such as `Status.Debuff.Stun`.
+///
+/// A tag matches itself and all of its parents: an actor tagged `Status.Debuff.Stun`
+/// also has the tags `Status.Debuff` and `Status`.
+#[derive(Clone, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub struct GameplayTag(SmolStr);
+
+impl GameplayTag {
+    /// Construct a new [`GameplayTag`] from a [`SmolStr`].
+    pub const fn new(text: SmolStr) -> Self {
+        Self(text)
+    }
+
+    /// Construct a new [`GameplayTag`] from a static string.
+    pub const fn new_static(text: &'static str) -> Self {
+        Self(SmolStr::new_static(text))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether this tag is `other` or one of its descendants.
+    pub fn matches(&self, other: &GameplayTag) -> bool {
+        match self.0.strip_prefix(other.0.as_str()) {
+            Some(rest) => rest.is_empty() || rest.starts_with('.'),
+            None => false,
+        }
+    }
+
+    /// The parent tag, if any. The parent of `Status.Debuff.Stun` is `Status.Debuff`.
+    pub fn parent(&self) -> Option<GameplayTag> {
+        self.0
+            .rsplit_once('.')
+            .map(|(parent, _)| GameplayTag(SmolStr::new(parent)))
+    }
+}
+
+impl From<&str> for GameplayTag {
+    fn from(value: &str) -> Self {
+        Self(SmolStr::new(value))
+    }
+}
+
+impl From<String> for GameplayTag {
+    fn from(value: String) -> Self {
+        Self(SmolStr::new(value))
+    }
+}
+
+impl core::fmt::Display for GameplayTag {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl core::fmt::Debug for GameplayTag {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "GameplayTag({:?})", self.0)
+    }
+}
+
+/// The gameplay tags of an actor.
+///
+/// Tags are reference counted: a tag granted by two effects stays until both are removed.
+#[derive(Component, Reflect, Default, Debug, Clone)]
+#[reflect(Component, Default)]
+pub struct GameplayTags {
+    counts: HashMap<GameplayTag, u32>,
+}
+
+impl GameplayTags {
+    /// Whether the actor has the tag or one of its descendants.
+    pub fn has_tag(&self, tag: &GameplayTag) -> bool {
+        self.counts.keys().any(|owned| owned.matches(tag))
+    }
+
+    /// Whether the actor has exactly this tag.
+    pub fn has_tag_exact(&self, tag: &GameplayTag) -> bool {
+        self.counts.contains_key(tag)
+    }
+
+    pub fn has_any<'a>(&self, tags: impl IntoIterator<Item = &'a GameplayTag>) -> bool {
+        tags.into_iter().any(|tag| self.has_tag(tag))
+    }
+
+    pub fn has_all<'a>(&self, tags: impl IntoIterator<Item = &'a GameplayTag>) -> bool {
+        tags.into_iter().all(|tag| self.has_tag(tag))
+    }
+
+    /// How many times the exact tag was added.
+    pub fn count(&self, tag: &GameplayTag) -> u32 {
+        self.counts.get(tag).copied().unwrap_or(0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &GameplayTag> {
+        self.counts.keys()
+    }
+
+    /// Adds a reference to the tag. Returns true if the tag was not present.
+    pub fn add(&mut self, tag: GameplayTag) -> bool {
+        let count = self.counts.entry(tag).or_insert(0);
+        *count += 1;
+        *count == 1
+    }
+
+    /// Removes a reference to the tag. Returns true if it was the last one.
+    pub fn remove(&mut self, tag: &GameplayTag) -> bool {
+        let Some(count) = self.counts.get_mut(tag) else {
+            return false;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.counts.remove(tag);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Triggered on an actor when it gains a tag it did not have.
+#[derive(EntityEvent, Debug, Clone)]
+pub struct GameplayTagAdded {
+    pub entity: Entity,
+    pub tag: GameplayTag,
+}
+
+/// Triggered on an actor when the last reference to a tag is removed.
+#[derive(EntityEvent, Debug, Clone)]
+pub struct GameplayTagRemoved {
+    pub entity: Entity,
+    pub tag: GameplayTag,
+}
+
+/// The tags an active effect currently grants to its target.
+/// Removing the component, or despawning the effect, revokes them.
+#[derive(Component, Debug)]
+pub struct EffectGrantedTags {
+    target: Entity,
+    tags: Vec<GameplayTag>,
+}
+
+pub struct GameplayTagsPlugin;
+
+impl Plugin for GameplayTagsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<GameplayTags>()
+            .add_systems(Update, update_effect_granted_tags.in_set(EffectsSet::Notify))
+            .add_observer(revoke_effect_granted_tags);
+    }
+}
+
+/// Adds a reference to each tag on the entity and triggers [`GameplayTagAdded`] for new tags.
+pub fn add_tags(world: &mut World, entity: Entity, tags: &[GameplayTag]) {
+    let Ok(mut entity_mut) = world.get_entity_mut(entity) else {
+        return;
+    };
+    if !entity_mut.contains::<GameplayTags>() {
+        entity_mut.insert(GameplayTags::default());
+    }
+    let mut owned = entity_mut.get_mut::<GameplayTags>().unwrap();
+    let added = tags
+        .iter()
+        .filter(|tag| owned.add((*tag).clone()))
+        .cloned()
+        .collect::<Vec<_>>();
+    for tag in added {
+        debug!("Actor {} gained tag {}.", entity, tag);
+        world.trigger(GameplayTagAdded { entity, tag });
+    }
+}
+
+/// Removes a reference to each tag on the entity and triggers [`GameplayTagRemoved`]
+/// for tags that are no longer present.
+pub fn remove_tags(world: &mut World, entity: Entity, tags: &[GameplayTag]) {
+    let Some(mut owned) = world.get_mut::<GameplayTags>(entity) else {
+        return;
+    };
+    let removed = tags
+        .iter()
+        .filter(|tag| owned.remove(tag))
+        .cloned()
+        .collect::<Vec<_>>();
+    for tag in removed {
+        debug!("Actor {} lost tag {}.", entity, tag);
+        world.trigger(GameplayTagRemoved { entity, tag });
+    }
+}
+
+// Grants the tags of active effects and revokes those of inactive effects
+fn update_effect_granted_tags(
+    effects: Query<(
+        Entity,
+        &Effect,
+        &EffectTarget,
+        Has<EffectInactive>,
+        Has<EffectGrantedTags>,
+    )>,
+    effect_assets: Res<Assets<EffectDef>>,
+    mut commands: Commands,
+) {
+    for (effect_entity, effect, target, is_inactive, is_granted) in effects.iter() {
+        if is_inactive && is_granted {
+            commands.entity(effect_entity).remove::<EffectGrantedTags>();
+            continue;
+        }
+        if is_inactive || is_granted {
+            continue;
+        }
+        let Some(effect_def) = effect_assets.get(&effect.0) else {
+            continue;
+        };
+        if effect_def.granted_tags.is_empty() {
+            continue;
+        }
+
+        let target = target.0;
+        let tags = effect_def.granted_tags.clone();
+        commands.entity(effect_entity).insert(EffectGrantedTags {
+            target,
+            tags: tags.clone(),
+        });
+        commands.queue(move |world: &mut World| add_tags(world, target, &tags));
+    }
+}
+
+fn revoke_effect_granted_tags(
+    trigger: On<Remove, EffectGrantedTags>,
+    granted: Query<&EffectGrantedTags>,
+    mut commands: Commands,
+) {
+    let Ok(granted) = granted.get(trigger.event_target()) else {
+        return;
+    };
+    let target = granted.target;
+    let tags = granted.tags.clone();
+    commands.queue(move |world: &mut World| remove_tags(world, target, &tags));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hierarchical_tags() {
+        let stun = GameplayTag::new_static("Status.Debuff.Stun");
+        let debuff = GameplayTag::new_static("Status.Debuff");
+        let mut tags = GameplayTags::default();
+
+        assert!(tags.add(stun.clone()));
+        assert!(!tags.add(stun.clone()));
+        assert!(tags.has_tag(&debuff));
+        assert!(!tags.has_tag_exact(&debuff));
+        assert!(!tags.has_tag(&GameplayTag::new_static("Status.Debuffs")));
+        assert_eq!(stun.parent(), Some(debuff.clone()));
+
+        assert!(!tags.remove(&stun));
+        assert!(tags.has_tag(&stun));
+        assert!(tags.remove(&stun));
+        assert!(!tags.has_all([&stun, &debuff]));
+    }
+}
@@ -1,13 +1,45 @@
+use bevy::asset::LoadState;
 use bevy::ecs::system::RunSystemOnce;
 use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use vitality::ability::{
+    Ability, AbilityActivationFailed, AbilityBuilder, AbilityCancel, AbilityCharges,
+    AbilityContext, AbilityCooldown, ActivationFailure, ActiveAbility, CancelReason, Charges,
+    CommitPolicy, CooldownGroup, CooldownReduction, EndAbility, ExecuteAbility,
+    GrantedAbilities,
+};
+use vitality::assets::{AbilityDef, EffectDef};
 use vitality::actors::ActorBuilder;
-use vitality::context::Vitality;
-use vitality::effect::{Effect, EffectApplicationPolicy, EffectBuilder};
-use vitality::modifier::{ModOp, EffectSubject};
+use vitality::context::{EffectExprContext, Vitality};
+use vitality::effect::{
+    AppliedEffects, BlockReason, Effect, EffectApplicationBlocked, EffectApplicationPolicy,
+    EffectApplied, EffectBuilder, EffectDuration, EffectExpired, EffectFilter, EffectImmunities,
+    EffectParams, EffectRemovalReason, EffectRemoved, EffectSource, EffectStackingPolicy,
+    EffectStacksChanged, EffectTicker, ExecutionOutput, ImmunityRule, RemoveEffects,
+    StackChangeReason, StackExpiration, StackScaling, StackingScope, Stacks,
+};
+use vitality::modifier::{
+    AggregationPipeline, AttributeExplainer, EffectSubject, ModOp, SkipReason, StageKind,
+};
 use vitality::prelude::*;
-use vitality::{AttributesPlugin, attribute, init_attribute};
+use vitality::registry::RegistryMut;
+use vitality::registry::ability_registry::AbilityToken;
+use vitality::registry::actor_registry::ActorToken;
+use vitality::registry::effect_registry::{EffectRegistry, EffectToken};
+use vitality::snapshot::ActorSnapshot;
+use vitality::condition::{HasTag, IsAttributeWithinBounds};
+use vitality::tags::{GameplayTag, GameplayTags};
+use vitality::{
+    AttributesPlugin, BaseValueChanged, attribute, attribute_calculator, init_attribute,
+    init_attribute_with_calculator, init_attribute_with_pipeline,
+};
 
 attribute!(TestA, u32);
+attribute!(TestB, u32);
+attribute!(TestC, u32);
 
 fn prepare_actor(mut ctx: Vitality) {
     let actor_template = ctx.add_actor(
@@ -67,3 +99,1997 @@ fn test_update_current_values() {
     assert_eq!(0, attribute.base_value());
     assert_eq!(0, attribute.current_value());
 }
+
+/// Applies a stacking effect whose modifier scales linearly with the stack count.
+/// Asserts that every added stack adds another 42 to the attribute.
+#[test]
+fn test_modifier_scales_with_stacks() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    let handle = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.add_effect(
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify_scaled::<TestA>(
+                        42u32,
+                        ModOp::Add,
+                        EffectSubject::Target,
+                        StackScaling::Linear,
+                    )
+                    .with_stacking_policy(EffectStackingPolicy::Add {
+                        count: 1,
+                        max_stack: 3,
+                    })
+                    .build(),
+            )
+        })
+        .unwrap();
+
+    for expected in [42, 84, 126, 126] {
+        let handle = handle.clone();
+        app.world_mut()
+            .run_system_once(move |mut ctx: Vitality| {
+                ctx.apply_effect_to_self(entity, &handle);
+            })
+            .unwrap();
+
+        app.update();
+
+        let attribute = app.world().get::<TestA>(entity).unwrap();
+        assert_eq!(0, attribute.base_value());
+        assert_eq!(expected, attribute.current_value());
+    }
+}
+
+/// Registers TestA with a pipeline adding a flat bonus after the multipliers.
+/// Asserts that (0 + 10) * 2 + 5 = 25, ignoring the modifier of a channel missing from the pipeline.
+#[test]
+fn test_custom_aggregation_pipeline() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute_with_pipeline::<TestA>(
+        AggregationPipeline::default().stage("final_flat", StageKind::Add),
+    ));
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            let handle = ctx.add_effect(
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify_channel::<TestA>(5u32, "final_flat", EffectSubject::Target)
+                    .modify_channel::<TestA>(100u32, "missing", EffectSubject::Target)
+                    .modify::<TestA>(2u32, ModOp::Mul, EffectSubject::Target)
+                    .modify::<TestA>(10u32, ModOp::Add, EffectSubject::Target)
+                    .build(),
+            );
+            ctx.apply_effect_to_self(entity, &handle);
+        })
+        .unwrap();
+
+    app.update();
+
+    let attribute = app.world().get::<TestA>(entity).unwrap();
+    assert_eq!(0, attribute.base_value());
+    assert_eq!(25, attribute.current_value());
+}
+
+/// Asserts that (0 + 10) * 3 / 2 + 4 = 19 is capped to 18,
+/// then that the override with the highest priority wins over Set, whatever the order.
+#[test]
+fn test_extended_mod_ops() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            let handle = ctx.add_effect(
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify::<TestA>(18u32, ModOp::Min, EffectSubject::Target)
+                    .modify::<TestA>(4u32, ModOp::PostAdd, EffectSubject::Target)
+                    .modify::<TestA>(2u32, ModOp::Div, EffectSubject::Target)
+                    .modify::<TestA>(3u32, ModOp::Mul, EffectSubject::Target)
+                    .modify::<TestA>(10u32, ModOp::Add, EffectSubject::Target)
+                    .build(),
+            );
+            ctx.apply_effect_to_self(entity, &handle);
+        })
+        .unwrap();
+
+    app.update();
+    assert_eq!(18, app.world().get::<TestA>(entity).unwrap().current_value());
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            let priority = ctx.add_effect(
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify::<TestA>(7u32, ModOp::Override { priority: 1 }, EffectSubject::Target)
+                    .build(),
+            );
+            let set = ctx.add_effect(
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify::<TestA>(50u32, ModOp::Set, EffectSubject::Target)
+                    .build(),
+            );
+            ctx.apply_effect_to_self(entity, &priority);
+            ctx.apply_effect_to_self(entity, &set);
+        })
+        .unwrap();
+
+    app.update();
+    assert_eq!(7, app.world().get::<TestA>(entity).unwrap().current_value());
+}
+
+/// Explains TestA(u32) on a clamped actor with a stacked effect adding 5 per stack twice,
+/// a cap of 8 and an inactive effect adding 100.
+/// Asserts that the breakdown matches the current value of 8.
+#[test]
+fn test_attribute_explainer() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+
+    let entity = app
+        .world_mut()
+        .run_system_once(|mut ctx: Vitality| {
+            let actor = ctx.add_actor(
+                ActorBuilder::new()
+                    .name("TestActor".into())
+                    .with::<TestA>(0)
+                    .clamp::<TestA>(0u32, 1000u32)
+                    .build(),
+            );
+            ctx.spawn_actor_from_handle(&actor).id()
+        })
+        .unwrap();
+    app.update();
+
+    let stacked = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            let stacked = ctx.add_effect(
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify_scaled::<TestA>(
+                        5u32,
+                        ModOp::Add,
+                        EffectSubject::Target,
+                        StackScaling::Linear,
+                    )
+                    .with_stacking_policy(EffectStackingPolicy::Add {
+                        count: 1,
+                        max_stack: 3,
+                    })
+                    .build(),
+            );
+            ctx.apply_dynamic_effect_to_self(
+                entity,
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify::<TestA>(8u32, ModOp::Min, EffectSubject::Target)
+                    .build(),
+            );
+            ctx.apply_dynamic_effect_to_self(
+                entity,
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify::<TestA>(100u32, ModOp::Add, EffectSubject::Target)
+                    .active_while(HasTag::target("Status.Debuff"))
+                    .build(),
+            );
+            stacked
+        })
+        .unwrap();
+
+    for _ in 0..2 {
+        let stacked = stacked.clone();
+        app.world_mut()
+            .run_system_once(move |mut ctx: Vitality| {
+                ctx.apply_effect_to_self(entity, &stacked);
+            })
+            .unwrap();
+        app.update();
+    }
+    app.update();
+
+    let breakdown = app
+        .world_mut()
+        .run_system_once(move |explainer: AttributeExplainer| {
+            explainer.explain::<TestA>(entity).unwrap()
+        })
+        .unwrap();
+
+    let current_value = app.world().get::<TestA>(entity).unwrap().current_value();
+    assert_eq!(8, current_value);
+    assert_eq!(current_value, breakdown.result);
+    assert_eq!(Some((0, 1000)), breakdown.clamp);
+    assert_eq!(3, breakdown.effects.len());
+    assert_eq!(2, breakdown.contributing().count());
+
+    let values = breakdown.contributing().map(|modifier| modifier.value).collect::<Vec<_>>();
+    assert!(values.contains(&10));
+    assert!(breakdown
+        .effects
+        .iter()
+        .any(|effect| effect.skipped == Some(SkipReason::Inactive)));
+}
+
+/// Derives TestB = TestA * 10 on an actor with TestA(2).
+/// Asserts that TestB follows the current value of TestA when an effect adds 3 to it.
+#[test]
+fn test_derived_attribute_recomputes() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins((init_attribute::<TestA>, init_attribute::<TestB>));
+
+    let entity = app
+        .world_mut()
+        .run_system_once(|mut ctx: Vitality| {
+            let actor = ctx.add_actor(
+                ActorBuilder::new()
+                    .with::<TestA>(2)
+                    .derive::<TestB>(TestA::src() * TestB::lit(10))
+                    .build(),
+            );
+            ctx.spawn_actor_from_handle(&actor).id()
+        })
+        .unwrap();
+    app.update();
+    assert_eq!(20, app.world().get::<TestB>(entity).unwrap().base_value());
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.apply_dynamic_effect_to_self(
+                entity,
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify::<TestA>(3u32, ModOp::Add, EffectSubject::Target)
+                    .build(),
+            );
+        })
+        .unwrap();
+    app.update();
+    app.update();
+
+    assert_eq!(5, app.world().get::<TestA>(entity).unwrap().current_value());
+    assert_eq!(50, app.world().get::<TestB>(entity).unwrap().base_value());
+}
+
+/// Derives TestB and TestC from each other, and TestA = TestC * 2.
+/// Asserts that the cycle is rejected and keeps its initial values, while TestA is derived.
+#[test]
+fn test_derived_attribute_cycle() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins((
+        init_attribute::<TestA>,
+        init_attribute::<TestB>,
+        init_attribute::<TestC>,
+    ));
+
+    let entity = app
+        .world_mut()
+        .run_system_once(|mut ctx: Vitality| {
+            let actor = ctx.add_actor(
+                ActorBuilder::new()
+                    .with::<TestB>(7)
+                    .with::<TestC>(3)
+                    .derive::<TestB>(TestC::src() + TestB::lit(1))
+                    .derive::<TestC>(TestB::src())
+                    .derive::<TestA>(TestC::src() * TestA::lit(2))
+                    .build(),
+            );
+            ctx.spawn_actor_from_handle(&actor).id()
+        })
+        .unwrap();
+    app.update();
+    app.update();
+
+    assert_eq!(7, app.world().get::<TestB>(entity).unwrap().base_value());
+    assert_eq!(3, app.world().get::<TestC>(entity).unwrap().base_value());
+    assert_eq!(6, app.world().get::<TestA>(entity).unwrap().base_value());
+}
+
+#[attribute_calculator]
+enum TestMod {
+    #[additive]
+    Flat(f64),
+    #[increased]
+    Increased(f64),
+    #[multiplicative]
+    More(f64),
+}
+
+/// Loads a regeneration effect and a malformed effect ticking every 0 seconds from files.
+/// Asserts that the regeneration is registered under its file name
+/// and that the malformed effect fails to load.
+#[test]
+fn test_effect_loader() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin {
+            file_path: "tests/assets".into(),
+            ..default()
+        },
+        AttributesPlugin,
+    ));
+    app.add_plugins(init_attribute::<TestA>);
+
+    app.update();
+
+    let asset_server = app.world().resource::<AssetServer>().clone();
+    let regeneration: Handle<EffectDef> = asset_server.load("effects/regeneration.effect.ron");
+    let broken: Handle<EffectDef> = asset_server.load("effects/broken.effect.ron");
+
+    // Assets load in the background
+    let token = EffectToken::new_static("regeneration");
+    for _ in 0..200 {
+        app.update();
+        let registered = app
+            .world()
+            .resource::<EffectRegistry>()
+            .get(&token)
+            .is_some();
+        if registered && matches!(asset_server.load_state(&broken), LoadState::Failed(_)) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let registry = app.world().resource::<EffectRegistry>();
+    assert_eq!(Some(&regeneration), registry.get(&token));
+    let effects = app.world().resource::<Assets<EffectDef>>();
+    assert!(
+        effects
+            .get(&regeneration)
+            .unwrap()
+            .application_policy
+            .is_periodic()
+    );
+    assert!(matches!(
+        asset_server.load_state(&broken),
+        LoadState::Failed(_)
+    ));
+}
+
+/// Registers TestA with the calculator of TestMod.
+/// Asserts that (0 + 10) * (1 + 1) * (1 + 1) = 40.
+#[test]
+fn test_attribute_calculator() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute_with_calculator::<TestA, TestMod>());
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            let handle = ctx.add_effect(
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify_with::<TestA>(TestMod::More(1.0), EffectSubject::Target)
+                    .modify_with::<TestA>(TestMod::Increased(1.0), EffectSubject::Target)
+                    .modify_with::<TestA>(TestMod::Flat(10.0), EffectSubject::Target)
+                    .build(),
+            );
+            ctx.apply_effect_to_self(entity, &handle);
+        })
+        .unwrap();
+
+    app.update();
+
+    let attribute = app.world().get::<TestA>(entity).unwrap();
+    assert_eq!(40, attribute.current_value());
+}
+
+/// Applies fractional modifiers to the integer attribute TestA.
+/// Asserts that they are not truncated: (0 + 8) * (1 + 0.5) * (1 + 0.25) = 15.
+#[test]
+fn test_attribute_calculator_fractions() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute_with_calculator::<TestA, TestMod>());
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            let handle = ctx.add_effect(
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify_with::<TestA>(TestMod::More(0.25), EffectSubject::Target)
+                    .modify_with::<TestA>(TestMod::Increased(0.5), EffectSubject::Target)
+                    .modify_with::<TestA>(TestMod::Flat(8.0), EffectSubject::Target)
+                    .build(),
+            );
+            ctx.apply_effect_to_self(entity, &handle);
+        })
+        .unwrap();
+
+    app.update();
+
+    let attribute = app.world().get::<TestA>(entity).unwrap();
+    assert_eq!(15, attribute.current_value());
+}
+
+/// Applies two effects granting Status.Debuff.Stun and a third checking HasTag on Status.Debuff.
+/// Asserts that the tag is reference counted and that the condition follows it.
+#[test]
+fn test_effect_granted_tags() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    let stun = GameplayTag::new_static("Status.Debuff.Stun");
+    let stuns = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            let stun = EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                .grant_tag("Status.Debuff.Stun")
+                .build();
+            let first = ctx.apply_dynamic_effect_to_self(entity, stun);
+            let second = ctx.apply_dynamic_effect_to_self(
+                entity,
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .grant_tag("Status.Debuff.Stun")
+                    .build(),
+            );
+            ctx.apply_dynamic_effect_to_self(
+                entity,
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .modify::<TestA>(10u32, ModOp::Add, EffectSubject::Target)
+                    .active_while(HasTag::target("Status.Debuff"))
+                    .build(),
+            );
+            [first, second]
+        })
+        .unwrap();
+
+    app.update();
+    app.update();
+
+    let tags = app.world().get::<GameplayTags>(entity).unwrap();
+    assert_eq!(2, tags.count(&stun));
+    assert_eq!(10, app.world().get::<TestA>(entity).unwrap().current_value());
+
+    // Removing one of the stuns keeps the tag
+    let mut query = app.world_mut().query::<(Entity, &Effect)>();
+    let stun_effects = query
+        .iter(app.world())
+        .filter(|(_, effect)| stuns.iter().any(|handle| handle.id() == effect.id()))
+        .map(|(effect_entity, _)| effect_entity)
+        .collect::<Vec<_>>();
+    app.world_mut().despawn(stun_effects[0]);
+    app.update();
+
+    let tags = app.world().get::<GameplayTags>(entity).unwrap();
+    assert!(tags.has_tag(&stun));
+
+    app.world_mut().despawn(stun_effects[1]);
+    app.update();
+    app.update();
+
+    let tags = app.world().get::<GameplayTags>(entity).unwrap();
+    assert!(!tags.has_tag(&GameplayTag::new_static("Status")));
+    assert_eq!(0, app.world().get::<TestA>(entity).unwrap().current_value());
+}
+
+/// Applies three permanent effects adding 10 to TestA, two of which grant Debuff.Magic.
+/// Dispels at most one magic debuff, then all effects from the actor.
+#[test]
+fn test_remove_effects() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            for tag in ["Debuff.Magic", "Debuff.Magic", "Buff"] {
+                ctx.apply_dynamic_effect_to_self(
+                    entity,
+                    EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                        .modify::<TestA>(10u32, ModOp::Add, EffectSubject::Target)
+                        .grant_tag(tag)
+                        .build(),
+                );
+            }
+        })
+        .unwrap();
+
+    app.update();
+    assert_eq!(30, app.world().get::<TestA>(entity).unwrap().current_value());
+
+    let removed = Arc::new(AtomicU32::new(0));
+    let counter = removed.clone();
+    app.world_mut()
+        .entity_mut(entity)
+        .observe(move |_: On<EffectRemoved>| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            let dispel = RemoveEffects::matching(GameplayTag::new_static("Debuff")).at_most(1);
+            ctx.remove_effects(entity, dispel);
+        })
+        .unwrap();
+
+    app.update();
+    assert_eq!(20, app.world().get::<TestA>(entity).unwrap().current_value());
+    assert_eq!(1, removed.load(Ordering::Relaxed));
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.remove_effects(entity, RemoveEffects::matching(EffectFilter::Source(entity)));
+        })
+        .unwrap();
+
+    app.update();
+    assert_eq!(0, app.world().get::<TestA>(entity).unwrap().current_value());
+    assert_eq!(3, removed.load(Ordering::Relaxed));
+}
+
+/// Applies a temporary effect lasting 0.5 seconds with 250ms frames.
+/// Asserts that the target observes its application, expiration and removal in order,
+/// then that despawning a permanent effect directly is observed as a removal.
+#[test]
+fn test_effect_lifecycle_events() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    // Virtual time advances by at most 250ms per update
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(250)));
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let (applied, expired, removed) = (events.clone(), events.clone(), events.clone());
+    app.world_mut()
+        .entity_mut(entity)
+        .observe(move |_: On<EffectApplied>| applied.lock().unwrap().push("applied"))
+        .observe(move |_: On<EffectExpired>| expired.lock().unwrap().push("expired"))
+        .observe(move |event: On<EffectRemoved>| {
+            let removal = match event.reason {
+                EffectRemovalReason::Expired => "removed",
+                EffectRemovalReason::Despawned => "despawned",
+                EffectRemovalReason::Dispelled => "dispelled",
+            };
+            removed.lock().unwrap().push(removal);
+        });
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.apply_dynamic_effect_to_self(
+                entity,
+                EffectBuilder::for_seconds(0.5)
+                    .modify::<TestA>(10u32, ModOp::Add, EffectSubject::Target)
+                    .build(),
+            );
+        })
+        .unwrap();
+
+    for _ in 0..4 {
+        app.update();
+    }
+
+    assert_eq!(*events.lock().unwrap(), vec!["applied", "expired", "removed"]);
+    assert_eq!(0, app.world().get::<TestA>(entity).unwrap().current_value());
+
+    // Effects despawned directly report their removal once
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.apply_dynamic_effect_to_self(
+                entity,
+                EffectBuilder::new(EffectApplicationPolicy::Permanent).build(),
+            );
+        })
+        .unwrap();
+    app.update();
+
+    let effect = app
+        .world()
+        .get::<AppliedEffects>(entity)
+        .unwrap()
+        .iter()
+        .next()
+        .unwrap();
+    app.world_mut().despawn(effect);
+    app.update();
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec!["applied", "expired", "removed", "applied", "despawned"]
+    );
+}
+
+/// Grants an immunity to stuns, then applies a stun from the actor and from a despawned source,
+/// and an instant effect adding 5 to TestA.
+/// Asserts that both stuns are blocked with the immunity effect as the blocker,
+/// and that the actor's own rule blocks the instant effect.
+#[test]
+fn test_effect_immunities() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.apply_dynamic_effect_to_self(
+                entity,
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .grant_immunity(GameplayTag::new_static("Status.Debuff"))
+                    .build(),
+            );
+        })
+        .unwrap();
+    app.update();
+
+    let blocked = Arc::new(Mutex::new(Vec::new()));
+    let events = blocked.clone();
+    app.world_mut()
+        .entity_mut(entity)
+        .observe(move |event: On<EffectApplicationBlocked>| {
+            events
+                .lock()
+                .unwrap()
+                .push((event.reason.clone(), event.blocked_by.is_some()));
+        });
+
+    // Tag rules do not need the source
+    let despawned = app.world_mut().spawn_empty().id();
+    app.world_mut().despawn(despawned);
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            let stun = ctx.apply_dynamic_effect_to_self(
+                entity,
+                EffectBuilder::for_seconds(2.0)
+                    .grant_tag("Status.Debuff.Stun")
+                    .build(),
+            );
+            ctx.apply_effect_to_target(entity, despawned, &stun);
+        })
+        .unwrap();
+    app.update();
+
+    // Blocks everything while TestA is 0
+    app.world_mut()
+        .entity_mut(entity)
+        .insert(EffectImmunities(vec![ImmunityRule::BlockIf(
+            IsAttributeWithinBounds::<TestA>::target(..1).into(),
+        )]));
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.apply_dynamic_effect_to_self(
+                entity,
+                EffectBuilder::instant()
+                    .modify::<TestA>(5u32, ModOp::Add, EffectSubject::Target)
+                    .build(),
+            );
+        })
+        .unwrap();
+    app.update();
+
+    assert_eq!(
+        *blocked.lock().unwrap(),
+        vec![
+            (BlockReason::ImmuneToTag(GameplayTag::new_static("Status.Debuff")), true),
+            (BlockReason::ImmuneToTag(GameplayTag::new_static("Status.Debuff")), true),
+            (BlockReason::Condition, false),
+        ]
+    );
+    assert_eq!(0, app.world().get::<TestA>(entity).unwrap().base_value());
+    let tags = app.world().get::<GameplayTags>(entity).unwrap();
+    assert!(!tags.has_tag(&GameplayTag::new_static("Status.Debuff.Stun")));
+}
+
+/// Applies an effect stacking up to 2 per source from the actor twice and from another actor once.
+/// Asserts that each source stacks its own effect.
+#[test]
+fn test_stacking_per_source() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    let (handle, other) = app
+        .world_mut()
+        .run_system_once(|mut ctx: Vitality| {
+            let handle = ctx.add_effect(
+                EffectBuilder::permanent()
+                    .modify_scaled::<TestA>(
+                        1u32,
+                        ModOp::Add,
+                        EffectSubject::Target,
+                        StackScaling::Linear,
+                    )
+                    .with_stacking_policy(EffectStackingPolicy::Add {
+                        count: 1,
+                        max_stack: 2,
+                    })
+                    .with_stacking_scope(StackingScope::PerSource)
+                    .build(),
+            );
+            let other = ctx
+                .add_spawn_actor(ActorBuilder::new().name("Other".into()).build())
+                .id();
+            (handle, other)
+        })
+        .unwrap();
+
+    for source in [entity, entity, other] {
+        let handle = handle.clone();
+        app.world_mut()
+            .run_system_once(move |mut ctx: Vitality| {
+                ctx.apply_effect_to_target(entity, source, &handle);
+            })
+            .unwrap();
+        app.update();
+    }
+    app.update();
+
+    let mut query = app.world_mut().query::<(&Effect, &Stacks)>();
+    let mut stacks = query
+        .iter(app.world())
+        .map(|(_, stacks)| stacks.base_value())
+        .collect::<Vec<_>>();
+    stacks.sort();
+    assert_eq!(vec![1, 2], stacks);
+    assert_eq!(3, app.world().get::<TestA>(entity).unwrap().current_value());
+}
+
+/// Applies a 1 second effect stacking up to 2 three times, with an overflow effect adding 100.
+/// Asserts that the third application overflows, and that stacks then expire one at a time.
+#[test]
+fn test_stack_overflow_and_expiration() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    // Virtual time advances by at most 250ms per update
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        250,
+    )));
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let stack_changes = changes.clone();
+    let removals = Arc::new(AtomicU32::new(0));
+    let removed = removals.clone();
+    app.world_mut()
+        .entity_mut(entity)
+        .observe(move |event: On<EffectStacksChanged>| {
+            stack_changes
+                .lock()
+                .unwrap()
+                .push((event.reason, event.old, event.new));
+        })
+        .observe(move |_: On<EffectRemoved>| {
+            removed.fetch_add(1, Ordering::Relaxed);
+        });
+
+    let handle = app
+        .world_mut()
+        .run_system_once(|mut ctx: Vitality| {
+            let overflow = ctx.add_effect(
+                EffectBuilder::instant()
+                    .modify::<TestA>(100u32, ModOp::Add, EffectSubject::Target)
+                    .build(),
+            );
+            ctx.add_effect(
+                EffectBuilder::for_seconds(1.0)
+                    .modify_scaled::<TestA>(
+                        1u32,
+                        ModOp::Add,
+                        EffectSubject::Target,
+                        StackScaling::Linear,
+                    )
+                    .with_stacking_policy(EffectStackingPolicy::AddAndRefresh {
+                        count: 1,
+                        max_stack: 2,
+                    })
+                    .with_stack_expiration(StackExpiration::OneStack)
+                    .on_stack_overflow(&overflow)
+                    .build(),
+            )
+        })
+        .unwrap();
+
+    for _ in 0..3 {
+        let handle = handle.clone();
+        app.world_mut()
+            .run_system_once(move |mut ctx: Vitality| {
+                ctx.apply_effect_to_self(entity, &handle);
+            })
+            .unwrap();
+    }
+    app.update();
+    app.update();
+
+    let attribute = app.world().get::<TestA>(entity).unwrap();
+    assert_eq!(100, attribute.base_value());
+    assert_eq!(102, attribute.current_value());
+
+    for _ in 0..12 {
+        app.update();
+    }
+
+    assert_eq!(
+        *changes.lock().unwrap(),
+        vec![
+            (StackChangeReason::Applied, 1, 2),
+            (StackChangeReason::Expired, 2, 1),
+        ]
+    );
+    assert_eq!(1, removals.load(Ordering::Relaxed));
+    assert_eq!(
+        100,
+        app.world().get::<TestA>(entity).unwrap().current_value()
+    );
+}
+
+/// Applies an instant and a permanent effect reading the "amount" parameter, with 42 and 7.
+/// Asserts that the base value is 42, the current value 49, and that the permanent effect
+/// keeps its parameters.
+#[test]
+fn test_effect_params() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            let instant = ctx.add_effect(
+                EffectBuilder::instant()
+                    .modify::<TestA>(
+                        EffectParams::value::<u32>("amount"),
+                        ModOp::Add,
+                        EffectSubject::Target,
+                    )
+                    .build(),
+            );
+            let permanent = ctx.add_effect(
+                EffectBuilder::permanent()
+                    .modify::<TestA>(
+                        EffectParams::value::<u32>("amount"),
+                        ModOp::Add,
+                        EffectSubject::Target,
+                    )
+                    .build(),
+            );
+            ctx.apply_effect_with_params(
+                entity,
+                entity,
+                &instant,
+                EffectParams::from([("amount", 42.0)]),
+            );
+            ctx.apply_effect_with_params(
+                entity,
+                entity,
+                &permanent,
+                EffectParams::new().with("amount", 7.0),
+            );
+        })
+        .unwrap();
+
+    app.update();
+    app.update();
+
+    let attribute = app.world().get::<TestA>(entity).unwrap();
+    assert_eq!(42, attribute.base_value());
+    assert_eq!(49, attribute.current_value());
+
+    let mut query = app.world_mut().query::<(&Effect, &EffectParams)>();
+    let (_, params) = query.single(app.world()).unwrap();
+    assert_eq!(Some(7.0), params.get("amount"));
+}
+
+/// Creates an actor with TestA(u32) at 0, a source with TestA at 10 and a source without it.
+/// Applies an execution draining 4 from the source into the target.
+/// Asserts that both writes are applied without an effect entity,
+/// then that nothing is written when the source lacks TestA.
+#[test]
+fn test_effect_execution() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    let (drain, source, empty) = app
+        .world_mut()
+        .run_system_once(|mut ctx: Vitality| {
+            let drain = ctx.add_effect(
+                EffectBuilder::instant()
+                    .execute(|ctx: &EffectExprContext, output: &mut ExecutionOutput| {
+                        let available = ctx.current_value::<TestA>(EffectSubject::Source);
+                        let amount = ctx.param("amount").unwrap_or(0.0) as u32;
+                        let amount = available.unwrap_or(0).min(amount);
+                        output.sub::<TestA>(EffectSubject::Source, amount);
+                        output.add::<TestA>(EffectSubject::Target, amount);
+                    })
+                    .build(),
+            );
+            let source = ctx
+                .add_spawn_actor(
+                    ActorBuilder::new()
+                        .name("Source".into())
+                        .with::<TestA>(10)
+                        .build(),
+                )
+                .id();
+            let empty = ctx
+                .add_spawn_actor(ActorBuilder::new().name("Empty".into()).build())
+                .id();
+            (drain, source, empty)
+        })
+        .unwrap();
+
+    app.update();
+
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let events = changes.clone();
+    app.world_mut()
+        .entity_mut(entity)
+        .observe(move |event: On<BaseValueChanged<TestA>>| {
+            events.lock().unwrap().push((event.source, event.effect));
+        });
+
+    for source in [source, empty] {
+        let drain = drain.clone();
+        app.world_mut()
+            .run_system_once(move |mut ctx: Vitality| {
+                ctx.apply_effect_with_params(
+                    entity,
+                    source,
+                    &drain,
+                    EffectParams::from([("amount", 4.0)]),
+                );
+            })
+            .unwrap();
+        app.update();
+        app.update();
+    }
+
+    assert_eq!(4, app.world().get::<TestA>(entity).unwrap().current_value());
+    assert_eq!(6, app.world().get::<TestA>(source).unwrap().current_value());
+    // Instant effects have no effect entity
+    assert_eq!(*changes.lock().unwrap(), vec![(source, None)]);
+}
+
+/// Creates an actor with attribute TestA(u32) and another actor as source.
+/// Applies twice an effect adding 10 when it reaches 2 stacks, and 100 and 1 when it expires.
+/// Asserts that the chained effects are applied, all from the original source.
+/// Then despawns a permanent effect adding 1 when removed and asserts that it is applied too.
+#[test]
+fn test_effect_chaining() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    // Virtual time advances by at most 250ms per update
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        250,
+    )));
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    let sources = Arc::new(Mutex::new(Vec::new()));
+    let applied_sources = sources.clone();
+    app.world_mut()
+        .entity_mut(entity)
+        .observe(move |event: On<EffectApplied>| {
+            if event.effect.is_none() {
+                applied_sources.lock().unwrap().push(event.source);
+            }
+        });
+
+    let (handle, removed, source) = app
+        .world_mut()
+        .run_system_once(|mut ctx: Vitality| {
+            let [max_stacks, expired, removed] = [10u32, 100, 1].map(|value| {
+                ctx.add_effect(
+                    EffectBuilder::instant()
+                        .modify::<TestA>(value, ModOp::Add, EffectSubject::Target)
+                        .build(),
+                )
+            });
+            let handle = ctx.add_effect(
+                EffectBuilder::for_seconds(1.0)
+                    .with_stacking_policy(EffectStackingPolicy::Add {
+                        count: 1,
+                        max_stack: 2,
+                    })
+                    .on_max_stacks(&max_stacks)
+                    .on_expire(&expired)
+                    .on_remove(&removed)
+                    .build(),
+            );
+            let source = ctx
+                .add_spawn_actor(ActorBuilder::new().name("Source".into()).build())
+                .id();
+            (handle, removed, source)
+        })
+        .unwrap();
+
+    for _ in 0..2 {
+        let handle = handle.clone();
+        app.world_mut()
+            .run_system_once(move |mut ctx: Vitality| {
+                ctx.apply_effect_to_target(entity, source, &handle);
+            })
+            .unwrap();
+    }
+    app.update();
+    app.update();
+
+    assert_eq!(10, app.world().get::<TestA>(entity).unwrap().current_value());
+
+    for _ in 0..8 {
+        app.update();
+    }
+
+    assert_eq!(111, app.world().get::<TestA>(entity).unwrap().current_value());
+    assert_eq!(vec![source; 3], *sources.lock().unwrap());
+
+    // Despawning an effect directly also applies the effects chained to its removal
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.apply_dynamic_effect_to_target(
+                entity,
+                source,
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .on_remove(&removed)
+                    .build(),
+            );
+        })
+        .unwrap();
+    app.update();
+
+    let effect = app
+        .world()
+        .get::<AppliedEffects>(entity)
+        .unwrap()
+        .iter()
+        .next()
+        .unwrap();
+    app.world_mut().despawn(effect);
+    app.update();
+
+    assert_eq!(
+        112,
+        app.world().get::<TestA>(entity).unwrap().current_value()
+    );
+    assert_eq!(vec![source; 4], *sources.lock().unwrap());
+}
+
+/// Creates an actor with attribute TestA(u32) and applies an effect adding 1 every second,
+/// ticking on application and limited to 4 ticks.
+/// Steps the app in 2.5s frames and asserts that the elapsed periods carry over,
+/// then that the effect expires after its last tick.
+#[test]
+fn test_periodic_effect_ticks() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    app.world_mut()
+        .resource_mut::<Time<Virtual>>()
+        .set_max_delta(Duration::from_secs(5));
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        2500,
+    )));
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            let handle = ctx.add_effect(
+                EffectBuilder::every_second_for_ticks(1.0, 4)
+                    .tick_on_apply()
+                    .modify::<TestA>(1u32, ModOp::Add, EffectSubject::Target)
+                    .build(),
+            );
+            ctx.apply_effect_to_self(entity, &handle);
+        })
+        .unwrap();
+
+    // The tick on application and the two periods elapsed
+    app.update();
+    assert_eq!(3, app.world().get::<TestA>(entity).unwrap().base_value());
+
+    // Three more periods elapsed, but only one tick remains
+    app.update();
+    assert_eq!(4, app.world().get::<TestA>(entity).unwrap().base_value());
+
+    app.update();
+    let mut query = app.world_mut().query::<&Effect>();
+    assert_eq!(0, query.iter(app.world()).count());
+    assert_eq!(4, app.world().get::<TestA>(entity).unwrap().base_value());
+}
+
+/// Creates an actor with attribute TestA(u32) and an effect whose duration is a parameter.
+/// Applies it for 1 second, then refreshes it for 3 seconds.
+/// Asserts that both durations are evaluated from their application.
+#[test]
+fn test_effect_duration_expression() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    app.add_systems(Startup, prepare_actor);
+
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &TestA)>();
+    let (entity, _) = query.single(app.world()).unwrap();
+
+    let handle = app
+        .world_mut()
+        .run_system_once(|mut ctx: Vitality| {
+            ctx.add_effect(
+                EffectBuilder::for_duration(EffectParams::value::<f64>("duration"))
+                    .modify::<TestA>(5u32, ModOp::Add, EffectSubject::Target)
+                    .with_stacking_policy(EffectStackingPolicy::RefreshDuration)
+                    .build(),
+            )
+        })
+        .unwrap();
+
+    for (seconds, expected) in [(1.0, 1.0), (3.0, 3.0)] {
+        let handle = handle.clone();
+        app.world_mut()
+            .run_system_once(move |mut ctx: Vitality| {
+                ctx.apply_effect_with_params(
+                    entity,
+                    entity,
+                    &handle,
+                    EffectParams::new().with("duration", seconds),
+                );
+            })
+            .unwrap();
+        app.update();
+
+        let mut query = app.world_mut().query::<&EffectDuration>();
+        let duration = query.single(app.world()).unwrap();
+        assert_eq!(expected, duration.duration().as_secs_f64());
+    }
+    assert_eq!(5, app.world().get::<TestA>(entity).unwrap().current_value());
+}
+
+/// Creates an actor with attribute TestA(u32) at 10 and two abilities costing 3 and 5 TestA.
+/// Activates the first by its definition and the second by its registered token.
+/// Asserts that only the selected ability is activated each time.
+#[test]
+fn test_activate_ability_by_def() {
+    const BOLT: AbilityToken = AbilityToken::new_static("test.bolt");
+    const STRIKE: AbilityToken = AbilityToken::new_static("test.strike");
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+
+    app.update();
+
+    let (bolt, strike) = app
+        .world_mut()
+        .run_system_once(|mut registry: RegistryMut| {
+            registry.add_ability(BOLT, AbilityBuilder::new().with_cost::<TestA>(3u32).build());
+            registry.add_ability(
+                STRIKE,
+                AbilityBuilder::new().with_cost::<TestA>(5u32).build(),
+            );
+            (registry.ability(&BOLT), registry.ability(&STRIKE))
+        })
+        .unwrap();
+    let actor = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.add_spawn_actor(
+                ActorBuilder::new()
+                    .with::<TestA>(10)
+                    .grant_ability(&bolt)
+                    .grant_ability(&strike)
+                    .build(),
+            )
+            .id()
+        })
+        .unwrap();
+    app.update();
+
+    let bolt = bolt.id();
+    app.world_mut()
+        .run_system_once(move |mut abilities: AbilityContext| {
+            abilities.try_activate_by_def(actor, bolt);
+        })
+        .unwrap();
+    app.update();
+    assert_eq!(7, app.world().get::<TestA>(actor).unwrap().base_value());
+
+    app.world_mut()
+        .run_system_once(move |mut abilities: AbilityContext| {
+            abilities.try_activate_by_token(actor, &STRIKE).unwrap();
+        })
+        .unwrap();
+    app.update();
+    assert_eq!(2, app.world().get::<TestA>(actor).unwrap().base_value());
+}
+
+/// Creates an actor with attribute TestA(u32) at 10, a bolt costing 3 with a cooldown and
+/// blocked by silence, and a burst costing 15.
+/// Asserts that failed activations report the cooldown, the missing cost and the blocking tag.
+#[test]
+fn test_ability_activation_failed() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+
+    app.update();
+
+    let (bolt, burst) = app
+        .world_mut()
+        .run_system_once(|mut abilities: ResMut<Assets<AbilityDef>>| {
+            let bolt = abilities.add(
+                AbilityBuilder::new()
+                    .with_cost::<TestA>(3u32)
+                    .with_cooldown(5.0)
+                    .blocked_by_tag("Status.Silence")
+                    .build(),
+            );
+            let burst = abilities.add(AbilityBuilder::new().with_cost::<TestA>(15u32).build());
+            (bolt, burst)
+        })
+        .unwrap();
+    let (bolt_def, burst_def) = (bolt.id(), burst.id());
+    let actor = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.add_spawn_actor(
+                ActorBuilder::new()
+                    .with::<TestA>(10)
+                    .grant_ability(&bolt)
+                    .grant_ability(&burst)
+                    .build(),
+            )
+            .id()
+        })
+        .unwrap();
+    app.update();
+
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let recorded = failures.clone();
+    app.world_mut()
+        .entity_mut(actor)
+        .observe(move |event: On<AbilityActivationFailed>| {
+            assert_eq!(event.caster, event.entity);
+            recorded.lock().unwrap().push(event.reason.clone());
+        });
+
+    let try_activate = |app: &mut App, definition: AssetId<AbilityDef>| {
+        app.world_mut()
+            .run_system_once(move |mut abilities: AbilityContext| {
+                abilities.try_activate_by_def(actor, definition);
+            })
+            .unwrap();
+        app.update();
+    };
+
+    try_activate(&mut app, bolt_def);
+    assert_eq!(7, app.world().get::<TestA>(actor).unwrap().base_value());
+    assert!(failures.lock().unwrap().is_empty());
+
+    try_activate(&mut app, bolt_def);
+    assert!(matches!(
+        failures.lock().unwrap().pop(),
+        Some(ActivationFailure::OnCooldown { remaining }) if remaining > Duration::ZERO
+    ));
+
+    try_activate(&mut app, burst_def);
+    assert_eq!(
+        Some(ActivationFailure::InsufficientCost {
+            attribute: "TestA".into(),
+            missing: 8.0,
+        }),
+        failures.lock().unwrap().pop()
+    );
+
+    let mut tags = GameplayTags::default();
+    tags.add(GameplayTag::new_static("Status.Silence.Mute"));
+    app.world_mut().entity_mut(actor).insert(tags);
+    try_activate(&mut app, bolt_def);
+    assert_eq!(
+        Some(ActivationFailure::BlockedByTag("Status.Silence".into())),
+        failures.lock().unwrap().pop()
+    );
+    assert_eq!(7, app.world().get::<TestA>(actor).unwrap().base_value());
+}
+
+/// Creates an actor with attribute TestA(u32) at 10 and an ability costing 1 TestA
+/// with 2 charges recharging every second.
+/// Asserts that charges are consumed, block the ability when empty and are restored over time.
+#[test]
+fn test_ability_charges() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    // Virtual time advances by at most 250ms per update
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        250,
+    )));
+
+    app.update();
+
+    let dash = app
+        .world_mut()
+        .run_system_once(|mut abilities: ResMut<Assets<AbilityDef>>| {
+            abilities.add(
+                AbilityBuilder::new()
+                    .with_cost::<TestA>(1u32)
+                    .with_charges(2.0, 1.0)
+                    .build(),
+            )
+        })
+        .unwrap();
+    let dash_def = dash.id();
+    let actor = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.add_spawn_actor(ActorBuilder::new().with::<TestA>(10).grant_ability(&dash).build())
+                .id()
+        })
+        .unwrap();
+    app.update();
+    app.update();
+
+    let mut query = app.world_mut().query::<(Entity, &Charges)>();
+    let (ability, charges) = query.single(app.world()).unwrap();
+    assert_eq!(2, charges.current_value());
+
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let recorded = failures.clone();
+    app.world_mut()
+        .entity_mut(ability)
+        .observe(move |event: On<AbilityActivationFailed>| {
+            recorded.lock().unwrap().push(event.reason.clone());
+        });
+
+    let try_activate = |app: &mut App| {
+        app.world_mut()
+            .run_system_once(move |mut abilities: AbilityContext| {
+                abilities.try_activate_by_def(actor, dash_def);
+            })
+            .unwrap();
+        app.update();
+    };
+
+    try_activate(&mut app);
+    try_activate(&mut app);
+    assert_eq!(8, app.world().get::<TestA>(actor).unwrap().base_value());
+    assert_eq!(0, app.world().get::<Charges>(ability).unwrap().current_value());
+
+    try_activate(&mut app);
+    assert_eq!(8, app.world().get::<TestA>(actor).unwrap().base_value());
+    assert!(matches!(
+        failures.lock().unwrap().pop(),
+        Some(ActivationFailure::NoCharges { recharge_remaining }) if recharge_remaining > Duration::ZERO
+    ));
+
+    // Both charges are restored, one per second
+    for _ in 0..8 {
+        app.update();
+    }
+    assert_eq!(2, app.world().get::<Charges>(ability).unwrap().current_value());
+
+    try_activate(&mut app);
+    assert_eq!(7, app.world().get::<TestA>(actor).unwrap().base_value());
+    assert_eq!(1, app.world().get::<Charges>(ability).unwrap().current_value());
+}
+
+/// Creates an actor with attribute TestA(u32) at 10 and a channeled ability executing every
+/// second, costing 2 TestA when it ends and cancelled by stuns.
+/// Asserts that the ability executes while active, commits its cost when ended
+/// and commits nothing when a stun cancels it.
+#[test]
+fn test_channeled_ability() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    // Virtual time advances by at most 250ms per update
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        250,
+    )));
+
+    app.update();
+
+    let executions = Arc::new(AtomicU32::new(0));
+    let counter = executions.clone();
+    let ends = Arc::new(Mutex::new(Vec::new()));
+    let recorded = ends.clone();
+    let drain = app
+        .world_mut()
+        .run_system_once(move |mut abilities: ResMut<Assets<AbilityDef>>| {
+            let counter = counter.clone();
+            let recorded = recorded.clone();
+            abilities.add(
+                AbilityBuilder::new()
+                    .with_cost::<TestA>(2u32)
+                    .channeled(1.0)
+                    .commit_on(CommitPolicy::OnEnd)
+                    .cancelled_by_tag("Status.Debuff.Stun")
+                    .add_execution(move |_: On<ExecuteAbility>| {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                    })
+                    .add_execution(move |event: On<EndAbility>| {
+                        recorded.lock().unwrap().push(event.cancelled);
+                    })
+                    .build(),
+            )
+        })
+        .unwrap();
+    let drain_def = drain.id();
+    let actor = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.add_spawn_actor(ActorBuilder::new().with::<TestA>(10).grant_ability(&drain).build())
+                .id()
+        })
+        .unwrap();
+    app.update();
+
+    let mut query = app.world_mut().query_filtered::<Entity, With<Ability>>();
+    let ability = query.single(app.world()).unwrap();
+
+    let try_activate = |app: &mut App| {
+        app.world_mut()
+            .run_system_once(move |mut abilities: AbilityContext| {
+                abilities.try_activate_by_def(actor, drain_def);
+            })
+            .unwrap();
+    };
+
+    try_activate(&mut app);
+    for _ in 0..8 {
+        app.update();
+    }
+    assert!(app.world().get::<ActiveAbility>(ability).is_some());
+    assert_eq!(2, executions.load(Ordering::Relaxed));
+    assert_eq!(10, app.world().get::<TestA>(actor).unwrap().base_value());
+
+    app.world_mut()
+        .run_system_once(move |mut abilities: AbilityContext| {
+            abilities.end_ability(ability);
+        })
+        .unwrap();
+    app.update();
+    assert!(app.world().get::<ActiveAbility>(ability).is_none());
+    assert_eq!(8, app.world().get::<TestA>(actor).unwrap().base_value());
+    assert_eq!(vec![false], *ends.lock().unwrap());
+
+    // A stun cancels the ability before it pays its cost
+    try_activate(&mut app);
+    for _ in 0..4 {
+        app.update();
+    }
+    assert_eq!(3, executions.load(Ordering::Relaxed));
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.apply_dynamic_effect_to_self(
+                actor,
+                EffectBuilder::new(EffectApplicationPolicy::Permanent)
+                    .grant_tag("Status.Debuff.Stun")
+                    .build(),
+            );
+        })
+        .unwrap();
+    app.update();
+    app.update();
+    assert!(app.world().get::<ActiveAbility>(ability).is_none());
+    assert_eq!(8, app.world().get::<TestA>(actor).unwrap().base_value());
+    assert_eq!(vec![false, true], *ends.lock().unwrap());
+}
+
+/// Creates an actor with attribute TestA(u32) at 10 and a channeled ability costing 2 TestA
+/// when it begins, interrupted when TestA decreases.
+/// Tries to activate the ability twice in the same frame.
+/// Asserts that it is activated once, that paying its cost does not interrupt it,
+/// and that an effect subtracting 1 from TestA does.
+#[test]
+fn test_interrupted_ability() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    // Virtual time advances by at most 250ms per update
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        250,
+    )));
+
+    app.update();
+
+    let cancels = Arc::new(Mutex::new(Vec::new()));
+    let recorded = cancels.clone();
+    let focus = app
+        .world_mut()
+        .run_system_once(move |mut abilities: ResMut<Assets<AbilityDef>>| {
+            let recorded = recorded.clone();
+            abilities.add(
+                AbilityBuilder::new()
+                    .with_cost::<TestA>(2u32)
+                    .channeled(1.0)
+                    .commit_on(CommitPolicy::OnStart)
+                    .interrupted_by::<TestA>()
+                    .add_execution(move |event: On<AbilityCancel>| {
+                        recorded.lock().unwrap().push(event.reason.clone());
+                    })
+                    .build(),
+            )
+        })
+        .unwrap();
+    let focus_def = focus.id();
+    let actor = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.add_spawn_actor(ActorBuilder::new().with::<TestA>(10).grant_ability(&focus).build())
+                .id()
+        })
+        .unwrap();
+    app.update();
+
+    let mut query = app.world_mut().query_filtered::<Entity, With<Ability>>();
+    let ability = query.single(app.world()).unwrap();
+
+    app.world_mut()
+        .run_system_once(move |mut abilities: AbilityContext| {
+            abilities.try_activate_by_def(actor, focus_def);
+            abilities.try_activate_by_def(actor, focus_def);
+        })
+        .unwrap();
+    for _ in 0..4 {
+        app.update();
+    }
+    assert!(app.world().get::<ActiveAbility>(ability).is_some());
+    assert_eq!(8, app.world().get::<TestA>(actor).unwrap().base_value());
+    assert!(cancels.lock().unwrap().is_empty());
+
+    app.world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.apply_dynamic_effect_to_self(
+                actor,
+                EffectBuilder::instant()
+                    .modify::<TestA>(1u32, ModOp::Sub, EffectSubject::Target)
+                    .build(),
+            );
+        })
+        .unwrap();
+    app.update();
+    app.update();
+    assert!(app.world().get::<ActiveAbility>(ability).is_none());
+    assert_eq!(7, app.world().get::<TestA>(actor).unwrap().base_value());
+    assert_eq!(vec![CancelReason::Interrupted], *cancels.lock().unwrap());
+}
+
+/// Creates an actor with attribute TestA(u32) at 10 and a channeled ability executing every
+/// 0 seconds and costing 2 TestA when it begins.
+/// Asserts that the ability does not begin and pays nothing.
+#[test]
+fn test_invalid_channel_interval() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+
+    app.update();
+
+    let drain = app
+        .world_mut()
+        .run_system_once(|mut abilities: ResMut<Assets<AbilityDef>>| {
+            abilities.add(
+                AbilityBuilder::new()
+                    .with_cost::<TestA>(2u32)
+                    .channeled(0.0)
+                    .build(),
+            )
+        })
+        .unwrap();
+    let drain_def = drain.id();
+    let actor = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.add_spawn_actor(
+                ActorBuilder::new()
+                    .with::<TestA>(10)
+                    .grant_ability(&drain)
+                    .build(),
+            )
+            .id()
+        })
+        .unwrap();
+    app.update();
+
+    let mut query = app.world_mut().query_filtered::<Entity, With<Ability>>();
+    let ability = query.single(app.world()).unwrap();
+
+    app.world_mut()
+        .run_system_once(move |mut abilities: AbilityContext| {
+            abilities.try_activate_by_def(actor, drain_def);
+        })
+        .unwrap();
+    app.update();
+    app.update();
+    assert!(app.world().get::<ActiveAbility>(ability).is_none());
+    assert_eq!(10, app.world().get::<TestA>(actor).unwrap().base_value());
+}
+
+/// Creates an actor with attribute TestA(u32) at 20, a cooldown reduction of 50%, a bolt on
+/// a 2s global cooldown and a potion on a 10s potion cooldown, both costing 1 TestA.
+/// Asserts that the groups block their abilities only, are halved by the reduction,
+/// and can be reduced and reset.
+#[test]
+fn test_cooldown_groups() {
+    const POTION: CooldownGroup = CooldownGroup::new_static("Potion");
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    // Virtual time advances by at most 250ms per update
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        250,
+    )));
+
+    app.update();
+
+    let (bolt, potion) = app
+        .world_mut()
+        .run_system_once(|mut abilities: ResMut<Assets<AbilityDef>>| {
+            let bolt = abilities.add(
+                AbilityBuilder::new()
+                    .with_cost::<TestA>(1u32)
+                    .with_global_cooldown(2.0)
+                    .build(),
+            );
+            let potion = abilities.add(
+                AbilityBuilder::new()
+                    .with_cost::<TestA>(1u32)
+                    .in_cooldown_group(POTION, 10.0)
+                    .build(),
+            );
+            (bolt, potion)
+        })
+        .unwrap();
+    let (bolt_def, potion_def) = (bolt.id(), potion.id());
+    let actor = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| {
+            ctx.add_spawn_actor(
+                ActorBuilder::new()
+                    .with::<TestA>(20)
+                    .with::<CooldownReduction>(0.5)
+                    .grant_ability(&bolt)
+                    .grant_ability(&potion)
+                    .build(),
+            )
+            .id()
+        })
+        .unwrap();
+    app.update();
+
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let recorded = failures.clone();
+    app.world_mut()
+        .entity_mut(actor)
+        .observe(move |event: On<AbilityActivationFailed>| {
+            recorded.lock().unwrap().push(event.reason.clone());
+        });
+
+    let try_activate = |app: &mut App, definition: AssetId<AbilityDef>| {
+        app.world_mut()
+            .run_system_once(move |mut abilities: AbilityContext| {
+                abilities.try_activate_by_def(actor, definition);
+            })
+            .unwrap();
+        app.update();
+    };
+    let remaining = |app: &mut App, group: CooldownGroup| {
+        app.world_mut()
+            .run_system_once(move |abilities: AbilityContext| {
+                abilities.cooldown_group_remaining(actor, &group)
+            })
+            .unwrap()
+    };
+
+    // The global cooldown does not block the potion
+    try_activate(&mut app, bolt_def);
+    try_activate(&mut app, potion_def);
+    assert_eq!(18, app.world().get::<TestA>(actor).unwrap().base_value());
+
+    try_activate(&mut app, bolt_def);
+    assert_eq!(18, app.world().get::<TestA>(actor).unwrap().base_value());
+    assert!(matches!(
+        failures.lock().unwrap().pop(),
+        Some(ActivationFailure::OnGroupCooldown { group, remaining })
+            if group == CooldownGroup::GLOBAL && remaining <= Duration::from_secs(1)
+    ));
+
+    // The halved global cooldown is over after a second
+    for _ in 0..4 {
+        app.update();
+    }
+    try_activate(&mut app, bolt_def);
+    assert_eq!(17, app.world().get::<TestA>(actor).unwrap().base_value());
+
+    // The potion cooldown is halved to 5s
+    let before = remaining(&mut app, POTION);
+    assert!(before > Duration::from_secs(3) && before <= Duration::from_secs(5));
+    app.world_mut()
+        .run_system_once(move |mut abilities: AbilityContext| {
+            abilities.reduce_cooldown_group(actor, &POTION, Duration::from_secs(2));
+        })
+        .unwrap();
+    assert_eq!(before - Duration::from_secs(2), remaining(&mut app, POTION));
+
+    try_activate(&mut app, potion_def);
+    assert!(matches!(
+        failures.lock().unwrap().pop(),
+        Some(ActivationFailure::OnGroupCooldown { group, .. }) if group == POTION
+    ));
+
+    app.world_mut()
+        .run_system_once(move |mut abilities: AbilityContext| {
+            abilities.reset_cooldown_group(actor, &POTION);
+        })
+        .unwrap();
+    try_activate(&mut app, potion_def);
+    assert_eq!(16, app.world().get::<TestA>(actor).unwrap().base_value());
+}
+
+/// Creates an actor with attribute TestA(u32) at 100, granted an ability with a 5 second
+/// cooldown, and a source actor applying a permanent effect adding 5 and twice a poison
+/// subtracting 1 every second for 10 seconds.
+/// Captures the actor once the poison ticked, saves it to RON and restores it on a new entity.
+/// Asserts that the restored actor keeps the current value, the effect sources, remaining
+/// durations, ticks and stacks, and the remaining cooldown of the original.
+#[test]
+fn test_actor_snapshot_round_trip() {
+    const ACTOR: ActorToken = ActorToken::new_static("test.actor");
+    const BUFF: EffectToken = EffectToken::new_static("test.buff");
+    const POISON: EffectToken = EffectToken::new_static("test.poison");
+    const BOLT: AbilityToken = AbilityToken::new_static("test.bolt");
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    // Virtual time advances by at most 250ms per update
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        250,
+    )));
+
+    app.update();
+
+    let (buff, poison) = app
+        .world_mut()
+        .run_system_once(|mut registry: RegistryMut| {
+            registry.add_effect(
+                BUFF,
+                EffectBuilder::permanent()
+                    .modify::<TestA>(5u32, ModOp::Add, EffectSubject::Target)
+                    .build(),
+            );
+            registry.add_effect(
+                POISON,
+                EffectBuilder::every_second_for_duration(1.0, 10.0)
+                    .modify::<TestA>(1u32, ModOp::Sub, EffectSubject::Target)
+                    .with_stacking_policy(EffectStackingPolicy::Add {
+                        count: 1,
+                        max_stack: 3,
+                    })
+                    .build(),
+            );
+            registry.add_ability(BOLT, AbilityBuilder::new().with_cooldown(5.0).build());
+            let bolt = registry.ability(&BOLT);
+            registry.add_actor(
+                ACTOR,
+                ActorBuilder::new().with::<TestA>(100).grant_ability(&bolt).build(),
+            );
+            (registry.effect(&BUFF).clone(), registry.effect(&POISON).clone())
+        })
+        .unwrap();
+    let (actor, source) = app
+        .world_mut()
+        .run_system_once(|mut ctx: Vitality| {
+            (ctx.spawn_actor(&ACTOR).id(), ctx.spawn_actor(&ACTOR).id())
+        })
+        .unwrap();
+    app.update();
+
+    for handle in [buff, poison.clone(), poison] {
+        app.world_mut()
+            .run_system_once(move |mut ctx: Vitality| {
+                ctx.apply_effect_to_target(actor, source, &handle);
+            })
+            .unwrap();
+        app.update();
+    }
+    app.world_mut()
+        .run_system_once(move |mut abilities: AbilityContext| {
+            abilities.try_activate_by_token(actor, &BOLT).unwrap();
+        })
+        .unwrap();
+    for _ in 0..3 {
+        app.update();
+    }
+
+    let snapshot = ActorSnapshot::capture(app.world(), actor).unwrap();
+    let saved = ron::to_string(&snapshot).unwrap();
+    let snapshot: ActorSnapshot = ron::from_str(&saved).unwrap();
+    let restored = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| ctx.restore_actor(snapshot.clone()).id())
+        .unwrap();
+    app.update();
+
+    // The current value, the effects sorted by stacks and the remaining cooldown of an actor
+    let state = |world: &World, actor: Entity| {
+        let mut effects = world
+            .get::<AppliedEffects>(actor)
+            .unwrap()
+            .iter()
+            .map(|effect| {
+                let effect = world.entity(effect);
+                (
+                    effect.get::<Stacks>().unwrap().current_value(),
+                    effect.get::<EffectSource>().unwrap().0,
+                    effect.get::<EffectDuration>().map(|duration| duration.remaining()),
+                    effect.get::<EffectTicker>().map(|ticker| ticker.ticks()),
+                )
+            })
+            .collect::<Vec<_>>();
+        effects.sort_by_key(|(stacks, ..)| *stacks);
+        let ability = world.get::<GrantedAbilities>(actor).unwrap().iter().next().unwrap();
+        let cooldown = world.get::<AbilityCooldown>(ability).unwrap().timer().remaining();
+        let value = world.get::<TestA>(actor).unwrap().current_value();
+        (value, effects, cooldown)
+    };
+
+    let original = state(app.world(), actor);
+    let (value, effects, cooldown) = &original;
+    assert_eq!(104, *value);
+    assert!(matches!(
+        effects.as_slice(),
+        [(1, buff_source, None, None), (2, poison_source, Some(remaining), Some(1))]
+            if *buff_source == source && *poison_source == source && !remaining.is_zero()
+    ));
+    assert!(!cooldown.is_zero());
+    assert_eq!(original, state(app.world(), restored));
+}
+
+/// Creates an actor granted an ability with 2 charges recharging every second.
+/// Consumes a charge, captures the actor halfway through the recharge and restores it.
+/// Asserts that the restored ability keeps its charges and recharge progress.
+#[test]
+fn test_ability_charges_snapshot() {
+    const ACTOR: ActorToken = ActorToken::new_static("test.actor");
+    const DASH: AbilityToken = AbilityToken::new_static("test.dash");
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), AttributesPlugin));
+    app.add_plugins(init_attribute::<TestA>);
+    // Virtual time advances by at most 250ms per update
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        250,
+    )));
+
+    app.update();
+
+    app.world_mut()
+        .run_system_once(|mut registry: RegistryMut| {
+            registry.add_ability(DASH, AbilityBuilder::new().with_charges(2.0, 1.0).build());
+            let dash = registry.ability(&DASH);
+            registry.add_actor(ACTOR, ActorBuilder::new().grant_ability(&dash).build());
+        })
+        .unwrap();
+    let actor = app
+        .world_mut()
+        .run_system_once(|mut ctx: Vitality| ctx.spawn_actor(&ACTOR).id())
+        .unwrap();
+    app.update();
+    app.update();
+
+    app.world_mut()
+        .run_system_once(move |mut abilities: AbilityContext| {
+            abilities.try_activate_by_token(actor, &DASH).unwrap();
+        })
+        .unwrap();
+    app.update();
+    app.update();
+
+    let snapshot = ActorSnapshot::capture(app.world(), actor).unwrap();
+    let saved = ron::to_string(&snapshot).unwrap();
+    let snapshot: ActorSnapshot = ron::from_str(&saved).unwrap();
+    let restored = app
+        .world_mut()
+        .run_system_once(move |mut ctx: Vitality| ctx.restore_actor(snapshot.clone()).id())
+        .unwrap();
+    app.update();
+
+    // The charges of the ability and the time until the next one
+    let charges = |world: &World, actor: Entity| {
+        let ability = world.get::<GrantedAbilities>(actor).unwrap().iter().next().unwrap();
+        (
+            world.get::<Charges>(ability).unwrap().current_value(),
+            world.get::<AbilityCharges>(ability).unwrap().recharge_remaining(),
+        )
+    };
+
+    let (count, remaining) = charges(app.world(), actor);
+    assert_eq!(1, count);
+    assert!(!remaining.is_zero() && remaining < Duration::from_secs(1));
+    assert_eq!((count, remaining), charges(app.world(), restored));
+}